@@ -1,12 +1,448 @@
-pub fn handle_create_cluster(provider: String, nodes: Option<i32>, _timeout: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Creating cluster with provider: {} and {:?} nodes", provider, nodes);
-    // TODO: Implement cluster creation logic
+use chrono::Utc;
+use comfy_table::{Cell, Table};
+use gml_core::ClusterRequest;
+use gml_core::{NodeDetails, NodeRequest};
+use gml_core::error::{ErrorKind, GmlError};
+use gml_core::journal::{self, Operation};
+use gml_core::paths;
+use gml_core::state::{GmlState, NewNode, NodeEntry};
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use crate::config::{self, Config};
+use crate::daemon;
+use crate::hooks::{self, Hook};
+use crate::launch;
+use crate::node;
+use crate::prompt;
+use crate::providers;
+use crate::spinner;
+
+/// What `gml cluster create` reports about the members once the cluster is up
+pub struct MemberOutput {
+    /// Print the members as JSON on stdout instead of a table
+    pub json: bool,
+    /// Add a `gml-<node id>` host entry for each member to ~/.ssh/config
+    pub ssh_config: bool,
+    /// Write the members' addresses to this file, one per line
+    pub hostfile: Option<PathBuf>,
+}
+
+/// The `gml cluster create` flags that describe the members, when no spec file is given
+pub struct ClusterFlags {
+    pub provider: Option<String>,
+    /// Named account in the provider's config to launch in
+    pub account: Option<String>,
+    pub instance_type: Option<String>,
+    pub nodes: Option<i32>,
+    pub timeout: Option<String>,
+    pub regions: Vec<String>,
+}
+
+pub async fn handle_create_cluster(
+    flags: ClusterFlags,
+    yes: bool,
+    dry_run: bool,
+    output: &MemberOutput,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let ClusterFlags { provider, account, instance_type, nodes, timeout, regions } = flags;
+    let provider = provider.ok_or("--provider is required unless --spec-file is given")?;
+    let instance_type = instance_type.ok_or("--instance-type is required unless --spec-file is given")?;
+    // Members are only ever cleaned up by their timeout, so a cluster can't be created without one
+    let timeout = timeout.ok_or("--timeout is required unless --spec-file is given")?;
+    let timeout_duration = node::parse_timeout_duration(&timeout)
+        .ok_or_else(|| format!("Invalid timeout format: {}", timeout))?;
+    let node_count = match nodes {
+        Some(n) if n < 1 => return Err("A cluster needs at least one node".into()),
+        Some(n) => n as usize,
+        None => 1,
+    };
+
+    let config = config::parse_config()?;
+    let provider_config = config.get_provider_account(&provider, account.as_deref())?;
+    // Providers without a cluster primitive get their members launched one by one below
+    let cluster_handle = match providers::create_cluster_handle(&provider, provider_config, None) {
+        Ok(handle) => Some(handle),
+        Err(e) if e.kind() == ErrorKind::Unsupported => None,
+        Err(e) => return Err(Box::from(e)),
+    };
+
+    let spinner = spinner::create_spinner();
+    let mut request = ClusterRequest {
+        node_count,
+        regions,
+        member: NodeRequest {
+            instance_type: instance_type.clone(),
+            region: None,
+            zone: provider_config.zone.clone(),
+            subnet: provider_config.subnet.clone(),
+            disk_gb: provider_config.disk_gb,
+            public_key: None,
+            on_status: Some(spinner::status_callback(&spinner)),
+            on_launched: None,
+        },
+        launch_slots: Some(launch::shared_slots(&config)),
+    };
+    let region_for = |index: usize| {
+        request.region_for(index).map(str::to_string).or_else(|| provider_config.region.clone())
+    };
+    let member_regions: Vec<Option<String>> = (0..node_count).map(region_for).collect();
+
+    // A node handle for the checks and lookups that work per member rather than per cluster
+    let node_handle = providers::create_provider_handle(
+        &provider,
+        provider_config,
+        member_regions[0].clone(),
+        config.ssh_public_key.clone(),
+    )
+        .await
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    spinner.set_message("Validating request...");
+    node_handle.validate_request(&request.member)
+        .await
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    spinner.set_message("Estimating cost...");
+    let mut cost_summary = None;
+    let hourly_cost = match node_handle.estimate_cost(&request.member).await {
+        Ok(estimate) => {
+            let hourly = estimate.hourly * node_count as f64;
+            let total = hourly * timeout_duration.to_std().unwrap_or_default().as_secs_f64() / 3600.0;
+            let summary = format!(
+                "~{}/hr for {} node(s), ~{} over the {} timeout",
+                node::format_cost(hourly, &estimate.currency), node_count,
+                node::format_cost(total, &estimate.currency), timeout
+            );
+            let question = format!("This will cost {}. Continue?", summary);
+            cost_summary = Some(summary);
+            if !dry_run && !spinner.suspend(|| prompt::confirm(&question, yes))? {
+                spinner.finish_with_message("Aborted");
+                return Ok(());
+            }
+            // Stored prices are summed across nodes, so only keep them in a single currency
+            (estimate.currency == "USD").then_some(estimate.hourly)
+        }
+        Err(e) => {
+            spinner.println(format!("Warning: could not estimate cost: {}", e));
+            None
+        }
+    };
+
+    if request.is_multi_region() {
+        spinner.println(
+            "Warning: members span multiple regions, so they will communicate over public IPs with \
+             cross-region latency; expect slower collective operations than a single-region cluster"
+        );
+    }
+
+    if dry_run {
+        spinner.finish_and_clear();
+        match &account {
+            Some(account) => println!("Cluster with {} member(s) on {} (account {}):", node_count, provider, account),
+            None => println!("Cluster with {} member(s) on {}:", node_count, provider),
+        }
+        for (index, region) in member_regions.iter().enumerate() {
+            println!("  node {}: {} in {}", index, instance_type, region.as_deref().unwrap_or("the default region"));
+        }
+        let ssh_key = provider_config.ssh_key.as_ref().map(|key| key.to_string())
+            .or_else(|| config.ssh_public_key.clone())
+            .unwrap_or_else(|| "default public key in ~/.ssh".to_string());
+        println!("SSH key: {}", ssh_key);
+        if let Some(summary) = cost_summary {
+            println!("Estimated cost: {}", summary);
+        }
+        match node_handle.describe_launch(&request.member) {
+            Ok(api_call) => {
+                println!("API call for node 0:");
+                for line in api_call.lines() {
+                    println!("  {}", line);
+                }
+            }
+            Err(e) => println!("API call: can't be shown: {}", e),
+        }
+        println!("Nothing was launched (--dry-run)");
+        return Ok(());
+    }
+
+    daemon::ensure_daemon_running(&spinner).await?;
+
+    // Journal each member as the provider accepts it, so `gml resume` can terminate the instances
+    // if we die before they are saved. Members of a multi-region cluster report their own region;
+    // the others are all in the first member's.
+    let operations: Arc<Mutex<Vec<Operation>>> = Arc::new(Mutex::new(Vec::new()));
+    request.member.on_launched = Some({
+        let operations = operations.clone();
+        let provider = provider.clone();
+        let account = account.clone();
+        let default_region = member_regions[0].clone();
+        let spinner = spinner.clone();
+        Arc::new(move |provider_id: &str, region: Option<&str>| {
+            let region = region.map(str::to_string).or_else(|| default_region.clone());
+            let operation = Operation::launch(&provider, region, None).in_account(account.clone());
+            let journaled = journal::record(&operation).and_then(|_| operation.launched(provider_id));
+            if let Err(e) = journaled {
+                spinner.println(format!("Warning: could not journal launch: {}", e));
+            }
+            operations.lock().unwrap().push(operation);
+        })
+    });
+
+    spinner.set_message(format!("Launching {} node(s) with provider {}...", node_count, provider));
+    let result = match &cluster_handle {
+        Some(cluster_handle) => cluster_handle.create_cluster(request).await.map(|details| details.nodes),
+        None => launch_members(&provider, provider_config, &config, &member_regions, &request).await,
+    };
+    // After a failed launch every member the provider accepted has been stopped, or is named in
+    // the error as possibly still running
+    let finish_operations = || -> Result<(), GmlError> {
+        operations.lock().unwrap().iter().try_for_each(|operation| operation.done())
+    };
+    let nodes = match result {
+        Ok(nodes) => nodes,
+        Err(e) => {
+            spinner.finish_and_clear();
+            finish_operations()?;
+            return Err(format!("Cluster creation failed: {}", e.message()).into());
+        }
+    };
+
+    let user = node_handle.get_user()
+        .await
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    spinner.set_message("Saving state...");
+    let cluster_id = uuid::Uuid::new_v4().to_string();
+    let expiration = (Utc::now() + timeout_duration).to_rfc3339();
+    let members = GmlState::update(|state| {
+        let mut members = Vec::new();
+        for (details, region) in nodes.iter().zip(&member_regions) {
+            members.push(state.add_node(NewNode {
+                details: details.clone(),
+                name: None,
+                provider: provider.clone(),
+                region: region.clone(),
+                instance_type: instance_type.clone(),
+                timeout: Some(expiration.clone()),
+                soft_timeout: None,
+                user: user.clone(),
+                tags: HashMap::new(),
+                reserved: false,
+                hourly_cost,
+                disk_gb: None,
+                cluster_id: Some(cluster_id.clone()),
+                identity_file: None,
+                account: account.clone(),
+            })?);
+        }
+        state.add_cluster(cluster_id.clone(), provider.clone(), members.len(), Some(expiration.clone()), members.clone())?;
+        Ok(members)
+    })
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+    finish_operations()?;
+
+    spinner.finish_with_message(format!("Cluster {} created", cluster_id));
+    report_members(&cluster_id, &members, output)
+}
+
+/// Launch every member as an independent node with the provider's `start_node`, in parallel up to
+/// the request's launch slots. If any launch fails, the members that did start are stopped again.
+async fn launch_members(
+    provider: &str,
+    provider_config: &config::ProviderConfig,
+    config: &config::Config,
+    member_regions: &[Option<String>],
+    request: &ClusterRequest,
+) -> Result<Vec<NodeDetails>, GmlError> {
+    // A handle per member, since the region is fixed per handle
+    let mut handles = Vec::new();
+    for region in member_regions {
+        handles.push(providers::create_provider_handle(provider, provider_config, region.clone(), config.ssh_public_key.clone()).await?);
+    }
+    // The region also goes on each request, so the member's launch is journaled with it
+    let launches = handles.iter()
+        .zip(member_regions)
+        .map(|(handle, region)| (handle.as_ref(), NodeRequest { region: region.clone(), ..request.member.clone() }))
+        .collect();
+    gml_core::start_nodes_with_rollback(launches, request.launch_slots.as_deref()).await
+}
+
+/// Print how to reach each member of a newly created cluster, in member order, and write the
+/// SSH config entries and hostfile that were asked for
+pub fn report_members(cluster_id: &str, member_ids: &[String], output: &MemberOutput) -> Result<(), Box<dyn std::error::Error>> {
+    let state = GmlState::load()?;
+    let members: Vec<&NodeEntry> = member_ids.iter()
+        .map(|id| state.node(id).ok_or_else(|| format!("Member {} is missing from state", id)))
+        .collect::<Result<_, _>>()?;
+
+    let mut aliases = Vec::new();
+    if output.ssh_config {
+        let home_dir = paths::home_dir()?;
+        for member in &members {
+            aliases.push(node::configure_ssh_host(&home_dir, member)?);
+        }
+    }
+    if let Some(path) = &output.hostfile {
+        let hostfile: String = members.iter().map(|member| format!("{}\n", member.ip)).collect();
+        fs::write(path, hostfile).map_err(|e| format!("Failed to write hostfile {}: {}", path.display(), e))?;
+    }
+
+    if output.json {
+        let members: Vec<serde_json::Value> = members.iter().enumerate().map(|(index, member)| serde_json::json!({
+            "index": index,
+            "id": member.id,
+            "provider_id": member.provider_id,
+            "ip": member.ip,
+            "user": member.user,
+            "ssh_port": member.ssh_port.unwrap_or(22),
+            "region": member.region,
+            "instance_type": member.instance_type,
+            "ssh": ssh_command(member),
+            "ssh_host": aliases.get(index),
+        })).collect();
+        let report = serde_json::json!({ "cluster_id": cluster_id, "members": members });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    let mut headers = vec!["#", "Node ID", "IP", "User", "SSH"];
+    if output.ssh_config {
+        headers.push("SSH Host");
+    }
+    table.set_header(headers);
+    for (index, member) in members.iter().enumerate() {
+        let mut row = vec![
+            Cell::new(index),
+            Cell::new(&member.id),
+            Cell::new(&member.ip),
+            Cell::new(&member.user),
+            Cell::new(ssh_command(member)),
+        ];
+        if let Some(alias) = aliases.get(index) {
+            row.push(Cell::new(alias));
+        }
+        table.add_row(row);
+    }
+    println!("{}", table);
+    if let Some(path) = &output.hostfile {
+        println!("Wrote member addresses to {}", path.display());
+    }
     Ok(())
 }
 
-pub fn handle_delete_cluster(provider: String, _cluster_id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Deleting cluster with provider: {}", provider);
-    // TODO: Implement cluster deletion logic
+fn ssh_command(node: &NodeEntry) -> String {
+    format!("ssh{} {}@{}", node::ssh_options(node.ssh_port, node.identity_file.as_deref()), node.user, node.ip)
+}
+
+/// Delete every member node of a cluster, then the cluster itself.
+///
+/// A member that fails to stop is reported and skipped, so one API error doesn't leave the rest
+/// of the cluster running. Members with an open gml session are skipped too unless `force` is
+/// set, as `gml node delete` does. Until every member is gone the cluster entry is kept, listing
+/// only the members that are left, so running the command again (or the daemon) retries them.
+pub async fn handle_delete_cluster(cluster_id: String, provider: Option<String>, force: bool, yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let state = GmlState::load()?;
+    let cluster = state.cluster(&cluster_id)
+        .cloned()
+        .ok_or_else(|| format!("Cluster with ID '{}' not found", cluster_id))?;
+    if let Some(provider) = &provider {
+        if provider != &cluster.provider {
+            return Err(format!(
+                "Cluster '{}' was created on {}, not {}",
+                cluster_id, cluster.provider, provider
+            ).into());
+        }
+    }
+
+    let mut members: Vec<NodeEntry> = state.list_cluster_nodes(&cluster_id).into_iter().cloned().collect();
+    // Clusters created before nodes recorded their cluster are only linked through the member list
+    for id in &cluster.members {
+        if !members.iter().any(|member| &member.id == id) {
+            members.extend(state.node(id).cloned());
+        }
+    }
+
+    println!("Cluster {} has {} member node(s):", cluster_id, members.len());
+    for member in &members {
+        match member.active_session() {
+            Some(pid) => println!(
+                "  {} ({} {} at {}, in use by gml process {})",
+                member.id, member.provider, member.instance_type, member.ip, pid
+            ),
+            None => println!("  {} ({} {} at {})", member.id, member.provider, member.instance_type, member.ip),
+        }
+    }
+    let question = format!("Delete cluster {}? This terminates every member instance", cluster_id);
+    if !prompt::confirm(&question, yes)? {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let config = config::parse_config()?;
+    let spinner = spinner::create_spinner();
+    let mut in_use = Vec::new();
+    let mut failed = Vec::new();
+    for (index, member) in members.iter().enumerate() {
+        if let Some(pid) = member.active_session() {
+            if !force {
+                spinner.suspend(|| println!("  Skipped {}: in use by gml process {}", member.id, pid));
+                in_use.push(member.id.clone());
+                continue;
+            }
+        }
+        spinner.set_message(format!("Deleting member {} of {} ({})...", index + 1, members.len(), member.id));
+        match delete_member(&config, member, &spinner).await {
+            // Printed to stdout, unlike the spinner, so the outcome for each node is always visible
+            Ok(()) => spinner.suspend(|| println!("  Deleted {}", member.id)),
+            Err(e) => {
+                spinner.suspend(|| println!("  Failed to delete {}: {}", member.id, e));
+                failed.push(member.id.clone());
+            }
+        }
+    }
+
+    if !failed.is_empty() || !in_use.is_empty() {
+        spinner.finish_and_clear();
+        GmlState::update(|state| {
+            if let Some(entry) = state.clusters.iter_mut().find(|c| c.id == cluster_id) {
+                entry.members.retain(|id| failed.contains(id) || in_use.contains(id));
+                entry.node_count = entry.members.len();
+            }
+            Ok(())
+        })?;
+        let mut reasons = Vec::new();
+        if !in_use.is_empty() {
+            reasons.push(format!("{} in use by a gml session; pass --force to delete them anyway", in_use.len()));
+        }
+        if !failed.is_empty() {
+            reasons.push(format!("{} could not be deleted", failed.len()));
+        }
+        return Err(format!(
+            "{} of {} member node(s) were kept ({}); run `gml cluster delete {}` again to retry them",
+            failed.len() + in_use.len(), members.len(), reasons.join(", "), cluster_id
+        ).into());
+    }
+
+    GmlState::update(|state| state.remove_cluster(&cluster_id))?;
+    spinner.finish_with_message(format!("Cluster {} deleted ({} member node(s))", cluster_id, members.len()));
     Ok(())
 }
 
+/// Stop one member with its provider and forget it, running the delete hooks around it
+async fn delete_member(config: &Config, member: &NodeEntry, spinner: &ProgressBar) -> Result<(), Box<dyn std::error::Error>> {
+    let hook_env = [
+        ("GML_NODE_ID", member.id.clone()),
+        ("GML_NODE_IP", member.ip.clone()),
+        ("GML_NODE_PROVIDER", member.provider.clone()),
+    ];
+    spinner.suspend(|| hooks::run(&config.hooks, Hook::PreDelete, &hook_env))?;
+
+    let provider_handle = node::provider_handle_for_node(config, member).await?;
+    node::terminate_node(provider_handle.as_ref(), member).await?;
+
+    spinner.suspend(|| hooks::run(&config.hooks, Hook::PostDelete, &hook_env))
+}
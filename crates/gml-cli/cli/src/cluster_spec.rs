@@ -0,0 +1,435 @@
+//! Declarative cluster definitions for `gml cluster create --spec-file`.
+//!
+//! The whole file is checked before anything is launched: the TOML itself, the provider config,
+//! the timeout, and, where the provider can tell us, instance types and capacity. Members are then
+//! launched concurrently, and if any of them fails the ones that did come up are terminated.
+
+use chrono::Utc;
+use futures::future;
+use gml_core::{NodeProvider, NodeRequest, NodeType};
+use gml_core::state::GmlState;
+use gml_core::suggest;
+use indicatif::ProgressBar;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::cluster::{self, MemberOutput};
+use crate::config::{self, Config};
+use crate::daemon;
+use crate::node::{self, LaunchedNode, NodeRecord};
+use crate::prompt;
+use crate::providers;
+use crate::sh;
+use crate::spinner;
+
+/// A cluster spec file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClusterSpec {
+    pub provider: String,
+    /// Named account in the provider's config to launch in, instead of the default one
+    pub account: Option<String>,
+    /// How long until the daemon deletes the members (e.g. "4h")
+    pub timeout: String,
+    /// Regions to spread members without their own `region` across, round-robin
+    #[serde(default)]
+    pub regions: Vec<String>,
+    /// Tags applied to every member
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    #[serde(default)]
+    pub ssh: SshSpec,
+    #[serde(rename = "member", default)]
+    pub members: Vec<MemberSpec>,
+}
+
+/// `[ssh]` options for connecting members to each other
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SshSpec {
+    /// Give every member a shared key and `gml-<n>` host aliases for the others
+    #[serde(default)]
+    pub mesh: bool,
+}
+
+/// A `[[member]]` table: `count` identical nodes
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MemberSpec {
+    #[serde(rename = "instance-type")]
+    pub instance_type: String,
+    #[serde(default = "default_count")]
+    pub count: usize,
+    pub region: Option<String>,
+    pub zone: Option<String>,
+    pub subnet: Option<String>,
+    #[serde(rename = "disk-gb")]
+    pub disk_gb: Option<u32>,
+    /// Tags for these members, on top of the cluster-wide tags
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+}
+
+fn default_count() -> usize {
+    1
+}
+
+/// One node to launch, with the spec's defaults resolved
+struct PlannedNode {
+    index: usize,
+    instance_type: String,
+    region: Option<String>,
+    zone: Option<String>,
+    subnet: Option<String>,
+    disk_gb: Option<u32>,
+    tags: HashMap<String, String>,
+    hourly_cost: Option<f64>,
+}
+
+impl PlannedNode {
+    fn request(&self, spinner: &ProgressBar) -> NodeRequest {
+        NodeRequest {
+            instance_type: self.instance_type.clone(),
+            region: self.region.clone(),
+            zone: self.zone.clone(),
+            subnet: self.subnet.clone(),
+            disk_gb: self.disk_gb,
+            public_key: None,
+            on_status: Some(spinner::status_callback(spinner)),
+            on_launched: None,
+        }
+    }
+}
+
+pub async fn handle_create_from_spec(
+    path: &Path,
+    yes: bool,
+    dry_run: bool,
+    output: &MemberOutput,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let spec: ClusterSpec = toml::from_str(&contents)
+        .map_err(|e| format!("Invalid cluster spec {}: {}", path.display(), e))?;
+    let config = config::parse_config()?;
+
+    let spinner = spinner::create_spinner();
+    spinner.set_message("Validating cluster spec...");
+    let (mut planned, handles) = match validate(&spec, &config).await {
+        Ok(validated) => validated,
+        Err(errors) => {
+            spinner.finish_and_clear();
+            let list: Vec<String> = errors.iter().map(|e| format!("  - {}", e)).collect();
+            return Err(format!("Invalid cluster spec {}:\n{}", path.display(), list.join("\n")).into());
+        }
+    };
+    let timeout = node::parse_timeout_duration(&spec.timeout).expect("timeout was validated");
+
+    spinner.set_message("Estimating cost...");
+    let mut hourly_total = Some(0.0);
+    let mut currency = String::from("USD");
+    for node in planned.iter_mut() {
+        let handle = &handles[&node.region];
+        match handle.estimate_cost(&node.request(&spinner)).await {
+            Ok(estimate) => {
+                hourly_total = hourly_total.map(|total| total + estimate.hourly);
+                // Stored prices are summed across nodes, so only keep them in a single currency
+                node.hourly_cost = (estimate.currency == "USD").then_some(estimate.hourly);
+                currency = estimate.currency;
+            }
+            Err(e) => {
+                spinner.println(format!("Warning: could not estimate cost of {}: {}", node.instance_type, e));
+                hourly_total = None;
+            }
+        }
+    }
+    spinner.finish_and_clear();
+
+    println!("Cluster with {} member(s) on {}:", planned.len(), spec.provider);
+    for node in &planned {
+        println!(
+            "  node {}: {} in {}",
+            node.index,
+            node.instance_type,
+            node.region.as_deref().unwrap_or("the default region")
+        );
+    }
+    if gml_core::spans_multiple_regions(planned.iter().filter_map(|node| node.region.as_ref())) {
+        eprintln!(
+            "Warning: members span multiple regions, so they will communicate over public IPs with \
+             cross-region latency; expect slower collective operations than a single-region cluster"
+        );
+    }
+
+    if dry_run {
+        if let Some(hourly) = hourly_total {
+            println!("Estimated cost: ~{}/hr", node::format_cost(hourly, &currency));
+        }
+        println!("Spec is valid; nothing was launched (--dry-run)");
+        return Ok(());
+    }
+
+    match hourly_total {
+        Some(hourly) => {
+            let total = hourly * timeout.to_std().unwrap_or_default().as_secs_f64() / 3600.0;
+            let question = format!(
+                "This will cost ~{}/hr, ~{} over the {} timeout. Continue?",
+                node::format_cost(hourly, &currency), node::format_cost(total, &currency), spec.timeout
+            );
+            if !prompt::confirm(&question, yes)? {
+                println!("Aborted");
+                return Ok(());
+            }
+        }
+        None => eprintln!("Warning: launching without a complete cost estimate"),
+    }
+
+    let spinner = spinner::create_spinner();
+    daemon::ensure_daemon_running(&spinner).await?;
+
+    spinner.set_message(format!("Launching {} member(s)...", planned.len()));
+    let expiration = (Utc::now() + timeout).to_rfc3339();
+    let cluster_id = uuid::Uuid::new_v4().to_string();
+    let launches = planned.iter().map(|node| {
+        let record = NodeRecord {
+            name: None,
+            provider: spec.provider.clone(),
+            region: node.region.clone(),
+            timeout: Some(expiration.clone()),
+            soft_timeout: None,
+            tags: node.tags.clone(),
+            reserved: false,
+            hourly_cost: node.hourly_cost,
+            cluster_id: Some(cluster_id.clone()),
+            identity_file: None,
+            account: spec.account.clone(),
+        };
+        node::launch_node(handles[&node.region].as_ref(), &config, &spinner, node.request(&spinner), record)
+    });
+    let results = future::join_all(launches).await;
+
+    let mut launched: Vec<(&PlannedNode, LaunchedNode)> = Vec::new();
+    let mut failures: Vec<String> = Vec::new();
+    for (node, result) in planned.iter().zip(results) {
+        match result {
+            Ok(member) => launched.push((node, member)),
+            Err(e) => failures.push(format!("node {} ({}): {}", node.index, node.instance_type, e)),
+        }
+    }
+
+    if failures.is_empty() && spec.ssh.mesh {
+        spinner.set_message("Setting up SSH between members...");
+        let members: Vec<&LaunchedNode> = launched.iter().map(|(_, member)| member).collect();
+        if let Err(e) = setup_ssh_mesh(&members).await {
+            failures.push(format!("SSH setup: {}", e));
+        }
+    }
+
+    if !failures.is_empty() {
+        spinner.set_message(format!("Rolling back {} launched member(s)...", launched.len()));
+        let mut leftovers = Vec::new();
+        for (node, member) in &launched {
+            if let Err(e) = rollback(handles[&node.region].as_ref(), &member.id).await {
+                leftovers.push(format!("{} ({})", member.id, e));
+            }
+        }
+        spinner.finish_and_clear();
+
+        let mut message = format!("Cluster creation failed:\n  - {}", failures.join("\n  - "));
+        if leftovers.is_empty() {
+            message.push_str(&format!("\nRolled back {} member(s) that had launched", launched.len()));
+        } else {
+            message.push_str(&format!(
+                "\nThese members could not be terminated and are still tracked by gml; delete them with `gml node delete`: {}",
+                leftovers.join(", ")
+            ));
+        }
+        return Err(message.into());
+    }
+
+    let members: Vec<String> = launched.iter().map(|(_, member)| member.id.clone()).collect();
+    GmlState::update(|state| state.add_cluster(cluster_id.clone(), spec.provider.clone(), members.len(), Some(expiration), members.clone()))
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    spinner.finish_with_message(format!("Cluster {} created", cluster_id));
+    cluster::report_members(&cluster_id, &members, output)
+}
+
+/// Check the whole spec against config and the provider, collecting every problem rather than
+/// stopping at the first. On success, returns the expanded members and a provider handle per region.
+async fn validate(
+    spec: &ClusterSpec,
+    config: &Config,
+) -> Result<(Vec<PlannedNode>, HashMap<Option<String>, Box<dyn NodeProvider>>), Vec<String>> {
+    let mut errors = Vec::new();
+
+    if config.get_provider(&spec.provider).is_none() {
+        return Err(vec![format!("provider '{}' is not configured in ~/.gml/config.toml", spec.provider)]);
+    }
+    let provider_config = config.get_provider_account(&spec.provider, spec.account.as_deref())
+        .map_err(|e| vec![e.to_string()])?;
+    if node::parse_timeout_duration(&spec.timeout).is_none() {
+        errors.push(format!("invalid timeout '{}'; use formats like '1h30m', '2h', '30m'", spec.timeout));
+    }
+    if spec.members.is_empty() {
+        errors.push("no [[member]] tables; a cluster needs at least one node".to_string());
+    }
+    for (i, member) in spec.members.iter().enumerate() {
+        if member.count == 0 {
+            errors.push(format!("member {} ({}): count must be at least 1", i, member.instance_type));
+        }
+    }
+
+    // Expand members into nodes; spec regions are assigned round-robin to members without their own
+    let mut planned = Vec::new();
+    for member in &spec.members {
+        for _ in 0..member.count {
+            let index = planned.len();
+            let region = member.region.clone()
+                .or_else(|| gml_core::round_robin_region(&spec.regions, index).map(str::to_string))
+                .or_else(|| provider_config.region.clone());
+            let mut tags = spec.tags.clone();
+            tags.extend(member.tags.clone());
+            planned.push(PlannedNode {
+                index,
+                instance_type: member.instance_type.clone(),
+                region,
+                zone: member.zone.clone().or_else(|| provider_config.zone.clone()),
+                subnet: member.subnet.clone().or_else(|| provider_config.subnet.clone()),
+                disk_gb: member.disk_gb.or(provider_config.disk_gb),
+                tags,
+                hourly_cost: None,
+            });
+        }
+    }
+
+    let mut handles: HashMap<Option<String>, Box<dyn NodeProvider>> = HashMap::new();
+    for node in &planned {
+        if handles.contains_key(&node.region) {
+            continue;
+        }
+        match providers::create_provider_handle(&spec.provider, provider_config, node.region.clone(), config.ssh_public_key.clone()).await {
+            Ok(handle) => {
+                handles.insert(node.region.clone(), handle);
+            }
+            Err(e) => push_unique(&mut errors, e.message()),
+        }
+    }
+
+    // Instance types and capacity, for providers that list them
+    let mut node_types: HashMap<Option<String>, Vec<NodeType>> = HashMap::new();
+    for (region, handle) in &handles {
+        if let Ok(types) = handle.list_node_types().await {
+            node_types.insert(region.clone(), types);
+        }
+    }
+    for node in &planned {
+        let Some(types) = node_types.get(&node.region) else {
+            continue;
+        };
+        match types.iter().find(|t| t.name == node.instance_type) {
+            // Without a region from the spec or config the provider picks one, so any will do
+            Some(node_type) => {
+                let (available, region) = match node.region.as_deref() {
+                    Some(region) => (node_type.available_in(region), region),
+                    None => (node_type.has_capacity(), "any region"),
+                };
+                if !available {
+                    push_unique(&mut errors, format!("{} has no capacity in {} right now", node.instance_type, region));
+                }
+            }
+            None => {
+                let names: Vec<&str> = types.iter().map(|t| t.name.as_str()).collect();
+                let mut message = format!("unknown instance type '{}'", node.instance_type);
+                if let Some(closest) = suggest::closest_matches(&node.instance_type, &names).first() {
+                    message.push_str(&format!("; did you mean '{}'?", closest));
+                }
+                push_unique(&mut errors, message);
+            }
+        }
+    }
+
+    for node in &planned {
+        if let Some(handle) = handles.get(&node.region) {
+            if let Err(e) = handle.validate_request(&node.request(&ProgressBar::hidden())).await {
+                push_unique(&mut errors, e.message());
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Ok((planned, handles))
+    } else {
+        Err(errors)
+    }
+}
+
+fn push_unique(errors: &mut Vec<String>, error: String) {
+    if !errors.contains(&error) {
+        errors.push(error);
+    }
+}
+
+async fn rollback(provider_handle: &dyn NodeProvider, node_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let node = GmlState::load()?.node(node_id).cloned()
+        .ok_or_else(|| format!("Node with ID '{}' not found", node_id))?;
+    node::terminate_node(provider_handle, &node).await
+}
+
+/// Let members SSH to each other: every member gets the same fresh key pair, trusts it, and has
+/// `gml-<n>` host aliases for all members in `~/.ssh/config`
+async fn setup_ssh_mesh(members: &[&LaunchedNode]) -> Result<(), Box<dyn std::error::Error>> {
+    let dir = std::env::temp_dir().join(format!("gml-mesh-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir)?;
+    let result = distribute_mesh_key(&dir, members).await;
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+async fn distribute_mesh_key(dir: &Path, members: &[&LaunchedNode]) -> Result<(), Box<dyn std::error::Error>> {
+    let key = dir.join("gml_cluster");
+    sh::run(&format!("ssh-keygen -q -t ed25519 -N '' -C gml-cluster -f {}", key.display()))?;
+
+    let mut ssh_config = String::from("\n# Added by gml cluster create\n");
+    for (index, member) in members.iter().enumerate() {
+        ssh_config.push_str(&format!(
+            "Host gml-{}\n    HostName {}\n    User {}\n    IdentityFile ~/.ssh/gml_cluster\n    StrictHostKeyChecking no\n",
+            index, member.ip, member.user
+        ));
+        if let Some(port) = member.ssh_port {
+            ssh_config.push_str(&format!("    Port {}\n", port));
+        }
+    }
+    let config_file = dir.join("config");
+    fs::write(&config_file, ssh_config)?;
+
+    for member in members {
+        let port = member.ssh_port.unwrap_or(22);
+        let ssh_opts = format!("-o StrictHostKeyChecking=no -o ConnectTimeout=10 -p {}", port);
+        let scp_opts = format!("-o StrictHostKeyChecking=no -o ConnectTimeout=10 -P {}", port);
+        let target = format!("{}@{}", member.user, member.ip);
+        wait_for_ssh(&ssh_opts, &target).await?;
+        sh::run(&format!("scp -q {} {} {}.pub {}:.ssh/", scp_opts, key.display(), key.display(), target))?;
+        sh::run(&format!(
+            "ssh {} {} 'cat ~/.ssh/gml_cluster.pub >> ~/.ssh/authorized_keys && cat >> ~/.ssh/config && chmod 600 ~/.ssh/config' < {}",
+            ssh_opts, target, config_file.display()
+        ))?;
+    }
+    Ok(())
+}
+
+/// Nodes can report active shortly before sshd accepts connections
+async fn wait_for_ssh(ssh_opts: &str, target: &str) -> Result<(), Box<dyn std::error::Error>> {
+    const ATTEMPTS: u32 = 6;
+    let cmd = format!("ssh {} {} 'mkdir -p ~/.ssh && chmod 700 ~/.ssh' 2>/dev/null", ssh_opts, target);
+    for attempt in 1..=ATTEMPTS {
+        match sh::run(&cmd) {
+            Ok(()) => return Ok(()),
+            Err(_) if attempt < ATTEMPTS => tokio::time::sleep(std::time::Duration::from_secs(10)).await,
+            Err(e) => return Err(format!("could not reach {}: {}", target, e).into()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use clap::CommandFactory;
+use clap_complete::Shell;
+use gml_core::state::GmlState;
+use std::io;
+
+/// Print a completion script for `shell` to stdout.
+///
+/// With `dynamic`, the script also completes node IDs for single-node commands by calling
+/// `gml __complete-nodes`, which only reads local state and so stays fast.
+pub fn handle_completions_command(shell: Shell, dynamic: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let dynamic_script = if dynamic {
+        Some(match shell {
+            Shell::Bash => BASH_DYNAMIC,
+            Shell::Zsh => ZSH_DYNAMIC,
+            Shell::Fish => FISH_DYNAMIC,
+            _ => return Err(format!("--dynamic is not supported for {}", shell).into()),
+        })
+    } else {
+        None
+    };
+
+    clap_complete::generate(shell, &mut crate::Args::command(), "gml", &mut io::stdout());
+    if let Some(script) = dynamic_script {
+        print!("{}", script);
+    }
+    Ok(())
+}
+
+/// Print one `<id>\t<description>` line per tracked node, for shell completion scripts
+pub fn handle_complete_nodes() -> Result<(), Box<dyn std::error::Error>> {
+    for node in GmlState::load()?.nodes {
+        println!("{}\t{} {} {}", node.id, node.provider, node.instance_type, node.ip);
+    }
+    Ok(())
+}
+
+const BASH_DYNAMIC: &str = r#"
+_gml_dynamic() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    local words=" ${COMP_WORDS[*]:1:COMP_CWORD-1} "
+    local complete_id=0
+
+    case "$words" in
+        " connect "|*" node delete "*|*" node describe "*|*" node ping "*|*" node restart-workspace "*|*" node ssh "*)
+            [[ "$cur" != -* ]] && complete_id=1 ;;
+        *" node timeout "*)
+            [[ "$prev" == "--id" || "$prev" == "-i" ]] && complete_id=1 ;;
+    esac
+
+    if (( complete_id )); then
+        COMPREPLY=( $(compgen -W "$(gml __complete-nodes 2>/dev/null | cut -f1)" -- "$cur") )
+        return 0
+    fi
+    _gml "$@"
+}
+complete -F _gml_dynamic -o bashdefault -o default gml
+"#;
+
+const ZSH_DYNAMIC: &str = r#"
+_gml_dynamic() {
+    local words_before=" ${words[2,CURRENT-1]} "
+    local complete_id=0
+
+    if [[ "$words_before" == " connect " || "$words_before" == *" node "(delete|describe|ping|restart-workspace|ssh)" "* ]]; then
+        [[ "${words[CURRENT]}" != -* ]] && complete_id=1
+    elif [[ "$words_before" == *" node timeout "* && ( "${words[CURRENT-1]}" == "--id" || "${words[CURRENT-1]}" == "-i" ) ]]; then
+        complete_id=1
+    fi
+
+    if (( complete_id )); then
+        local -a nodes
+        nodes=(${(f)"$(gml __complete-nodes 2>/dev/null | sed 's/\t/:/')"})
+        _describe 'node' nodes
+        return
+    fi
+    _gml "$@"
+}
+compdef _gml_dynamic gml
+"#;
+
+const FISH_DYNAMIC: &str = r#"
+complete -c gml -n "__fish_seen_subcommand_from connect" -f -a "(gml __complete-nodes 2>/dev/null)"
+complete -c gml -n "__fish_seen_subcommand_from node; and __fish_seen_subcommand_from delete describe ping restart-workspace ssh" -f -a "(gml __complete-nodes 2>/dev/null)"
+complete -c gml -n "__fish_seen_subcommand_from node; and __fish_seen_subcommand_from timeout" -l id -s i -x -a "(gml __complete-nodes 2>/dev/null)"
+"#;
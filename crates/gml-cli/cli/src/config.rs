@@ -1,15 +1,119 @@
+use gml_core::BootPolling;
+use gml_core::error::GmlError;
+use gml_core::paths;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
+use std::time::Duration;
 
-const CONFIG_PATH: &str = "~/.gml/config.toml";
+const CONFIG_FILE: &str = "config.toml";
+
+/// Providers that can be configured from environment variables alone, with no table in the file
+const KNOWN_PROVIDERS: &[&str] = &["lambda", "google", "runpod"];
+
+/// Provider keys that a `GML_<TABLE>_<SUFFIX>` environment variable overrides, as
+/// (config key, variable suffix)
+const ENV_OVERRIDES: &[(&str, &str)] = &[
+    ("api-key", "API_KEY"),
+    ("ssh-key-name", "SSH_KEY"),
+    ("region", "REGION"),
+    ("project", "PROJECT"),
+];
+
+/// Written by `gml config init`. Every key is commented out, so the file parses as is and
+/// `gml config validate` points at what still has to be filled in.
+const CONFIG_TEMPLATE: &str = r#"# gml configuration. Uncomment and fill in the keys for the providers you use,
+# then run `gml config validate`.
+
+[gml]
+# Public key used by `gml connect` and given to new nodes (default: your key in ~/.ssh)
+# ssh-public-key = "~/.ssh/id_ed25519.pub"
+# Cap on provider launch calls in flight at once
+# max-parallel-launches = 4
+# How long `gml node delete` waits before deleting, so it can be undone with `gml node undo`
+# delete-grace-period = "5m"
+
+[lambda]
+# API key from the Lambda console. `gml config set-secret lambda.api-key` keeps it in the OS
+# keyring instead, and GML_LAMBDA_API_KEY is read when it isn't set here.
+# api-key = "..."
+# Name of an SSH key registered in your Lambda account, or a list of them to authorize each one on
+# new nodes, e.g. ["laptop", "ci"] (required)
+# ssh-key-name = "..."
+# Region to launch in, e.g. us-east-1; --region overrides it (required)
+# region = "us-east-1"
+# How long to wait for a node to boot, and how often to check on it
+# boot-timeout = "10m"
+# poll-interval = "10s"
+# Tries per Lambda API call that fails transiently
+# max-attempts = 3
+# Where `gml node logs` reads from: journald, or a log file path on the node
+# log-source = "journald"
+
+# [daemon]
+# gmld POSTs a JSON message here whenever it deletes a node on timeout, e.g. a Slack incoming webhook
+# webhook-url = "https://hooks.slack.com/services/..."
+
+# [google]
+# project = "my-project"
+# region = "us-central2-b"
+
+# [runpod]
+# api-key = "..."
+"#;
 
 #[derive(Debug)]
 pub struct Config {
     providers: HashMap<String, ProviderConfig>,
     /// From `[gml] ssh-public-key` — path to the SSH public key used for `connect` and Google TPU metadata.
     pub ssh_public_key: Option<String>,
+    /// From `[gml] max-parallel-launches` — cap on provider launch calls in flight at once.
+    pub max_parallel_launches: Option<usize>,
+    /// From `[gml] assume-yes` — answer every confirmation prompt with yes, for automation.
+    pub assume_yes: bool,
+    /// From `[gml] delete-grace-period` — how long `gml node delete` waits before the daemon
+    /// deletes the node, so it can be undone. Zero (the default) deletes immediately.
+    pub delete_grace_period: Duration,
+    /// From `[gml] compress-state` — store the state as `state.json.gz`. When unset, whichever
+    /// form is already on disk is kept.
+    pub compress_state: Option<bool>,
+    templates: HashMap<String, NodeTemplate>,
+    /// From `[hooks]` — local commands run around node creation and deletion.
+    pub hooks: Hooks,
+}
+
+/// Shell commands from the `[hooks]` table, run locally around node lifecycle operations
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Hooks {
+    #[serde(rename = "pre-create")]
+    pub pre_create: Option<String>,
+    #[serde(rename = "post-create")]
+    pub post_create: Option<String>,
+    #[serde(rename = "pre-delete")]
+    pub pre_delete: Option<String>,
+    #[serde(rename = "post-delete")]
+    pub post_delete: Option<String>,
+    #[serde(rename = "soft-timeout")]
+    pub soft_timeout: Option<String>,
+}
+
+/// Saved `gml node create` parameters, from a `[template.<name>]` table
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct NodeTemplate {
+    pub provider: Option<String>,
+    #[serde(rename = "instance-type")]
+    pub instance_type: Option<String>,
+    pub region: Option<String>,
+    pub zone: Option<String>,
+    pub subnet: Option<String>,
+    #[serde(rename = "disk-gb")]
+    pub disk_gb: Option<u32>,
+    pub timeout: Option<String>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    #[serde(default)]
+    pub reserved: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -17,11 +121,109 @@ pub struct ProviderConfig {
     #[serde(rename = "api-key")]
     pub api_key: Option<String>,
     #[serde(rename = "ssh-key-name")]
-    pub ssh_key: Option<String>,
+    pub ssh_key: Option<SshKeyNames>,
     #[serde(rename = "region")]
     pub region: Option<String>,
     #[serde(rename = "project")]
     pub project: Option<String>,
+    /// Default availability zone for launches, overridden by `--zone`
+    #[serde(rename = "zone")]
+    pub zone: Option<String>,
+    /// Default subnet for launches, overridden by `--subnet`
+    #[serde(rename = "subnet")]
+    pub subnet: Option<String>,
+    /// Default disk size in GB, overridden by `--disk`
+    #[serde(rename = "disk-gb")]
+    pub disk_gb: Option<u32>,
+    /// How long to wait for a launched node to come up (e.g. "20m"), see [`ProviderConfig::boot_polling`]
+    #[serde(rename = "boot-timeout")]
+    pub boot_timeout: Option<String>,
+    /// How often to check on a launching node (e.g. "5s")
+    #[serde(rename = "poll-interval")]
+    pub poll_interval: Option<String>,
+    /// Tries per provider API call before a transient failure is reported (Lambda only)
+    #[serde(rename = "max-attempts")]
+    pub max_attempts: Option<u32>,
+    /// Where `gml node logs` reads from: `journald`, or the path of a log file on the node
+    #[serde(rename = "log-source")]
+    pub log_source: Option<String>,
+    /// Name of the `[<provider>.<account>]` table this config was read from; `None` for the
+    /// provider's own table, the default account
+    #[serde(skip)]
+    pub account: Option<String>,
+    /// Named accounts, each already merged with the keys of the provider's own table
+    #[serde(skip)]
+    accounts: HashMap<String, ProviderConfig>,
+}
+
+/// `ssh-key-name`: one registered key name, or a list of them
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum SshKeyNames {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl SshKeyNames {
+    pub fn names(&self) -> Vec<String> {
+        match self {
+            SshKeyNames::One(name) => vec![name.clone()],
+            SshKeyNames::Many(names) => names.clone(),
+        }
+    }
+}
+
+impl std::fmt::Display for SshKeyNames {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.names().join(", "))
+    }
+}
+
+impl ProviderConfig {
+    /// Config table name without brackets, e.g. `lambda` or `lambda.work`. Also names the
+    /// account's keyring entry and `GML_<NAME>_API_KEY` variable.
+    pub fn table_name(&self, provider: &str) -> String {
+        match &self.account {
+            Some(account) => format!("{}.{}", provider, account),
+            None => provider.to_string(),
+        }
+    }
+
+    /// Required keys that aren't set by the file or the environment, or `None` for a provider gml
+    /// doesn't support. A `keyring:` API key counts as set without reading the keyring.
+    pub fn missing_keys(&self, provider: &str) -> Option<Vec<String>> {
+        let api_key_set = self.api_key.is_some();
+        let required: Vec<(&str, bool)> = match provider {
+            "lambda" => vec![
+                ("api-key", api_key_set),
+                ("ssh-key-name", self.ssh_key.as_ref().is_some_and(|key| !key.names().is_empty())),
+                ("region", self.region.is_some()),
+            ],
+            "google" => vec![("project", self.project.is_some() || std::env::var("GOOGLE_CLOUD_PROJECT").is_ok())],
+            "runpod" => vec![("api-key", api_key_set)],
+            _ => return None,
+        };
+        Some(required.into_iter().filter(|(_, set)| !set).map(|(key, _)| key.to_string()).collect())
+    }
+
+    /// `boot-timeout` and `poll-interval`, falling back to the defaults for whichever is unset
+    pub fn boot_polling(&self) -> Result<BootPolling, GmlError> {
+        let parse = |key: &str, value: &Option<String>| {
+            value.as_deref()
+                .map(|value| humantime::parse_duration(value)
+                    .map_err(|e| GmlError::from(format!("Invalid {} '{}': {}", key, value, e))))
+                .transpose()
+        };
+        let defaults = BootPolling::default();
+        let polling = BootPolling {
+            timeout: parse("boot-timeout", &self.boot_timeout)?.unwrap_or(defaults.timeout),
+            interval: parse("poll-interval", &self.poll_interval)?.unwrap_or(defaults.interval),
+        };
+        if polling.interval.is_zero() {
+            return Err(GmlError::from("poll-interval must be greater than zero"));
+        }
+        Ok(polling)
+    }
 }
 
 impl Config {
@@ -30,36 +232,163 @@ impl Config {
         self.providers.get(name)
     }
 
+    /// The config for `account` of a provider, or its default account when `account` is `None`
+    pub fn get_provider_account(&self, name: &str, account: Option<&str>) -> Result<&ProviderConfig, Box<dyn std::error::Error>> {
+        let provider_config = self.get_provider(name)
+            .ok_or_else(|| format!(
+                "Provider '{}' not found in config; add a [{}] table or set GML_{}_* environment variables",
+                name, name, name.to_uppercase()
+            ))?;
+        let Some(account) = account else {
+            return Ok(provider_config);
+        };
+        provider_config.accounts.get(account).ok_or_else(|| {
+            let mut names: Vec<&str> = provider_config.accounts.keys().map(String::as_str).collect();
+            names.sort();
+            let available = if names.is_empty() { "none".to_string() } else { names.join(", ") };
+            format!("Account '{}' not found under [{}] in config; accounts: {}", account, name, available).into()
+        })
+    }
+
     /// Get all provider names
     pub fn provider_names(&self) -> Vec<&String> {
         self.providers.keys().collect()
     }
+
+    /// Every provider's default account followed by its named accounts, sorted by table name
+    pub fn provider_accounts(&self) -> Vec<(&String, &ProviderConfig)> {
+        let mut accounts: Vec<(&String, &ProviderConfig)> = self.providers
+            .iter()
+            .flat_map(|(name, default)| std::iter::once(default).chain(default.accounts.values()).map(move |c| (name, c)))
+            .collect();
+        accounts.sort_by_key(|(name, provider_config)| provider_config.table_name(name));
+        accounts
+    }
+
+    /// Get a node template by name
+    pub fn get_template(&self, name: &str) -> Option<&NodeTemplate> {
+        self.templates.get(name)
+    }
 }
 
 #[derive(Debug, Deserialize)]
 struct GmlSection {
     #[serde(rename = "ssh-public-key")]
     ssh_public_key: Option<String>,
+    #[serde(rename = "max-parallel-launches")]
+    max_parallel_launches: Option<usize>,
+    #[serde(rename = "assume-yes", default)]
+    assume_yes: bool,
+    #[serde(rename = "delete-grace-period")]
+    delete_grace_period: Option<String>,
+    #[serde(rename = "compress-state")]
+    compress_state: Option<bool>,
+}
+
+/// Environment variable overriding `key` of a provider table: `GML_<TABLE>_<SUFFIX>`, where the
+/// table is e.g. `lambda` or `lambda.work` and `key` is a config key such as `ssh-key-name`
+pub fn env_var_name(table: &str, key: &str) -> String {
+    let suffix = ENV_OVERRIDES.iter()
+        .find(|(config_key, _)| *config_key == key)
+        .map_or(key, |(_, suffix)| suffix);
+    format!("GML_{}_{}", table, suffix).to_uppercase().replace(['-', '.'], "_")
+}
+
+/// `key` as it is given for the `table` provider table, for error messages: the config key
+/// followed by the environment variable that overrides it
+pub fn key_sources(table: &str, key: &str) -> String {
+    format!("{} under [{}] in config or {}", key, table, env_var_name(table, key))
+}
+
+/// Write the non-empty `GML_<TABLE>_<SUFFIX>` variables into `table`, so they take precedence
+/// over what the file says
+fn apply_env_overrides(table_name: &str, table: &mut toml::Table) {
+    for (key, _) in ENV_OVERRIDES {
+        if let Some(value) = std::env::var(env_var_name(table_name, key)).ok().filter(|v| !v.is_empty()) {
+            table.insert(key.to_string(), toml::Value::String(value));
+        }
+    }
 }
 
-fn expand_tilde(path: &str) -> PathBuf {
-    if path.starts_with("~/") {
-        if let Some(home) = std::env::var_os("HOME") {
-            return PathBuf::from(home).join(&path[2..]);
+/// Replace `${VAR}` in every string under `value` with the environment variable `VAR`, so the
+/// file can refer to secrets it doesn't contain. `path` is the dotted key of `value`, for errors.
+fn expand_env_refs(value: &mut toml::Value, path: &str) -> Result<(), String> {
+    match value {
+        toml::Value::String(text) => *text = expand_env_refs_in(text).map_err(|e| format!("Config key {} {}", path, e))?,
+        toml::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                expand_env_refs(item, &format!("{}[{}]", path, i))?;
+            }
         }
+        toml::Value::Table(table) => {
+            for (key, item) in table.iter_mut() {
+                expand_env_refs(item, &format!("{}.{}", path, key))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn expand_env_refs_in(text: &str) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let reference = &rest[start + 2..];
+        let end = reference.find('}').ok_or("has a '${' without a closing '}'")?;
+        let name = &reference[..end];
+        let value = std::env::var(name)
+            .map_err(|_| format!("refers to ${{{}}}, which is not set in the environment", name))?;
+        expanded.push_str(&value);
+        rest = &reference[end + 1..];
     }
-    PathBuf::from(path)
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Path to `~/.gml/config.toml`
+pub fn config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    Ok(paths::gml_dir()?.join(CONFIG_FILE))
 }
 
 pub fn parse_config() -> Result<Config, Box<dyn std::error::Error>> {
-    let config_path = expand_tilde(CONFIG_PATH);
-    let config_content = fs::read_to_string(&config_path)?;
+    let config_path = config_path()?;
+    // Without a file, providers can still be configured entirely from environment variables
+    let config_content = match fs::read_to_string(&config_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
     
     // Parse the entire TOML as a table of tables
-    let toml_value: toml::Value = toml::from_str(&config_content)?;
+    let mut toml_value: toml::Value = toml::from_str(&config_content)?;
+    if let toml::Value::Table(root_table) = &mut toml_value {
+        for (key, value) in root_table.iter_mut() {
+            // Hooks are shell commands, where `${VAR}` is for the shell to expand when they run
+            if key != "hooks" {
+                expand_env_refs(value, key)?;
+            }
+        }
+        for provider in KNOWN_PROVIDERS {
+            if !root_table.contains_key(*provider) {
+                let mut table = toml::Table::new();
+                apply_env_overrides(provider, &mut table);
+                if !table.is_empty() {
+                    root_table.insert(provider.to_string(), toml::Value::Table(table));
+                }
+            }
+        }
+    }
     
     let mut providers = HashMap::new();
     let mut ssh_public_key = None;
+    let mut max_parallel_launches = None;
+    let mut assume_yes = false;
+    let mut delete_grace_period = Duration::ZERO;
+    let mut compress_state = None;
+    let mut templates = HashMap::new();
+    let mut hooks = Hooks::default();
     
     // Extract all top-level tables (provider blocks)
     if let toml::Value::Table(root_table) = toml_value {
@@ -68,19 +397,54 @@ pub fn parse_config() -> Result<Config, Box<dyn std::error::Error>> {
             let table_str = toml::to_string(&table_value)?;
             let gml: GmlSection = toml::from_str(&table_str)?;
             ssh_public_key = gml.ssh_public_key;
+            max_parallel_launches = gml.max_parallel_launches;
+            assume_yes = gml.assume_yes;
+            compress_state = gml.compress_state;
+            if let Some(grace) = gml.delete_grace_period {
+                delete_grace_period = humantime::parse_duration(&grace)
+                    .map_err(|e| format!("Invalid delete-grace-period '{}': {}", grace, e))?;
+            }
+        }
+
+        if let Some(template_table) = root_table.get("template") {
+            templates = template_table.clone().try_into()
+                .map_err(|e| format!("Invalid [template] section: {}", e))?;
+        }
+
+        if let Some(hooks_table) = root_table.get("hooks") {
+            hooks = hooks_table.clone().try_into()
+                .map_err(|e| format!("Invalid [hooks] section: {}", e))?;
         }
 
         for (key, value) in root_table {
-            if key == "gml" {
+            // [daemon] is read by gmld itself
+            if key == "gml" || key == "template" || key == "hooks" || key == "daemon" {
                 continue;
             }
             // Try to deserialize each table as a ProviderConfig
             if let toml::Value::Table(table) = value {
+                // Sub-tables such as [lambda.work] are named accounts; the rest is the default account
+                let (account_tables, mut base): (toml::Table, toml::Table) = table
+                    .into_iter()
+                    .partition(|(_, value)| value.is_table());
+                apply_env_overrides(&key, &mut base);
                 // Create a new TOML value with just this table and deserialize it
-                let table_value = toml::Value::Table(table);
+                let table_value = toml::Value::Table(base.clone());
                 let table_str = toml::to_string(&table_value)?;
                 match toml::from_str::<ProviderConfig>(&table_str) {
-                    Ok(provider_config) => {
+                    Ok(mut provider_config) => {
+                        for (account, account_table) in account_tables {
+                            let mut merged = base.clone();
+                            if let toml::Value::Table(account_table) = account_table {
+                                merged.extend(account_table);
+                            }
+                            apply_env_overrides(&format!("{}.{}", key, account), &mut merged);
+                            let mut account_config: ProviderConfig = toml::Value::Table(merged)
+                                .try_into()
+                                .map_err(|e| format!("Invalid [{}.{}] section: {}", key, account, e))?;
+                            account_config.account = Some(account.clone());
+                            provider_config.accounts.insert(account, account_config);
+                        }
                         providers.insert(key, provider_config);
                     }
                     Err(_) => {
@@ -95,13 +459,197 @@ pub fn parse_config() -> Result<Config, Box<dyn std::error::Error>> {
     Ok(Config {
         providers,
         ssh_public_key,
+        max_parallel_launches,
+        assume_yes,
+        delete_grace_period,
+        compress_state,
+        templates,
+        hooks,
     })
 }
 
+/// Write `template` to `[template.<name>]` in the config file, replacing any existing template
+/// of that name. The rest of the file, including comments and formatting, is left untouched.
+pub fn save_template(name: &str, template: &NodeTemplate) -> Result<(), Box<dyn std::error::Error>> {
+    let mut doc = load_document()?;
+
+    let mut table = toml_edit::Table::new();
+    let fields = [
+        ("provider", &template.provider),
+        ("instance-type", &template.instance_type),
+        ("region", &template.region),
+        ("zone", &template.zone),
+        ("subnet", &template.subnet),
+        ("timeout", &template.timeout),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            table[key] = toml_edit::value(value.as_str());
+        }
+    }
+    if let Some(disk_gb) = template.disk_gb {
+        table["disk-gb"] = toml_edit::value(i64::from(disk_gb));
+    }
+    if template.reserved {
+        table["reserved"] = toml_edit::value(true);
+    }
+    if !template.tags.is_empty() {
+        let mut tags: Vec<_> = template.tags.iter().collect();
+        tags.sort();
+        let mut inline = toml_edit::InlineTable::new();
+        for (key, value) in tags {
+            inline.insert(key, value.as_str().into());
+        }
+        table["tags"] = toml_edit::value(inline);
+    }
+
+    let templates = doc.entry("template").or_insert_with(|| {
+        let mut templates = toml_edit::Table::new();
+        templates.set_implicit(true);
+        toml_edit::Item::Table(templates)
+    });
+    let templates = templates.as_table_mut().ok_or("[template] in config is not a table")?;
+    templates.insert(name, toml_edit::Item::Table(table));
+
+    write_document(&doc)
+}
+
+/// Set `key` in the `[<provider>]` table of the config file, creating the table if needed.
+/// Like [`save_template`], this leaves the rest of the file untouched.
+pub fn set_provider_value(provider: &str, key: &str, value: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let mut doc = load_document()?;
+    // `provider` may name an account table such as `lambda.work`
+    let mut table = doc.as_table_mut();
+    for part in provider.split('.') {
+        table = table.entry(part)
+            .or_insert_with(toml_edit::table)
+            .as_table_mut()
+            .ok_or_else(|| format!("[{}] in config is not a table", provider))?;
+    }
+    table[key] = toml_edit::value(value);
+    write_document(&doc)
+}
+
+/// The config file as an editable document that preserves comments and formatting
+fn load_document() -> Result<toml_edit::DocumentMut, Box<dyn std::error::Error>> {
+    let config_content = match fs::read_to_string(config_path()?) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(config_content.parse()?)
+}
+
+fn write_document(doc: &toml_edit::DocumentMut) -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = config_path()?;
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&config_path, doc.to_string())?;
+    Ok(())
+}
+
+/// Write a commented starter config to `~/.gml/config.toml`, refusing to replace an existing
+/// file unless `force` is set
+pub fn handle_init_command(force: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config_path = config_path()?;
+    if config_path.exists() && !force {
+        return Err(format!("{} already exists; pass --force to overwrite it", config_path.display()).into());
+    }
+    if let Some(parent) = config_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(&config_path, CONFIG_TEMPLATE)?;
+    println!("Wrote {}", config_path.display());
+    println!("Fill in the [lambda] section (or another provider's), then run `gml config validate`.");
+    Ok(())
+}
+
+/// Parse the config and report, without calling any provider, whether each provider table has
+/// the keys gml needs to use it. Fails if any of them is incomplete.
+pub fn handle_validate_command() -> Result<(), Box<dyn std::error::Error>> {
+    let config = parse_config()?;
+    let config_path = config_path()?;
+    if config_path.exists() {
+        println!("Config: ok ({})", config_path.display());
+    } else {
+        println!("Config: no file at {}, using environment variables only", config_path.display());
+    }
+
+    let accounts = config.provider_accounts();
+    if accounts.is_empty() {
+        return Err("No providers are configured; add a [lambda], [google] or [runpod] table, or set e.g. GML_LAMBDA_API_KEY".into());
+    }
+    let mut incomplete = 0;
+    for (provider, provider_config) in accounts {
+        let name = provider_config.table_name(provider);
+        match provider_config.missing_keys(provider) {
+            Some(missing) if missing.is_empty() => println!("{}: fully configured", name),
+            Some(missing) => {
+                let sources: Vec<String> = missing.iter()
+                    .map(|key| format!("{} ({})", key, env_var_name(&name, key)))
+                    .collect();
+                println!("{}: missing {}", name, sources.join(", "));
+                incomplete += 1;
+            }
+            None => {
+                println!("{}: not a supported provider (expected lambda, google or runpod)", name);
+                incomplete += 1;
+            }
+        }
+    }
+
+    if incomplete > 0 {
+        return Err(format!("{} provider table(s) are incomplete", incomplete).into());
+    }
+    Ok(())
+}
+
 pub fn parse_config_for_provider(provider: &str) -> Result<ProviderConfig, Box<dyn std::error::Error>> {
     let config = parse_config()?;
     config
         .get_provider(provider)
         .cloned()
         .ok_or_else(|| format!("Provider '{}' not found in config", provider).into())
-}
\ No newline at end of file
+}
+
+/// Parse the config and check each provider's settings against the provider itself. For now
+/// that is the configured `region`, which is compared with the provider's live region list.
+pub async fn handle_check_command() -> Result<(), Box<dyn std::error::Error>> {
+    let config = parse_config()?;
+    println!("Config: ok ({})", config_path()?.display());
+
+    let mut problems = 0;
+    for (provider, provider_config) in config.provider_accounts() {
+        let name = provider_config.table_name(provider);
+        let Some(region) = &provider_config.region else {
+            println!("{}: ok (no region set)", name);
+            continue;
+        };
+        let handle = match crate::providers::create_provider_handle(provider, provider_config, None, config.ssh_public_key.clone()).await {
+            Ok(handle) => handle,
+            Err(e) => {
+                println!("{}: error: {}", name, e);
+                problems += 1;
+                continue;
+            }
+        };
+        match handle.list_regions().await {
+            Ok(regions) if regions.contains(region) => println!("{}: ok (region {})", name, region),
+            Ok(regions) => {
+                println!("{}: error: {}", name, gml_core::unknown_region_message(region, &regions));
+                problems += 1;
+            }
+            Err(GmlError::Unsupported(_)) => println!("{}: ok (region {} not checked, the provider can't list its regions)", name, region),
+            Err(e) => {
+                println!("{}: error: could not list regions: {}", name, e);
+                problems += 1;
+            }
+        }
+    }
+
+    if problems > 0 {
+        return Err(format!("{} problem(s) found in {}", problems, config_path()?.display()).into());
+    }
+    Ok(())
+}
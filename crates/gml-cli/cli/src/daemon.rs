@@ -0,0 +1,132 @@
+use gml_core::daemon_pid;
+use gml_core::paths;
+use indicatif::ProgressBar;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+/// How often `gml daemon logs --follow` checks the log for new lines
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Start `gmld` unless its PID file names a live daemon. Two commands racing here may both start
+/// one; the second exits as soon as it finds the PID file claimed.
+pub async fn ensure_daemon_running(_spinner: &ProgressBar) -> Result<(), Box<dyn std::error::Error>> {
+    let daemon_running = daemon_pid::running_daemon_pid()?.is_some();
+
+    if !daemon_running {
+        let current_exe = env::current_exe()?;
+        let daemon_path = current_exe.parent()
+            .ok_or("Failed to get parent directory")?
+            .join("gmld");
+            
+        if !daemon_path.exists() {
+             return Err(format!("Daemon executable not found at {:?}", daemon_path).into());
+        }
+
+        // Send anything the daemon prints to its log rather than into this command's output
+        let log_dir = paths::gml_dir()?;
+        fs::create_dir_all(&log_dir)?;
+        let log_file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_dir.join("gmld.log"))?;
+
+        let mut command = Command::new(daemon_path);
+        command
+            .stdin(Stdio::null())
+            .stdout(log_file.try_clone()?)
+            .stderr(log_file);
+        detach(&mut command);
+        command.spawn()
+            .map_err(|e| format!("Failed to start daemon: {}", e))?;
+            
+        // Give it a moment to start
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+    
+    Ok(())
+}
+
+/// Start the daemon in its own session, without a controlling terminal, so closing the terminal
+/// that started it (and the SIGHUP that comes with that) doesn't take it down
+#[cfg(unix)]
+fn detach(command: &mut Command) {
+    use std::os::unix::process::CommandExt;
+
+    // SAFETY: setsid is async-signal-safe, so it may be called between fork and exec
+    unsafe {
+        command.pre_exec(|| {
+            if libc::setsid() == -1 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+}
+
+/// Start the daemon without a console and outside this console's process group, so closing the
+/// console or pressing Ctrl-C in it doesn't stop the daemon
+#[cfg(windows)]
+fn detach(command: &mut Command) {
+    use std::os::windows::process::CommandExt;
+
+    const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+    const DETACHED_PROCESS: u32 = 0x0000_0008;
+    command.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+}
+
+/// Print `~/.gml/gmld.log`, then with `follow` keep printing whatever is appended to it, like
+/// `tail -f`, until interrupted
+pub fn handle_logs(follow: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let log_path = paths::gml_dir()?.join("gmld.log");
+    let mut file = match File::open(&log_path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            eprintln!("The daemon has not logged anything yet ({} doesn't exist)", log_path.display());
+            if !follow {
+                return Ok(());
+            }
+            // The daemon creates the log when it first starts, so wait for that
+            loop {
+                thread::sleep(FOLLOW_INTERVAL);
+                match File::open(&log_path) {
+                    Ok(file) => break file,
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                    Err(e) => return Err(format!("Failed to read {}: {}", log_path.display(), e).into()),
+                }
+            }
+        }
+        Err(e) => return Err(format!("Failed to read {}: {}", log_path.display(), e).into()),
+    };
+
+    let mut stdout = io::stdout();
+    let mut position = io::copy(&mut file, &mut stdout)?;
+    stdout.flush()?;
+    if !follow {
+        return Ok(());
+    }
+
+    loop {
+        thread::sleep(FOLLOW_INTERVAL);
+        // The log only grows until gmld rotates it (or someone truncates it); then the file at the
+        // path is a new one, so start again from its top
+        let len = match fs::metadata(&log_path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("Failed to read {}: {}", log_path.display(), e).into()),
+        };
+        if len < position {
+            file = File::open(&log_path)?;
+            position = 0;
+        }
+        if len > position {
+            let mut appended = Vec::new();
+            position += file.read_to_end(&mut appended)? as u64;
+            stdout.write_all(&appended)?;
+            stdout.flush()?;
+        }
+    }
+}
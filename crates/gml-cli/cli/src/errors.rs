@@ -0,0 +1,51 @@
+//! How a failed command reports its error: `Error: ...` text by default, or with `--json` a
+//! single-line envelope on stderr, `{"error": {"kind": ..., "message": ..., "code": ...}}`, so
+//! automation can classify failures without parsing English messages.
+
+use gml_core::error::GmlError;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Exit code of every failed command
+pub const EXIT_FAILURE: i32 = 1;
+
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn enable_json() {
+    JSON.store(true, Ordering::Relaxed);
+}
+
+/// Print `e` to stderr in the selected format
+pub fn report(e: &(dyn std::error::Error + 'static)) {
+    if !JSON.load(Ordering::Relaxed) {
+        eprintln!("Error: {}", e);
+        return;
+    }
+
+    let message = match e.downcast_ref::<GmlError>() {
+        Some(gml_error) => gml_error.message(),
+        None => e.to_string(),
+    };
+    let envelope = serde_json::json!({
+        "error": {
+            "kind": kind(e),
+            "message": message,
+            "code": EXIT_FAILURE,
+        }
+    });
+    eprintln!("{}", envelope);
+}
+
+/// Stable category name for an error, from its type
+fn kind(e: &(dyn std::error::Error + 'static)) -> &'static str {
+    if let Some(gml_error) = e.downcast_ref::<GmlError>() {
+        gml_error.kind().as_str()
+    } else if e.is::<std::io::Error>() || e.is::<dialoguer::Error>() {
+        "io"
+    } else if e.is::<toml::de::Error>() {
+        "config"
+    } else if e.is::<serde_json::Error>() {
+        "parse"
+    } else {
+        "other"
+    }
+}
@@ -0,0 +1,62 @@
+//! User commands from `[hooks]` in config, run around node creation and deletion, and when a
+//! node's soft timeout passes.
+//!
+//! Hooks run through `sh` with details of the node in `GML_NODE_*` environment variables. Their
+//! stdout is sent to stderr so that machine-readable output such as `--output-env` stays clean.
+
+use crate::config::Hooks;
+use crate::sh;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Hook {
+    PreCreate,
+    PostCreate,
+    PreDelete,
+    PostDelete,
+    /// Run by the daemon when a node created with `--grace` reaches its soft timeout
+    SoftTimeout,
+}
+
+impl Hook {
+    fn name(&self) -> &'static str {
+        match self {
+            Hook::PreCreate => "pre-create",
+            Hook::PostCreate => "post-create",
+            Hook::PreDelete => "pre-delete",
+            Hook::PostDelete => "post-delete",
+            Hook::SoftTimeout => "soft-timeout",
+        }
+    }
+
+    fn command<'a>(&self, hooks: &'a Hooks) -> Option<&'a str> {
+        match self {
+            Hook::PreCreate => hooks.pre_create.as_deref(),
+            Hook::PostCreate => hooks.post_create.as_deref(),
+            Hook::PreDelete => hooks.pre_delete.as_deref(),
+            Hook::PostDelete => hooks.post_delete.as_deref(),
+            Hook::SoftTimeout => hooks.soft_timeout.as_deref(),
+        }
+    }
+}
+
+/// Run `hook` if it is configured. A failing pre-hook is an error that should abort the
+/// operation; a failing post-hook only prints a warning, since the operation already happened.
+pub fn run(hooks: &Hooks, hook: Hook, env: &[(&str, String)]) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(command) = hook.command(hooks) else {
+        return Ok(());
+    };
+
+    let result = sh::run_with_env(&format!("{{ {}\n}} 1>&2", command), env);
+    match (result, hook) {
+        (Ok(()), _) => Ok(()),
+        (Err(e), Hook::PreCreate | Hook::PreDelete) => {
+            Err(format!("{} hook failed ({}); aborting", hook.name(), e).into())
+        }
+        (Err(e), Hook::PostCreate | Hook::PostDelete) => {
+            eprintln!("Warning: {} hook failed: {}", hook.name(), e);
+            Ok(())
+        }
+        // Nothing to abort, but the caller (usually the daemon) should log the failure
+        (Err(e), Hook::SoftTimeout) => Err(format!("{} hook failed: {}", hook.name(), e).into()),
+    }
+}
@@ -0,0 +1,29 @@
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::config::Config;
+
+/// Launches allowed in flight at once when `max-parallel-launches` is not configured
+pub const DEFAULT_MAX_PARALLEL_LAUNCHES: usize = 4;
+
+static LAUNCH_SLOTS: OnceLock<Arc<Semaphore>> = OnceLock::new();
+
+/// Wait for a free launch slot, shared by every provisioning path in this process.
+///
+/// Hold the returned permit for the duration of the provider's launch call so that bulk
+/// operations stay under provider rate limits. The limit is fixed by the first caller's config.
+pub async fn acquire_slot(config: &Config) -> SemaphorePermit<'static> {
+    slots(config).acquire().await.expect("launch semaphore is never closed")
+}
+
+/// The same launch slots, for launches that happen inside a provider's `create_cluster`
+pub fn shared_slots(config: &Config) -> Arc<Semaphore> {
+    slots(config).clone()
+}
+
+fn slots(config: &Config) -> &'static Arc<Semaphore> {
+    LAUNCH_SLOTS.get_or_init(|| {
+        let max = config.max_parallel_launches.unwrap_or(DEFAULT_MAX_PARALLEL_LAUNCHES);
+        Arc::new(Semaphore::new(max.max(1)))
+    })
+}
@@ -0,0 +1,109 @@
+//! `gml node logs`: read a node's logs over SSH.
+//!
+//! `--since` and `--grep` are applied on the node, so only the matching lines are transferred.
+
+use clap::Args;
+use gml_core::state::GmlState;
+use humantime::parse_duration;
+use std::time::Duration;
+
+use crate::config;
+use crate::node;
+use crate::prompt;
+use crate::sh;
+
+/// `log-source` value for the systemd journal
+const JOURNALD: &str = "journald";
+
+/// Keeps lines from `since` on, for logs whose lines start with a `YYYY-MM-DD HH:MM:SS` (or ISO
+/// 8601) timestamp. Continuation lines without one follow the line before them.
+const SINCE_FILTER: &str = r#"{ ts = substr($0, 1, 19); sub(/T/, " ", ts); if (ts ~ /^[0-9][0-9][0-9][0-9]-[0-9][0-9]-[0-9][0-9] [0-9][0-9]:[0-9][0-9]:[0-9][0-9]$/) keep = (ts >= since) } keep"#;
+
+#[derive(Args, Debug)]
+pub struct LogsArgs {
+    /// The unique ID of the node
+    pub id: Option<String>,
+    /// Only show lines from the last <duration> (e.g. "30m", "2h")
+    #[arg(long)]
+    pub since: Option<String>,
+    /// Only show lines matching this extended regular expression
+    #[arg(long)]
+    pub grep: Option<String>,
+    /// `journald`, or the path of a log file on the node (defaults to `log-source` in the provider
+    /// config, else `journald`)
+    #[arg(long)]
+    pub source: Option<String>,
+    /// Show at most this many lines, the most recent ones; 0 shows all of them
+    #[arg(short = 'n', long, default_value_t = 200)]
+    pub lines: usize,
+}
+
+/// Where a node keeps the logs to read
+#[derive(Debug, Clone, PartialEq)]
+enum LogSource {
+    Journald,
+    /// A plain text file on the node, e.g. `/var/log/cloud-init-output.log`
+    File(String),
+}
+
+impl LogSource {
+    fn parse(value: &str) -> LogSource {
+        if value == JOURNALD {
+            LogSource::Journald
+        } else {
+            LogSource::File(value.to_string())
+        }
+    }
+}
+
+pub fn handle_node_logs(args: LogsArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let LogsArgs { id, since, grep, source, lines } = args;
+    let id = prompt::resolve_node_id(id)?;
+    let node = GmlState::load()?
+        .node(&id)
+        .cloned()
+        .ok_or_else(|| format!("Node with ID '{}' not found", id))?;
+    node::ensure_not_paused(&node)?;
+
+    let since = match &since {
+        Some(since) => Some(parse_duration(since).map_err(|e| format!("Invalid --since '{}': {}", since, e))?),
+        None => None,
+    };
+    let source = match source {
+        Some(source) => source,
+        None => config::parse_config()?
+            .get_provider_account(&node.provider, node.account.as_deref())
+            .ok()
+            .and_then(|provider_config| provider_config.log_source.clone())
+            .unwrap_or_else(|| JOURNALD.to_string()),
+    };
+
+    let remote_cmd = remote_command(&LogSource::parse(&source), since, grep.as_deref(), lines);
+    let ssh_cmd = format!(
+        "ssh -o StrictHostKeyChecking=no{} {}@{} {}",
+        node::ssh_options(node.ssh_port, node.identity_file.as_deref()), node.user, node.ip, sh::quote(&remote_cmd)
+    );
+    sh::run(&ssh_cmd).map_err(|e| format!("Failed to read logs from {}: {}", source, e))?;
+    Ok(())
+}
+
+/// Shell command run on the node to print the selected log lines
+fn remote_command(source: &LogSource, since: Option<Duration>, grep: Option<&str>, lines: usize) -> String {
+    let mut command = match (source, since) {
+        (LogSource::Journald, Some(since)) => format!("journalctl --no-pager --since=-{}s", since.as_secs()),
+        (LogSource::Journald, None) => "journalctl --no-pager".to_string(),
+        // Flat files are filtered on their own timestamps, which are in the node's local time
+        (LogSource::File(path), Some(since)) => format!(
+            "since=$(date -d \"@$(( $(date +%s) - {} ))\" '+%Y-%m-%d %H:%M:%S') && awk -v since=\"$since\" {} {}",
+            since.as_secs(), sh::quote(SINCE_FILTER), sh::quote(path)
+        ),
+        (LogSource::File(path), None) => format!("cat {}", sh::quote(path)),
+    };
+    if let Some(pattern) = grep {
+        command.push_str(&format!(" | grep -E -e {}", sh::quote(pattern)));
+    }
+    if lines > 0 {
+        command.push_str(&format!(" | tail -n {}", lines));
+    }
+    command
+}
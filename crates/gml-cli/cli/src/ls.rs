@@ -1,109 +1,332 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
+use clap::ValueEnum;
 use comfy_table::{Cell, Table};
-use gml_core::state::GmlState;
+use gml_core::net;
+use gml_core::state::{GmlState, NodeEntry};
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::thread;
+
+use crate::node;
+
+/// Fields `gml ls` can sort nodes by
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SortField {
+    /// Newest first
+    #[value(alias = "created")]
+    CreatedAt,
+    /// Soonest to expire first; nodes without a timeout last
+    #[value(alias = "remaining")]
+    TimeRemaining,
+    Provider,
+    #[value(alias = "type")]
+    InstanceType,
+    /// Most expensive first; nodes without a recorded price last
+    Cost,
+}
+
+/// Formats `gml ls --output` can print the listing in
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    /// Tables for people to read
+    Table,
+    /// `{"nodes": [...], "clusters": [...], ...}` as pretty JSON, for scripts
+    Json,
+}
+
+/// Nodes with less than this left count as expiring soon in the fleet summary
+const EXPIRING_SOON: Duration = Duration::hours(1);
+
+/// How `gml ls` lays out its tables
+#[derive(Clone, Copy, Debug)]
+pub enum Layout {
+    /// Tables, unless they are wider than the terminal
+    Auto,
+    /// Always tables
+    Wide,
+    /// Always one `key: value` block per row
+    Compact,
+}
+
+pub fn handle_ls_command(
+    provider: Option<&str>,
+    sort: SortField,
+    reverse: bool,
+    layout: Layout,
+    online: bool,
+    total: bool,
+    json: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let GmlState { mut nodes, mut clusters } = GmlState::load()?;
+    if let Some(provider) = provider {
+        nodes.retain(|node| node.provider == provider);
+        clusters.retain(|cluster| cluster.provider == provider);
+    }
 
-pub fn handle_ls_command() {
     // Display nodes
-    match GmlState::list_nodes() {
-        Ok(nodes) => {
-            if nodes.is_empty() {
-                println!("No nodes found.");
+    sort_nodes(&mut nodes, sort, reverse);
+    let latencies = if online { measure_latencies(&nodes) } else { Vec::new() };
+
+    if json {
+        let latency: HashMap<&str, &String> = nodes.iter().map(|n| n.id.as_str()).zip(&latencies).collect();
+        let mut output = serde_json::json!({
+            "nodes": nodes,
+            "clusters": clusters,
+            "summary": summarize(&nodes),
+        });
+        if online {
+            output["latency"] = serde_json::json!(latency);
+        }
+        println!("{}", serde_json::to_string_pretty(&output)?);
+        return Ok(());
+    }
+
+    if nodes.is_empty() {
+        println!("No nodes found.");
+    } else {
+        let mut rows = Vec::new();
+        for (i, node) in nodes.iter().enumerate() {
+            // Format the created_at timestamp to be more readable
+            let created_at = match DateTime::parse_from_rfc3339(&node.created_at) {
+                Ok(dt) => dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+                Err(_) => node.created_at.clone(),
+            };
+            
+            // Calculate and format time remaining
+            let time_remaining = if node.pending_delete_at.is_some() {
+                format!("Deleting in {}", format_time_remaining(&node.pending_delete_at))
+            } else if node.reserved {
+                "Reserved".to_string()
             } else {
-                let mut table = Table::new();
-                table.set_header(vec!["ID", "IP", "Provider", "Instance Type", "Time Remaining", "Created At"]);
-                
-                for node in &nodes {
-                    // Format the created_at timestamp to be more readable
-                    let created_at = match DateTime::parse_from_rfc3339(&node.created_at) {
-                        Ok(dt) => dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
-                        Err(_) => node.created_at.clone(),
-                    };
-                    
-                    // Calculate and format time remaining
-                    let time_remaining = format_time_remaining(&node.timeout);
-                    
-                    table.add_row(vec![
-                        Cell::new(&node.id),
-                        Cell::new(&node.ip),
-                        Cell::new(&node.provider),
-                        Cell::new(&node.instance_type),
-                        Cell::new(time_remaining),
-                        Cell::new(created_at),
-                    ]);
-                }
-                
-                println!("Nodes");
-                println!("{}", table);
+                format_time_remaining(&node.timeout)
+            };
+            
+            let mut row = vec![
+                node.id.clone(),
+                node.name.clone().unwrap_or_else(|| "-".to_string()),
+                node.cluster_id.as_deref().map_or("-".to_string(), short_id),
+                if node.paused { "paused".to_string() } else { node.ip.clone() },
+                node.status.clone(),
+                node.provider.clone(),
+                node.instance_type.clone(),
+                time_remaining,
+                created_at,
+            ];
+            if let Some(latency) = latencies.get(i) {
+                row.push(latency.clone());
             }
+            rows.push(row);
         }
-        Err(e) => {
-            eprintln!("Error listing nodes: {}", e);
-            std::process::exit(1);
+        
+        let mut headers = vec!["ID", "Name", "Cluster", "IP", "Status", "Provider", "Instance Type", "Time Remaining", "Created At"];
+        if online {
+            headers.push("Latency");
+        }
+        println!("Nodes");
+        print_rows(&headers, rows, layout);
+        if total {
+            println!("{}", summarize(&nodes));
         }
     }
     
     // Display clusters
-    match GmlState::list_clusters() {
-        Ok(clusters) => {
-            if clusters.is_empty() {
-                println!("\nNo clusters found.");
-            } else {
-                let mut table = Table::new();
-                table.set_header(vec!["ID", "Provider", "Node Count", "Timeout", "Created At"]);
-                
-                for cluster in &clusters {
-                    // Format the created_at timestamp to be more readable
-                    let created_at = match DateTime::parse_from_rfc3339(&cluster.created_at) {
-                        Ok(dt) => dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
-                        Err(_) => cluster.created_at.clone(),
-                    };
-                    
-                    // Format timeout - show "None" if not set
-                    let timeout_display = cluster.timeout.as_deref().unwrap_or("None");
-                    
-                    table.add_row(vec![
-                        Cell::new(&cluster.id),
-                        Cell::new(&cluster.provider),
-                        Cell::new(cluster.node_count),
-                        Cell::new(timeout_display),
-                        Cell::new(created_at),
-                    ]);
+    if clusters.is_empty() {
+        println!("\nNo clusters found.");
+    } else {
+        let mut rows = Vec::new();
+        for cluster in &clusters {
+            // Format the created_at timestamp to be more readable
+            let created_at = match DateTime::parse_from_rfc3339(&cluster.created_at) {
+                Ok(dt) => dt.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+                Err(_) => cluster.created_at.clone(),
+            };
+            
+            // Format timeout - show "None" if not set
+            let timeout_display = cluster.timeout.as_deref().unwrap_or("None");
+            
+            rows.push(vec![
+                cluster.id.clone(),
+                cluster.provider.clone(),
+                cluster.node_count.to_string(),
+                timeout_display.to_string(),
+                created_at,
+            ]);
+        }
+        
+        println!("\nClusters");
+        print_rows(&["ID", "Provider", "Node Count", "Timeout", "Created At"], rows, layout);
+    }
+    Ok(())
+}
+
+/// Fleet totals printed under the node table with `--total`, included in `--json` output, and
+/// summed up by `gml status`
+#[derive(Debug, Serialize)]
+pub struct Summary {
+    pub nodes: usize,
+    /// Sum of the hourly prices recorded when the nodes were created, in USD
+    pub hourly_cost: f64,
+    /// Nodes without a recorded price, which `hourly_cost` leaves out
+    pub unpriced: usize,
+    pub expired: usize,
+    /// Nodes with less than [`EXPIRING_SOON`] left
+    pub expiring_soon: usize,
+}
+
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Total: {} node(s), ~{}/hr", self.nodes, node::format_cost(self.hourly_cost, "USD"))?;
+        if self.unpriced > 0 {
+            write!(f, " ({} without a price)", self.unpriced)?;
+        }
+        write!(
+            f,
+            ", {} expired, {} expiring within {}",
+            self.expired,
+            self.expiring_soon,
+            humantime::format_duration(EXPIRING_SOON.to_std().unwrap_or_default())
+        )
+    }
+}
+
+/// Totals over the same entries the node table shows
+pub fn summarize(nodes: &[NodeEntry]) -> Summary {
+    let mut summary = Summary { nodes: nodes.len(), hourly_cost: 0.0, unpriced: 0, expired: 0, expiring_soon: 0 };
+    for node in nodes {
+        match node.hourly_cost {
+            Some(cost) => summary.hourly_cost += cost,
+            None => summary.unpriced += 1,
+        }
+        // Reserved nodes are never deleted, whatever their timeout says
+        if node.reserved {
+            continue;
+        }
+        match time_remaining(&node.timeout) {
+            Some(remaining) if remaining <= Duration::zero() => summary.expired += 1,
+            Some(remaining) if remaining <= EXPIRING_SOON => summary.expiring_soon += 1,
+            _ => {}
+        }
+    }
+    summary
+}
+
+/// Cluster IDs are UUIDs; the first block is enough to tell clusters apart in a table
+fn short_id(id: &str) -> String {
+    id.split('-').next().unwrap_or(id).to_string()
+}
+
+/// TCP connect latency to each node's SSH port, measured in parallel so unreachable nodes only
+/// cost one timeout in total
+fn measure_latencies(nodes: &[NodeEntry]) -> Vec<String> {
+    thread::scope(|scope| {
+        let checks: Vec<_> = nodes.iter()
+            .map(|node| scope.spawn(move || {
+                if node.paused {
+                    return "-".to_string();
                 }
-                
-                println!("\nClusters");
-                println!("{}", table);
-            }
+                match net::tcp_latency(&node::node_details(node), net::CONNECT_TIMEOUT) {
+                    Ok(latency) => node::format_latency(latency),
+                    Err(_) => "unreachable".to_string(),
+                }
+            }))
+            .collect();
+        checks.into_iter()
+            .map(|check| check.join().unwrap_or_else(|_| "unreachable".to_string()))
+            .collect()
+    })
+}
+
+/// Print rows as a table, or as `key: value` blocks when the table would be wider than the
+/// terminal and wrap into an unreadable mess (e.g. in a narrow tmux pane)
+fn print_rows(headers: &[&str], rows: Vec<Vec<String>>, layout: Layout) {
+    let mut table = Table::new();
+    table.set_header(headers.to_vec());
+    for row in &rows {
+        table.add_row(row.iter().map(Cell::new));
+    }
+
+    let compact = match layout {
+        Layout::Wide => false,
+        Layout::Compact => true,
+        // Without a terminal (piped output, CI logs) nothing wraps, so the table is fine
+        Layout::Auto => table.width().is_some_and(|width| {
+            table.lines().any(|line| line.chars().count() > usize::from(width))
+        }),
+    };
+    if !compact {
+        println!("{}", table);
+        return;
+    }
+
+    let key_width = headers.iter().map(|h| h.chars().count()).max().unwrap_or(0) + 1;
+    for (i, row) in rows.iter().enumerate() {
+        if i > 0 {
+            println!();
         }
-        Err(e) => {
-            eprintln!("Error listing clusters: {}", e);
-            std::process::exit(1);
+        for (header, value) in headers.iter().zip(row) {
+            println!("{:<width$} {}", format!("{}:", header), value, width = key_width);
         }
     }
 }
 
+/// Sort nodes in place by the given field, then flip the order if `reverse` is set
+fn sort_nodes(nodes: &mut [NodeEntry], sort: SortField, reverse: bool) {
+    match sort {
+        SortField::CreatedAt => nodes.sort_by_key(|n| std::cmp::Reverse(parse_time(&n.created_at))),
+        SortField::TimeRemaining => nodes.sort_by(compare_time_remaining),
+        SortField::Provider => nodes.sort_by(|a, b| a.provider.cmp(&b.provider)),
+        SortField::InstanceType => nodes.sort_by(|a, b| a.instance_type.cmp(&b.instance_type)),
+        SortField::Cost => nodes.sort_by(|a, b| match (a.hourly_cost, b.hourly_cost) {
+            (Some(a), Some(b)) => b.total_cmp(&a),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }),
+    }
+    if reverse {
+        nodes.reverse();
+    }
+}
+
+/// Nodes that never expire (reserved or without a timeout) sort after every node that does
+fn compare_time_remaining(a: &NodeEntry, b: &NodeEntry) -> Ordering {
+    let remaining = |node: &NodeEntry| if node.reserved { None } else { time_remaining(&node.timeout) };
+    match (remaining(a), remaining(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn parse_time(timestamp: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(timestamp).ok().map(|t| t.with_timezone(&Utc))
+}
+
+/// Time left until the timeout expires, negative once it has passed.
+/// Returns None if there is no timeout or it cannot be parsed.
+fn time_remaining(timeout: &Option<String>) -> Option<Duration> {
+    timeout.as_deref()
+        .and_then(parse_time)
+        .map(|timeout_utc| timeout_utc - Utc::now())
+}
+
 /// Calculate and format the remaining time until expiration
 /// Returns a formatted string like "2h 30m", "Expired", "None", or "Invalid"
 fn format_time_remaining(timeout: &Option<String>) -> String {
-    match timeout {
-        Some(timeout_str) => {
-            match DateTime::parse_from_rfc3339(timeout_str) {
-                Ok(timeout_dt) => {
-                    let timeout_utc = timeout_dt.with_timezone(&Utc);
-                    let now = Utc::now();
-                    if now >= timeout_utc {
-                        "Expired".to_string()
-                    } else {
-                        let remaining = timeout_utc - now;
-                        let total_seconds = remaining.num_seconds();
-                        let hours = total_seconds / 3600;
-                        let minutes = (total_seconds % 3600) / 60;
-                        format!("{}h {}m", hours, minutes)
-                    }
-                }
-                Err(_) => "Invalid".to_string(),
-            }
+    if timeout.is_none() {
+        return "None".to_string();
+    }
+    match time_remaining(timeout) {
+        Some(remaining) if remaining <= Duration::zero() => "Expired".to_string(),
+        Some(remaining) => {
+            let total_seconds = remaining.num_seconds();
+            let hours = total_seconds / 3600;
+            let minutes = (total_seconds % 3600) / 60;
+            format!("{}h {}m", hours, minutes)
         }
-        None => "None".to_string(),
+        None => "Invalid".to_string(),
     }
 }
 
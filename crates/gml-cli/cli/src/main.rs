@@ -4,9 +4,24 @@ mod config;
 mod providers;
 mod node;
 mod cluster;
+mod cluster_spec;
+mod completions;
+mod daemon;
+mod errors;
+mod hooks;
+mod launch;
+mod logs;
 mod ls;
+mod multi_provider;
 mod spinner;
+mod prompt;
+mod reconcile;
+mod repair;
+mod resume;
+mod secrets;
 mod sh;
+mod status;
+mod timings;
 
 
 #[derive(Parser, Debug)]
@@ -15,6 +30,13 @@ mod sh;
 struct Args {
     #[command(subcommand)]
     command: Commands,
+    /// Print how long each phase of the command took, to stderr
+    #[arg(long, global = true)]
+    timings: bool,
+    /// Report errors as a JSON object on stderr instead of text; `cluster create` also prints its
+    /// members as JSON, and `ls` its listing
+    #[arg(long, global = true)]
+    json: bool,
 }
 
 #[derive(Subcommand, Debug)]
@@ -30,60 +52,234 @@ enum Commands {
         action: ClusterAction,
     },
     /// List all nodes and clusters
-    Ls,
+    Ls {
+        /// Only list nodes and clusters on this provider
+        #[arg(short, long)]
+        provider: Option<String>,
+        /// Field to sort nodes by
+        #[arg(long, visible_alias = "sort-by", value_enum, default_value_t = ls::SortField::CreatedAt)]
+        sort: ls::SortField,
+        /// Reverse the sort order
+        #[arg(long)]
+        reverse: bool,
+        /// Always print tables, even if they are wider than the terminal
+        #[arg(long, conflicts_with = "compact")]
+        wide: bool,
+        /// Print each node as a block of `key: value` lines instead of a table
+        #[arg(long)]
+        compact: bool,
+        /// Add a column with each node's TCP connect latency
+        #[arg(long)]
+        online: bool,
+        /// Print a footer with the node count, total hourly cost and how many nodes have expired
+        /// or expire within the hour
+        #[arg(long)]
+        total: bool,
+        /// Print the listing as `table` or `json`; `json` is the same listing as the global --json
+        #[arg(short, long, value_enum, default_value_t = ls::OutputFormat::Table)]
+        output: ls::OutputFormat,
+    },
+    /// Show an overview of the daemon, config, nodes and clusters
+    Status {
+        /// Also contact each configured provider to check its credentials
+        #[arg(long)]
+        online: bool,
+    },
     /// Connect to a node
     Connect {
         /// The ID of the node
-        id: String,
+        id: Option<String>,
+    },
+    /// Manage gml configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Inspect and fix the local state file
+    State {
+        #[command(subcommand)]
+        action: StateAction,
     },
+    /// Inspect the gmld daemon
+    Daemon {
+        #[command(subcommand)]
+        action: DaemonAction,
+    },
+    /// Settle launches and deletions left unfinished by an interrupted command
+    Resume {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// Forget the interrupted operations instead of acting on them
+        #[arg(long)]
+        discard: bool,
+    },
+    /// Print a shell completion script
+    Completions {
+        shell: clap_complete::Shell,
+        /// Also complete node IDs by reading local state (bash, zsh and fish)
+        #[arg(long)]
+        dynamic: bool,
+    },
+    /// List node IDs for completion scripts
+    #[command(name = "__complete-nodes", hide = true)]
+    CompleteNodes,
 }
 
 #[derive(Subcommand, Debug)]
 enum NodeAction {
     /// Create a new node
-    Create {
-        #[arg(short, long)]
-        provider: String,
-        #[arg(short, long)]
-        instance_type: String,
-        #[arg(short, long)]
-        timeout: String,
-        #[arg(short, long)]
-        region: Option<String>,
-    },
+    Create(Box<node::CreateNodeArgs>),
     /// Delete a node
     Delete {
-        /// The unique ID of the node to delete
-        id: String,
+        /// The unique ID or name of the node to delete
+        id: Option<String>,
+        /// Delete even if another gml session is connected to the node
+        #[arg(long)]
+        force: bool,
+        /// Delete right away instead of after the configured `delete-grace-period`
+        #[arg(long)]
+        now: bool,
+        /// Delete without asking for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Cancel a deletion that is still within its grace period
+    Undo {
+        /// The unique ID of the node
+        id: Option<String>,
     },
     /// Manage node timeouts
     Timeout {
         #[command(subcommand)]
         action: TimeoutAction,
     },
-    /// List available node types for a provider
+    /// Re-sync the current directory to a node without reconnecting
+    RestartWorkspace {
+        /// The unique ID of the node
+        id: Option<String>,
+    },
+    /// Power a node off, keeping its disk, to stop paying for compute
+    Pause {
+        /// The unique ID of the node
+        id: Option<String>,
+    },
+    /// Power a paused node back on
+    Resume {
+        /// The unique ID of the node
+        id: Option<String>,
+    },
+    /// Open an interactive SSH session on a node
+    Ssh {
+        /// The unique ID or name of the node
+        id: Option<String>,
+    },
+    /// Give a node a name that other commands accept in place of its ID
+    #[command(visible_alias = "label")]
+    Rename {
+        /// The unique ID or current name of the node
+        id: String,
+        /// The new name; an empty string removes the name
+        name: String,
+    },
+    /// Run the `soft-timeout` hook for a node now, as gmld does when its soft timeout passes
+    SoftTimeout {
+        /// The unique ID of the node
+        id: Option<String>,
+    },
+    /// Measure TCP connect latency to a node's SSH port
+    Ping {
+        /// The unique ID of the node
+        id: Option<String>,
+        /// Number of connections to time
+        #[arg(short, long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+    },
+    /// Print a node's logs, filtered on the node
+    Logs(logs::LogsArgs),
+    /// Show all recorded details for a node
+    Describe {
+        /// The unique ID of the node
+        id: Option<String>,
+    },
+    /// Compare local state with the providers; remove nodes that no longer exist and report drift
+    Sync {
+        /// Remove stale nodes without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List the node types a provider offers
+    #[command(visible_alias = "types")]
     ListTypes {
         #[arg(short, long)]
         provider: String,
+        /// Only show types the provider currently has capacity for
+        #[arg(long)]
+        available_only: bool,
+        /// Only show types with at least this much memory per GPU, in GB
+        #[arg(long)]
+        min_vram: Option<u32>,
+        /// Print the provider's full listing as JSON instead of a table
+        #[arg(long, conflicts_with = "min_vram")]
+        raw: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    /// Store a secret in the OS keyring and refer to it from config (e.g. `lambda.api-key`)
+    SetSecret {
+        /// The setting to store, as <provider>.api-key
+        key: String,
+    },
+    /// Check the config against each provider, e.g. that the configured region exists
+    Check,
+    /// Write a commented starter config to ~/.gml/config.toml
+    Init {
+        /// Replace an existing config file
+        #[arg(long)]
+        force: bool,
+    },
+    /// Parse the config and report which providers have every required key, without calling them
+    Validate,
+}
+
+#[derive(Subcommand, Debug)]
+enum StateAction {
+    /// Salvage the readable entries of a corrupt state file and rewrite it
+    Repair,
+}
+
+#[derive(Subcommand, Debug)]
+enum DaemonAction {
+    /// Print the daemon's log, ~/.gml/gmld.log
+    Logs {
+        /// Keep printing lines as they are appended, like `tail -f`
+        #[arg(short, long)]
+        follow: bool,
     },
 }
 
 #[derive(Subcommand, Debug)]
 enum TimeoutAction {
-    /// Reset the timeout for a node
+    /// Reset the timeout for a node, or for every node matching --all, --tag or --provider
     Reset {
         /// The unique ID of the node
-        #[arg(short, long)]
-        id: String,
+        #[arg(short, long, conflicts_with_all = ["all", "tag", "provider"])]
+        id: Option<String>,
+        #[command(flatten)]
+        selection: node::NodeSelection,
         /// The duration for the timeout (e.g., "1h30m", "2h", "30m")
         #[arg(short, long)]
         duration: String,
     },
-    /// Remove the timeout for a node
+    /// Remove the timeout for a node, or for every node matching --all, --tag or --provider
     Remove {
         /// The unique ID of the node
-        #[arg(short, long)]
-        id: String,
+        #[arg(short, long, conflicts_with_all = ["all", "tag", "provider"])]
+        id: Option<String>,
+        #[command(flatten)]
+        selection: node::NodeSelection,
     },
 }
 
@@ -91,90 +287,271 @@ enum TimeoutAction {
 enum ClusterAction {
     /// Create a new cluster
     Create {
-        #[arg(short, long)]
-        provider: String,
+        #[arg(short, long, required_unless_present = "spec_file")]
+        provider: Option<String>,
+        /// Named account to launch in, from a `[<provider>.<account>]` table in config
+        #[arg(long)]
+        account: Option<String>,
+        /// Instance type of every member
+        #[arg(short, long, required_unless_present = "spec_file")]
+        instance_type: Option<String>,
         #[arg(short, long)]
         nodes: Option<i32>,
-        #[arg(short, long)]
+        #[arg(short, long, required_unless_present = "spec_file")]
         timeout: Option<String>,
+        /// Spread members across regions round-robin; repeat for multiple regions
+        #[arg(long = "region")]
+        regions: Vec<String>,
+        /// Create the cluster described by a TOML spec file instead of from flags
+        #[arg(long, conflicts_with_all = ["provider", "account", "instance_type", "nodes", "timeout", "regions"])]
+        spec_file: Option<std::path::PathBuf>,
+        /// Validate the request and show the plan, including the API call for flag-based clusters,
+        /// without launching anything
+        #[arg(long)]
+        dry_run: bool,
+        /// Launch without asking to confirm the estimated cost
+        #[arg(short, long)]
+        yes: bool,
+        /// Add a `gml-<node id>` host entry for each member to ~/.ssh/config
+        #[arg(long)]
+        ssh_config: bool,
+        /// Write the members' IP addresses to this file, one per line, e.g. for mpirun or pdsh
+        #[arg(long)]
+        hostfile: Option<std::path::PathBuf>,
     },
-    /// Delete a cluster
+    /// Delete a cluster and all of its member nodes
     Delete {
+        /// The ID of the cluster to delete
+        cluster_id: String,
+        /// Only delete the cluster if it was created on this provider
         #[arg(short, long)]
-        provider: String,
+        provider: Option<String>,
+        /// Delete members even if another gml session is connected to them
+        #[arg(long)]
+        force: bool,
+        /// Delete without asking for confirmation
         #[arg(short, long)]
-        cluster_id: Option<String>,
+        yes: bool,
     },
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    if args.timings {
+        timings::enable();
+    }
+    if args.json {
+        errors::enable_json();
+    }
+    // Completion runs on every tab press and doesn't save state, so it leaves the file as it is
+    let completing = matches!(args.command, Commands::Completions { .. } | Commands::CompleteNodes);
+    if !completing {
+        if let Err(e) = repair::apply_compress_state_setting() {
+            exit_with_error(e);
+        }
+    }
 
     match args.command {
         Commands::Node { action } => {
             match action {
-                NodeAction::Create { provider, instance_type, timeout, region } => {
-                    if let Err(e) = node::handle_create_node(provider, instance_type, timeout, region).await {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
+                NodeAction::Create(args) => {
+                    if let Err(e) = node::handle_create_node(*args).await {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Delete { id, force, now, yes } => {
+                    if let Err(e) = node::handle_delete_node(id, force, now, yes).await {
+                        exit_with_error(e);
                     }
                 }
-                NodeAction::Delete { id } => {
-                    if let Err(e) = node::handle_delete_node(id).await {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
+                NodeAction::Undo { id } => {
+                    if let Err(e) = node::handle_undo_delete(id) {
+                        exit_with_error(e);
                     }
                 }
                 NodeAction::Timeout { action } => {
                     match action {
-                        TimeoutAction::Reset { id, duration } => {
-                            if let Err(e) = node::handle_node_timeout_reset(id, duration) {
-                                eprintln!("Error: {}", e);
-                                std::process::exit(1);
+                        TimeoutAction::Reset { id, selection, duration } => {
+                            if let Err(e) = node::handle_node_timeout_reset(id, selection, duration) {
+                                exit_with_error(e);
                             }
                         }
-                        TimeoutAction::Remove { id } => {
-                            if let Err(e) = node::handle_node_timeout_remove(id) {
-                                eprintln!("Error: {}", e);
-                                std::process::exit(1);
+                        TimeoutAction::Remove { id, selection } => {
+                            if let Err(e) = node::handle_node_timeout_remove(id, selection) {
+                                exit_with_error(e);
                             }
                         }
                     }
                 }
-                NodeAction::ListTypes { provider } => {
-                    if let Err(e) = node::handle_list_node_types(provider).await {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
+                NodeAction::RestartWorkspace { id } => {
+                    if let Err(e) = node::handle_restart_workspace(id) {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Pause { id } => {
+                    if let Err(e) = node::handle_pause_node(id).await {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Resume { id } => {
+                    if let Err(e) = node::handle_resume_node(id).await {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Ssh { id } => {
+                    if let Err(e) = node::handle_ssh_node(id).await {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Rename { id, name } => {
+                    if let Err(e) = node::handle_rename_node(id, name) {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::SoftTimeout { id } => {
+                    if let Err(e) = node::handle_soft_timeout(id) {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Ping { id, count } => {
+                    if let Err(e) = node::handle_ping_node(id, count) {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Logs(args) => {
+                    if let Err(e) = logs::handle_node_logs(args) {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Describe { id } => {
+                    if let Err(e) = node::handle_describe_node(id) {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::Sync { yes } => {
+                    if let Err(e) = reconcile::handle_node_sync(yes).await {
+                        exit_with_error(e);
+                    }
+                }
+                NodeAction::ListTypes { provider, available_only, min_vram, raw } => {
+                    if let Err(e) = node::handle_list_node_types(provider, available_only, min_vram, raw).await {
+                        exit_with_error(e);
                     }
                 }
             }
         }
         Commands::Cluster { action } => {
             match action {
-                ClusterAction::Create { provider, nodes, timeout } => {
-                    if let Err(e) = cluster::handle_create_cluster(provider, nodes, timeout) {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
+                ClusterAction::Create {
+                    provider, account, instance_type, nodes, timeout, regions, spec_file, dry_run, yes, ssh_config, hostfile,
+                } => {
+                    // With --json the members are printed as JSON too, not only errors
+                    let output = cluster::MemberOutput { json: args.json, ssh_config, hostfile };
+                    let result = match spec_file {
+                        Some(path) => cluster_spec::handle_create_from_spec(&path, yes, dry_run, &output).await,
+                        None => {
+                            let flags = cluster::ClusterFlags { provider, account, instance_type, nodes, timeout, regions };
+                            cluster::handle_create_cluster(flags, yes, dry_run, &output).await
+                        }
+                    };
+                    if let Err(e) = result {
+                        exit_with_error(e);
                     }
                 }
-                ClusterAction::Delete { provider, cluster_id } => {
-                    if let Err(e) = cluster::handle_delete_cluster(provider, cluster_id) {
-                        eprintln!("Error: {}", e);
-                        std::process::exit(1);
+                ClusterAction::Delete { cluster_id, provider, force, yes } => {
+                    if let Err(e) = cluster::handle_delete_cluster(cluster_id, provider, force, yes).await {
+                        exit_with_error(e);
                     }
                 }
             }
         }
-        Commands::Ls => {
-            ls::handle_ls_command();
+        Commands::Ls { provider, sort, reverse, wide, compact, online, total, output } => {
+            let layout = match (wide, compact) {
+                (true, _) => ls::Layout::Wide,
+                (_, true) => ls::Layout::Compact,
+                _ => ls::Layout::Auto,
+            };
+            // With --json the listing is printed as JSON too, not only errors
+            let json = args.json || output == ls::OutputFormat::Json;
+            if let Err(e) = ls::handle_ls_command(provider.as_deref(), sort, reverse, layout, online, total, json) {
+                exit_with_error(e);
+            }
+        }
+        Commands::Status { online } => {
+            if let Err(e) = status::handle_status_command(online).await {
+                exit_with_error(e);
+            }
         }
         Commands::Connect { id } => {
             if let Err(e) = node::handle_connect_command(id) {
-                eprintln!("Error: {}", e);
-                std::process::exit(1);
+                exit_with_error(e);
+            }
+        }
+        Commands::Config { action } => {
+            match action {
+                ConfigAction::SetSecret { key } => {
+                    if let Err(e) = secrets::handle_set_secret(key) {
+                        exit_with_error(e);
+                    }
+                }
+                ConfigAction::Check => {
+                    if let Err(e) = config::handle_check_command().await {
+                        exit_with_error(e);
+                    }
+                }
+                ConfigAction::Init { force } => {
+                    if let Err(e) = config::handle_init_command(force) {
+                        exit_with_error(e);
+                    }
+                }
+                ConfigAction::Validate => {
+                    if let Err(e) = config::handle_validate_command() {
+                        exit_with_error(e);
+                    }
+                }
+            }
+        }
+        Commands::Daemon { action } => {
+            match action {
+                DaemonAction::Logs { follow } => {
+                    if let Err(e) = daemon::handle_logs(follow) {
+                        exit_with_error(e);
+                    }
+                }
+            }
+        }
+        Commands::State { action } => {
+            match action {
+                StateAction::Repair => {
+                    if let Err(e) = repair::handle_state_repair() {
+                        exit_with_error(e);
+                    }
+                }
+            }
+        }
+        Commands::Resume { yes, discard } => {
+            if let Err(e) = resume::handle_resume_command(yes, discard).await {
+                exit_with_error(e);
+            }
+        }
+        Commands::Completions { shell, dynamic } => {
+            if let Err(e) = completions::handle_completions_command(shell, dynamic) {
+                exit_with_error(e);
+            }
+        }
+        Commands::CompleteNodes => {
+            if let Err(e) = completions::handle_complete_nodes() {
+                exit_with_error(e);
             }
         }
     }
+    timings::report();
+}
+
+fn exit_with_error(e: Box<dyn std::error::Error>) -> ! {
+    errors::report(e.as_ref());
+    timings::report();
+    std::process::exit(errors::EXIT_FAILURE);
 }
 
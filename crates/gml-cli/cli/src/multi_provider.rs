@@ -0,0 +1,356 @@
+//! `gml node create` with several `--provider`s: one node on each, launched in parallel.
+//!
+//! Every provider is checked, launched and reported on independently, so one that is out of
+//! capacity or misconfigured doesn't stop the others. The outcome for each is shown in one table.
+
+use chrono::Utc;
+use comfy_table::{Cell, Table};
+use futures::future;
+use gml_core::{CostEstimate, NodeProvider, NodeRequest};
+use indicatif::ProgressBar;
+use std::collections::HashMap;
+
+use crate::config::{self, Config};
+use crate::daemon;
+use crate::hooks::{self, Hook};
+use crate::node::{self, CreateNodeArgs, LaunchedNode, NodeRecord, CHEAPEST_INSTANCE_TYPE, STDIN_INSTANCE_TYPE};
+use crate::prompt;
+use crate::providers;
+use crate::spinner;
+
+/// One `--provider` entry, `<provider>` or `<provider>=<instance type>`
+struct Target {
+    provider: String,
+    instance_type: String,
+    /// Name for the node, from `--label-from-git`
+    name: Option<String>,
+}
+
+/// A target that passed its pre-launch checks
+struct Prepared {
+    handle: Box<dyn NodeProvider>,
+    request: NodeRequest,
+    region: Option<String>,
+    estimate: Option<CostEstimate>,
+}
+
+/// What happened on one provider
+struct Outcome {
+    target: Target,
+    hourly: Option<CostEstimate>,
+    result: Result<LaunchedNode, String>,
+}
+
+pub async fn handle_create_on_providers(args: CreateNodeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    let CreateNodeArgs {
+        provider: providers, account, instance_type, min_vram, timeout, grace, region, zone, subnet, disk_gb, tags, reserved,
+        label_from_git, auto_region, yes, force, no_validate, private_key_from, progress_bar, output_env, hold, template,
+        save_template, dry_run,
+    } = args;
+
+    // Placement is specific to each cloud
+    let placement = [(region.is_some(), "--region"), (zone.is_some(), "--zone"), (subnet.is_some(), "--subnet")];
+    if let Some((_, flag)) = placement.iter().find(|(given, _)| *given) {
+        return Err(format!(
+            "{} only works with a single --provider; set it in each provider's config block instead",
+            flag
+        ).into());
+    }
+    let single_node_only = [
+        (hold, "--hold"),
+        (output_env, "--output-env"),
+        (progress_bar, "--progress-bar"),
+        (template.is_some(), "--template"),
+        (save_template.is_some(), "--save-template"),
+        (dry_run, "--dry-run"),
+        (auto_region, "--auto-region"),
+        (grace.is_some(), "--grace"),
+        (account.is_some(), "--account"),
+        (private_key_from.is_some(), "--private-key-from"),
+        (instance_type.len() > 1, "a list of --instance-type values"),
+    ];
+    if let Some((_, flag)) = single_node_only.iter().find(|(given, _)| *given) {
+        return Err(format!("{} only works with a single --provider", flag).into());
+    }
+
+    let mut targets = parse_targets(&providers, instance_type.first().map(String::as_str))?;
+    if timeout.is_none() && !reserved {
+        return Err("--timeout is required unless the node is --reserved".into());
+    }
+    if min_vram.is_some() && !targets.iter().any(|t| t.instance_type == CHEAPEST_INSTANCE_TYPE) {
+        return Err(format!("--min-vram only applies with --instance-type {}", CHEAPEST_INSTANCE_TYPE).into());
+    }
+    let timeout_duration = match &timeout {
+        Some(timeout) => Some(
+            node::parse_timeout_duration(timeout)
+                .ok_or_else(|| format!("Invalid timeout format: '{}'. Use formats like '1h30m', '2h', '30m'", timeout))?,
+        ),
+        None => None,
+    };
+    let timeout_expiration = timeout_duration.map(|duration| (Utc::now() + duration).to_rfc3339());
+
+    let config = config::parse_config()?;
+    let mut tags: HashMap<String, String> = tags.into_iter().collect();
+    if label_from_git {
+        if let Some(name) = node::label_from_git_context(&mut tags) {
+            name_targets(&name, &mut targets);
+        }
+    }
+
+    let spinner = spinner::create_spinner();
+    daemon::ensure_daemon_running(&spinner).await?;
+
+    spinner.set_message(format!("Checking {} provider(s)...", targets.len()));
+    let checks = targets.iter().map(|target| prepare(&config, target, min_vram, disk_gb, force, no_validate, &spinner));
+    let checked = future::join_all(checks).await;
+
+    let mut outcomes = Vec::new();
+    let mut ready = Vec::new();
+    for (mut target, check) in targets.into_iter().zip(checked) {
+        match check {
+            Ok((instance_type, prepared)) => {
+                target.instance_type = instance_type;
+                ready.push((target, prepared));
+            }
+            Err(e) => outcomes.push(Outcome { target, hourly: None, result: Err(e) }),
+        }
+    }
+    spinner.finish_and_clear();
+
+    if ready.is_empty() {
+        print_outcomes(&outcomes);
+        return Err("No provider passed its pre-launch checks; nothing was launched".into());
+    }
+
+    println!("Launching one node on each of {} provider(s):", ready.len());
+    for (target, prepared) in &ready {
+        let price = prepared.estimate.as_ref()
+            .map_or("price unknown".to_string(), |e| format!("~{}/hr", node::format_cost(e.hourly, &e.currency)));
+        println!(
+            "  {}: {} in {} ({})",
+            target.provider,
+            target.instance_type,
+            prepared.region.as_deref().unwrap_or("the default region"),
+            price
+        );
+    }
+    if !confirm_cost(&ready, timeout.as_deref(), timeout_duration, yes)? {
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let spinner = spinner::create_spinner();
+    let mut launches = Vec::new();
+    let mut launching = Vec::new();
+    for (target, prepared) in ready {
+        let pre_create = hooks::run(&config.hooks, Hook::PreCreate, &hook_env(&target, &prepared.region));
+        if let Err(e) = spinner.suspend(|| pre_create) {
+            outcomes.push(Outcome { target, hourly: prepared.estimate, result: Err(e.to_string()) });
+            continue;
+        }
+
+        let Prepared { handle, request, region, estimate } = prepared;
+        let record = NodeRecord {
+            name: target.name.clone(),
+            provider: target.provider.clone(),
+            region,
+            timeout: timeout_expiration.clone(),
+            soft_timeout: None,
+            tags: tags.clone(),
+            reserved,
+            // Stored prices are summed across nodes, so only keep them in a single currency
+            hourly_cost: estimate.as_ref().filter(|e| e.currency == "USD").map(|e| e.hourly),
+            cluster_id: None,
+            identity_file: None,
+            account: None,
+        };
+        launching.push((target, estimate, record.region.clone()));
+        let (config, spinner) = (&config, &spinner);
+        launches.push(async move {
+            node::launch_node(handle.as_ref(), config, spinner, request, record)
+                .await
+                .map_err(|e| e.to_string())
+        });
+    }
+
+    spinner.set_message(format!("Launching {} node(s)...", launches.len()));
+    let results = future::join_all(launches).await;
+    spinner.finish_and_clear();
+
+    for ((target, hourly, region), result) in launching.into_iter().zip(results) {
+        if let Ok(launched) = &result {
+            let mut env = hook_env(&target, &region);
+            env.extend([
+                ("GML_NODE_ID", launched.id.clone()),
+                ("GML_NODE_IP", launched.ip.clone()),
+                ("GML_NODE_USER", launched.user.clone()),
+            ]);
+            hooks::run(&config.hooks, Hook::PostCreate, &env)?;
+        }
+        outcomes.push(Outcome { target, hourly, result });
+    }
+
+    print_outcomes(&outcomes);
+    let failed = outcomes.iter().filter(|outcome| outcome.result.is_err()).count();
+    if failed > 0 {
+        return Err(format!("{} of {} provider(s) failed to launch a node", failed, outcomes.len()).into());
+    }
+    Ok(())
+}
+
+/// Split `--provider` entries into providers and instance types; entries without their own type use
+/// `--instance-type`
+fn parse_targets(entries: &[String], default_type: Option<&str>) -> Result<Vec<Target>, String> {
+    entries.iter().map(|entry| {
+        let (provider, instance_type) = match entry.split_once('=') {
+            Some((provider, instance_type)) => (provider.trim(), Some(instance_type.trim())),
+            None => (entry.trim(), default_type),
+        };
+        let instance_type = instance_type.filter(|t| !t.is_empty()).ok_or_else(|| {
+            format!("No instance type for provider '{}'; pass --instance-type or --provider {}=<type>", provider, provider)
+        })?;
+        if instance_type == STDIN_INSTANCE_TYPE {
+            return Err(format!("--instance-type {} only works with a single --provider", STDIN_INSTANCE_TYPE));
+        }
+        Ok(Target { provider: provider.to_string(), instance_type: instance_type.to_string(), name: None })
+    }).collect()
+}
+
+/// Give each target's node its own name, since names must be unique: `<name>-<provider>`, plus
+/// a counter for a provider that is listed more than once
+fn name_targets(name: &str, targets: &mut [Target]) {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for target in targets.iter() {
+        *counts.entry(target.provider.clone()).or_default() += 1;
+    }
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    for target in targets.iter_mut() {
+        let index = seen.entry(target.provider.clone()).or_default();
+        *index += 1;
+        target.name = Some(if counts[&target.provider] > 1 {
+            format!("{}-{}-{}", name, target.provider, index)
+        } else {
+            format!("{}-{}", name, target.provider)
+        });
+    }
+}
+
+/// Create the provider handle, resolve `cheapest`, validate the request and estimate its cost.
+/// Returns the resolved instance type alongside the prepared launch.
+async fn prepare(
+    config: &Config,
+    target: &Target,
+    min_vram: Option<u32>,
+    disk_gb: Option<u32>,
+    force: bool,
+    no_validate: bool,
+    spinner: &ProgressBar,
+) -> Result<(String, Prepared), String> {
+    let provider_config = config.get_provider(&target.provider)
+        .ok_or_else(|| format!("Provider '{}' not found in config", target.provider))?;
+    let region = provider_config.region.clone();
+    let handle = providers::create_provider_handle(
+        &target.provider,
+        provider_config,
+        region.clone(),
+        config.ssh_public_key.clone(),
+    )
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let instance_type = if target.instance_type == CHEAPEST_INSTANCE_TYPE {
+        node::cheapest_instance_type(handle.as_ref(), min_vram, region.as_deref())
+            .await
+            .map_err(|e| e.to_string())?
+            .name
+    } else {
+        if !no_validate {
+            // A provider that can't list its types is left to reject a bad one at launch
+            if let Ok(node_types) = handle.list_node_types().await {
+                if let Some(message) = node::unknown_instance_type(&target.instance_type, &target.provider, &node_types) {
+                    return Err(message);
+                }
+            }
+        }
+        target.instance_type.clone()
+    };
+
+    let request = NodeRequest {
+        instance_type: instance_type.clone(),
+        region: region.clone(),
+        zone: provider_config.zone.clone(),
+        subnet: provider_config.subnet.clone(),
+        disk_gb: disk_gb.or(provider_config.disk_gb),
+        public_key: None,
+        on_status: Some(spinner::status_callback(spinner)),
+        on_launched: None,
+    };
+    if !force {
+        handle.validate_request(&request).await.map_err(|e| e.to_string())?;
+    }
+
+    let estimate = match handle.estimate_cost(&request).await {
+        Ok(estimate) => Some(estimate),
+        Err(e) => {
+            spinner.println(format!("Warning: could not estimate cost on {}: {}", target.provider, e));
+            None
+        }
+    };
+    Ok((instance_type, Prepared { handle, request, region, estimate }))
+}
+
+/// Ask to confirm the combined hourly cost of every node about to launch
+fn confirm_cost(
+    ready: &[(Target, Prepared)],
+    timeout: Option<&str>,
+    timeout_duration: Option<chrono::Duration>,
+    yes: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let estimates: Vec<&CostEstimate> = ready.iter().filter_map(|(_, prepared)| prepared.estimate.as_ref()).collect();
+    let Some(first) = estimates.first() else {
+        eprintln!("Warning: launching without a cost estimate");
+        return Ok(true);
+    };
+    if estimates.len() < ready.len() {
+        eprintln!("Warning: the total below leaves out providers without a cost estimate");
+    }
+
+    let hourly: f64 = estimates.iter().map(|e| e.hourly).sum();
+    let mut question = format!("This will cost ~{}/hr", node::format_cost(hourly, &first.currency));
+    if let (Some(timeout), Some(duration)) = (timeout, timeout_duration) {
+        let total = hourly * duration.to_std().unwrap_or_default().as_secs_f64() / 3600.0;
+        question.push_str(&format!(", ~{} over the {} timeout", node::format_cost(total, &first.currency), timeout));
+    }
+    question.push_str(". Continue?");
+    prompt::confirm(&question, yes)
+}
+
+fn hook_env(target: &Target, region: &Option<String>) -> Vec<(&'static str, String)> {
+    vec![
+        ("GML_NODE_PROVIDER", target.provider.clone()),
+        ("GML_NODE_INSTANCE_TYPE", target.instance_type.clone()),
+        ("GML_NODE_REGION", region.clone().unwrap_or_default()),
+    ]
+}
+
+fn print_outcomes(outcomes: &[Outcome]) {
+    let mut table = Table::new();
+    table.set_header(vec!["Provider", "Instance Type", "Price/hr", "Node ID", "Address", "Result"]);
+    for outcome in outcomes {
+        let price = outcome.hourly.as_ref().map_or("-".to_string(), |e| node::format_cost(e.hourly, &e.currency));
+        let (id, address, result) = match &outcome.result {
+            Ok(launched) => (launched.id.clone(), format!("{}@{}", launched.user, launched.ip), "created".to_string()),
+            Err(e) => ("-".to_string(), "-".to_string(), format!("failed: {}", e)),
+        };
+        table.add_row(vec![
+            Cell::new(&outcome.target.provider),
+            Cell::new(&outcome.target.instance_type),
+            Cell::new(price),
+            Cell::new(id),
+            Cell::new(address),
+            Cell::new(result),
+        ]);
+    }
+    println!("{}", table);
+}
+
@@ -1,124 +1,1213 @@
 use chrono::Utc;
-use gml_core::{NodeRequest, NodeDetails};
+use clap::Args;
+use comfy_table::{Cell, Table};
+use gml_core::{CostEstimate, NodeDetails, NodeProvider, NodeRequest, NodeType};
+use gml_core::boot_stats;
+use gml_core::error::GmlError;
+use gml_core::journal::{self, Operation};
+use gml_core::net;
+use gml_core::paths;
 use gml_core::ssh;
-use gml_core::state::GmlState;
-use std::process::{Command, Stdio};
+use gml_core::suggest;
+use gml_core::state::{self, GmlState, NewNode, NodeEntry};
+use std::process::Command;
+use std::collections::HashMap;
 use std::env;
-use std::time::Duration;
 use std::path::Path;
 use std::fs;
-use sysinfo::System;
-use indicatif::ProgressBar;
+use std::io::IsTerminal;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use humantime::parse_duration;
-use dirs;
+use indicatif::ProgressBar;
 use serde_json;
 
-use crate::config;
+use crate::config::{self, Config, NodeTemplate};
+use crate::daemon;
+use crate::hooks::{self, Hook};
+use crate::launch;
+use crate::multi_provider;
+use crate::prompt;
 use crate::providers;
 use crate::spinner;
+use crate::timings;
 use crate::sh;
 
-pub async fn handle_create_node(provider: String, instance_type: String, timeout: String, region: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
-    let spinner = spinner::create_spinner();
+/// `--instance-type` value that picks the cheapest type the provider has capacity for
+pub const CHEAPEST_INSTANCE_TYPE: &str = "cheapest";
+/// `--instance-type` value that reads the type from stdin, e.g. piped from a picker like fzf
+pub const STDIN_INSTANCE_TYPE: &str = "-";
+
+#[derive(Args, Debug)]
+pub struct CreateNodeArgs {
+    /// Provider to launch on. Give several (repeated or comma-separated) to launch one node on each
+    /// in parallel, optionally as `<provider>=<instance type>`
+    #[arg(short, long, value_delimiter = ',', required_unless_present = "template")]
+    pub provider: Vec<String>,
+    /// Named account to launch in, from a `[<provider>.<account>]` table in config
+    #[arg(long)]
+    pub account: Option<String>,
+    /// Instance type to launch, `cheapest` for the cheapest type with capacity, or `-` to read it from stdin.
+    /// Give several (repeated or comma-separated) to try them in order until one launches
+    #[arg(short, long, visible_alias = "instance-type-list", value_delimiter = ',')]
+    pub instance_type: Vec<String>,
+    /// Also try every region with capacity for each instance type, the selected region first
+    #[arg(long)]
+    pub auto_region: bool,
+    /// With `--instance-type cheapest`, only consider types with at least this much memory per GPU, in GB
+    #[arg(long)]
+    pub min_vram: Option<u32>,
+    /// How long until the daemon deletes the node (e.g. "2h", "1h30m"); optional for reserved nodes
+    #[arg(short, long, required_unless_present_any = ["reserved", "template"])]
+    pub timeout: Option<String>,
+    /// When --timeout expires, run the `soft-timeout` hook and only delete the node this much later
+    /// (e.g. "30m"), to leave time to save a checkpoint
+    #[arg(long)]
+    pub grace: Option<String>,
+    /// Region to launch in (defaults to `region` in the provider config)
+    #[arg(short, long)]
+    pub region: Option<String>,
+    /// Availability zone to launch into (defaults to `zone` in the provider config)
+    #[arg(long)]
+    pub zone: Option<String>,
+    /// Subnet to attach the node to (defaults to `subnet` in the provider config)
+    #[arg(long)]
+    pub subnet: Option<String>,
+    /// Disk size in GB (defaults to `disk-gb` in the provider config, else the provider's default)
+    #[arg(long = "disk")]
+    pub disk_gb: Option<u32>,
+    /// Tag the node as key=value; repeat for multiple tags. Also applied on the provider when supported
+    #[arg(long = "tag", value_parser = parse_tag)]
+    pub tags: Vec<(String, String)>,
+    /// Mark the node as long-lived reserved capacity that the daemon never deletes
+    #[arg(long)]
+    pub reserved: bool,
+    /// Name the node `<repo>-<branch>` after the git repository in the current directory, and tag
+    /// it with the repo, branch and commit
+    #[arg(long)]
+    pub label_from_git: bool,
+    /// Launch without asking to confirm the estimated cost
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Skip pre-launch checks against the provider, such as looking up the configured SSH key
+    #[arg(long)]
+    pub force: bool,
+    /// Don't check the instance type against the provider's list of types before launching
+    #[arg(long)]
+    pub no_validate: bool,
+    /// Authorize this private key's public key on the node instead of the configured key,
+    /// registering it with the provider if needed. `gml ssh` and `gml connect` then log in with it
+    #[arg(long, value_name = "PATH")]
+    pub private_key_from: Option<String>,
+    /// While waiting for boot, show a progress bar based on how long this instance type usually takes
+    #[arg(long)]
+    pub progress_bar: bool,
+    /// On success, print only `export GML_NODE_*=...` lines to stdout, for use with `eval`
+    #[arg(long)]
+    pub output_env: bool,
+    /// Stay attached to a tmux session on the node; when it ends, choose whether to delete the node
+    #[arg(long, conflicts_with = "output_env")]
+    pub hold: bool,
+    /// Fill in any options not given on the command line from `[template.<name>]` in config
+    #[arg(long)]
+    pub template: Option<String>,
+    /// Save this invocation's parameters as `[template.<name>]` in config
+    #[arg(long)]
+    pub save_template: Option<String>,
+    /// Resolve the config, provider and request and print the launch that would be made, without
+    /// launching anything or changing state
+    #[arg(long, conflicts_with_all = ["hold", "output_env", "save_template"])]
+    pub dry_run: bool,
+}
 
-    ensure_daemon_running(&spinner).await?;
+pub async fn handle_create_node(args: CreateNodeArgs) -> Result<(), Box<dyn std::error::Error>> {
+    if args.provider.len() > 1 {
+        return multi_provider::handle_create_on_providers(args).await;
+    }
+    let CreateNodeArgs {
+        provider, account, instance_type, min_vram, timeout, grace, region, zone, subnet, disk_gb, tags, reserved,
+        label_from_git, auto_region, yes, force, no_validate, private_key_from, progress_bar, output_env, hold, template,
+        save_template, dry_run,
+    } = args;
+
+    if hold && !std::io::stdin().is_terminal() {
+        return Err("--hold needs an interactive terminal".into());
+    }
 
     // Parse config from ~/.gml/config.toml
+    let phase = timings::phase("parse config");
     let config = config::parse_config()?;
+    drop(phase);
+
+    // Options given on the command line take precedence over the template's
+    let template = match &template {
+        Some(name) => config.get_template(name)
+            .cloned()
+            .ok_or_else(|| format!("Template '{}' not found in config", name))?,
+        None => NodeTemplate::default(),
+    };
+    let resolved = NodeTemplate {
+        provider: provider.into_iter().next().or(template.provider),
+        instance_type: if instance_type.is_empty() { template.instance_type } else { Some(instance_type.join(",")) },
+        region: region.or(template.region),
+        zone: zone.or(template.zone),
+        subnet: subnet.or(template.subnet),
+        disk_gb: disk_gb.or(template.disk_gb),
+        timeout: timeout.or(template.timeout),
+        tags: template.tags.into_iter().chain(tags).collect(),
+        reserved: reserved || template.reserved,
+    };
 
-    // Try to get config for the specified provider
-    let provider_config = config.get_provider(&provider)
-        .ok_or_else(|| format!("Provider '{}' not found in config", provider))?;
+    if let Some(name) = &save_template {
+        let question = format!("Template '{}' already exists. Overwrite it?", name);
+        if config.get_template(name).is_none() || prompt::confirm(&question, yes)? {
+            config::save_template(name, &resolved)?;
+            eprintln!("Saved template '{}'", name);
+        } else {
+            eprintln!("Keeping existing template '{}'", name);
+        }
+    }
+
+    let NodeTemplate { provider, instance_type, region, zone, subnet, disk_gb, timeout, mut tags, reserved } = resolved;
+    let provider = provider.ok_or("--provider is required")?;
+    let instance_types: Vec<String> = instance_type
+        .ok_or("--instance-type is required")?
+        .split(',')
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect();
+    let Some(instance_type) = instance_types.first().cloned() else {
+        return Err("--instance-type is required".into());
+    };
+    if instance_types.len() > 1 && instance_types.iter().any(|t| t == CHEAPEST_INSTANCE_TYPE || t == STDIN_INSTANCE_TYPE) {
+        return Err(format!(
+            "--instance-type {} and {} can't be part of a list of instance types",
+            CHEAPEST_INSTANCE_TYPE, STDIN_INSTANCE_TYPE
+        ).into());
+    }
+    if timeout.is_none() && !reserved {
+        return Err("--timeout is required unless the node is --reserved".into());
+    }
+    if min_vram.is_some() && instance_type != CHEAPEST_INSTANCE_TYPE {
+        return Err(format!("--min-vram only applies with --instance-type {}", CHEAPEST_INSTANCE_TYPE).into());
+    }
+    let from_stdin = instance_type == STDIN_INSTANCE_TYPE;
+    let instance_type = if from_stdin {
+        read_instance_type_from_stdin()?
+    } else {
+        instance_type
+    };
+
+    let name = if label_from_git { label_from_git_context(&mut tags) } else { None };
+    let private_key = private_key_from.as_deref()
+        .map(ssh::load_private_key)
+        .transpose()
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+    let identity_file = private_key.as_ref().map(|key| key.path.display().to_string());
+
+    let spinner = spinner::create_spinner();
+
+    // Parse timeout duration and calculate expiration time before launching anything
+    let timeout_duration = match &timeout {
+        Some(timeout) => Some(
+            parse_timeout_duration(timeout)
+                .ok_or_else(|| format!("Invalid timeout format: '{}'. Use formats like '1h30m', '2h', '30m'", timeout))?,
+        ),
+        None => None,
+    };
+    let grace = match &grace {
+        Some(grace) => Some(
+            parse_timeout_duration(grace)
+                .ok_or_else(|| format!("Invalid grace format: '{}'. Use formats like '30m', '1h'", grace))?,
+        ),
+        None => None,
+    };
+    if grace.is_some() && timeout_duration.is_none() {
+        return Err("--grace needs a --timeout".into());
+    }
+    // With a grace period, --timeout is the soft deadline and deletion comes after the grace
+    let deadline = timeout_duration.map(|duration| Utc::now() + duration);
+    let soft_timeout = grace.and(deadline).map(|deadline| deadline.to_rfc3339());
+    let timeout_expiration = deadline.map(|deadline| (deadline + grace.unwrap_or_else(chrono::Duration::zero)).to_rfc3339());
+    let billed_duration = timeout_duration.map(|duration| duration + grace.unwrap_or_else(chrono::Duration::zero));
+
+    if !dry_run {
+        let _phase = timings::phase("start daemon");
+        daemon::ensure_daemon_running(&spinner).await?;
+    }
+
+    // Try to get config for the specified provider and account
+    let provider_config = config.get_provider_account(&provider, account.as_deref())?;
 
     // Use the config to create a provider handle
+    let region = region.or_else(|| provider_config.region.clone());
+    let phase = timings::phase("create provider handle");
     let provider_handle = providers::create_provider_handle(
         &provider,
         provider_config,
-        region,
+        region.clone(),
         config.ssh_public_key.clone(),
     )
         .await
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+    drop(phase);
+
+    let cheapest = instance_type == CHEAPEST_INSTANCE_TYPE;
+    let instance_type = if cheapest {
+        spinner.set_message("Finding the cheapest instance type...");
+        let cheapest = cheapest_instance_type(provider_handle.as_ref(), min_vram, region.as_deref()).await?;
+        if let Some(price) = &cheapest.price {
+            spinner.println(format!("Selected {} at {}/hr", cheapest.name, format_cost(price.hourly, &price.currency)));
+        }
+        cheapest.name
+    } else {
+        instance_type
+    };
 
-    let request = NodeRequest {
-        instance_type: instance_type.clone(),
+    // A single type may have been resolved from `cheapest` or stdin above. A list of types, or
+    // --auto-region, is tried one (type, region) pair at a time.
+    let instance_types = if instance_types.len() > 1 { instance_types } else { vec![instance_type] };
+
+    // A mistyped type would otherwise only fail at launch, with the provider's own error
+    if !no_validate && !cheapest {
+        spinner.set_message("Checking instance type...");
+        let _phase = timings::phase("check instance type");
+        match provider_handle.list_node_types().await {
+            Ok(node_types) => {
+                for instance_type in &instance_types {
+                    if let Some(message) = unknown_instance_type(instance_type, &provider, &node_types) {
+                        return Err(format!("{}\n  Pass --no-validate to skip this check", message).into());
+                    }
+                }
+            }
+            Err(e) => spinner.println(format!("Warning: could not check the instance type: {}", e)),
+        }
+    }
+    let candidates = if instance_types.len() > 1 || auto_region {
+        spinner.set_message("Checking capacity...");
+        launch_candidates(provider_handle.as_ref(), &instance_types, region.as_deref(), auto_region, &spinner).await?
+    } else {
+        vec![(instance_types[0].clone(), region.clone())]
+    };
+
+    // CLI placement flags take precedence over the provider's config defaults
+    let zone = zone.or_else(|| provider_config.zone.clone());
+    let subnet = subnet.or_else(|| provider_config.subnet.clone());
+    let disk_gb = disk_gb.or(provider_config.disk_gb);
+    let request_for = |instance_type: &str, region: &Option<String>| NodeRequest {
+        instance_type: instance_type.to_string(),
+        region: region.clone(),
+        zone: zone.clone(),
+        subnet: subnet.clone(),
+        disk_gb,
+        public_key: private_key.as_ref().map(|key| key.public_key.clone()),
+        on_status: Some(spinner::status_callback(&spinner)),
+        on_launched: None,
     };
 
-    spinner.set_message(format!("Creating node with provider {}...", provider));
-    let details = provider_handle.start_node(request)
+    if !force {
+        spinner.set_message("Validating request...");
+        let _phase = timings::phase("validate request");
+        provider_handle.validate_request(&request_for(&candidates[0].0, &candidates[0].1))
+            .await
+            .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+        if let Some(region) = &region {
+            gml_core::check_region(provider_handle.as_ref(), region)
+                .await
+                .map_err(|e| format!("{}\n  Pass --force to skip this check", e))?;
+        }
+    }
+
+    spinner.set_message("Estimating cost...");
+    let phase = timings::phase("estimate cost");
+    let mut estimates: HashMap<String, CostEstimate> = HashMap::new();
+    let mut estimate_error = None;
+    for (instance_type, candidate_region) in &candidates {
+        if estimates.contains_key(instance_type) {
+            continue;
+        }
+        match provider_handle.estimate_cost(&request_for(instance_type, candidate_region)).await {
+            Ok(estimate) => {
+                estimates.insert(instance_type.clone(), estimate);
+            }
+            Err(e) => {
+                estimate_error = Some(e);
+                break;
+            }
+        }
+    }
+    drop(phase);
+    match estimate_error {
+        None => {
+            // With fallbacks, confirm the most expensive type that might launch
+            let estimate = estimates.values()
+                .max_by(|a, b| a.hourly.total_cmp(&b.hourly))
+                .expect("there is at least one candidate");
+            let bound = if estimates.len() > 1 { "up to " } else { "" };
+            let mut question = format!("This will cost {}~{}/hr", bound, format_cost(estimate.hourly, &estimate.currency));
+            if let (Some(timeout), Some(duration)) = (&timeout, billed_duration) {
+                let total = estimate.total_for(duration.to_std().unwrap_or_default());
+                question.push_str(&format!(", {}~{} over the {} timeout", bound, format_cost(total, &estimate.currency), timeout));
+                if let Some(grace) = grace {
+                    question.push_str(&format!(" plus {} grace", humantime::format_duration(grace.to_std().unwrap_or_default())));
+                }
+            }
+            question.push_str(". Continue?");
+
+            if !dry_run && !spinner.suspend(|| prompt::confirm(&question, yes))? {
+                spinner.finish_with_message("Aborted");
+                return Ok(());
+            }
+        }
+        Some(e) => {
+            spinner.println(format!("Warning: could not estimate cost: {}", e));
+            estimates.clear();
+        }
+    }
+
+    if dry_run {
+        spinner.finish_and_clear();
+        // Describe the first launch that would be tried, on a handle for its region
+        let (instance_type, candidate_region) = &candidates[0];
+        let regional_handle = if candidate_region != &region {
+            Some(providers::create_provider_handle(
+                &provider,
+                provider_config,
+                candidate_region.clone(),
+                config.ssh_public_key.clone(),
+            )
+                .await
+                .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?)
+        } else {
+            None
+        };
+        let handle = regional_handle.as_deref().unwrap_or(provider_handle.as_ref());
+        let ssh_key = match &identity_file {
+            Some(path) => format!("public key of {} (--private-key-from)", path),
+            None => provider_config.ssh_key.as_ref().map(|key| key.to_string())
+                .or_else(|| config.ssh_public_key.clone())
+                .unwrap_or_else(|| "default public key in ~/.ssh".to_string()),
+        };
+        let plan = LaunchPlan {
+            provider: &provider,
+            account: account.as_deref(),
+            candidates: &candidates,
+            ssh_key,
+            zone: zone.as_deref(),
+            subnet: subnet.as_deref(),
+            disk_gb,
+            timeout: timeout.as_deref(),
+            grace,
+            estimates: &estimates,
+            api_call: handle.describe_launch(&request_for(instance_type, candidate_region)),
+        };
+        plan.print();
+        return Ok(());
+    }
+
+    let mut launched = None;
+    for (attempt, (instance_type, candidate_region)) in candidates.iter().enumerate() {
+        if candidates.len() > 1 {
+            spinner.println(format!(
+                "Trying {} in {} ({} of {})",
+                instance_type,
+                candidate_region.as_deref().unwrap_or("the default region"),
+                attempt + 1,
+                candidates.len()
+            ));
+        }
+        spinner.suspend(|| hooks::run(&config.hooks, Hook::PreCreate, &[
+            ("GML_NODE_PROVIDER", provider.clone()),
+            ("GML_NODE_INSTANCE_TYPE", instance_type.clone()),
+            ("GML_NODE_REGION", candidate_region.clone().unwrap_or_default()),
+        ]))?;
+
+        // The handle is bound to a region, so other regions need their own
+        let regional_handle = if candidate_region != &region {
+            Some(providers::create_provider_handle(
+                &provider,
+                provider_config,
+                candidate_region.clone(),
+                config.ssh_public_key.clone(),
+            )
+                .await
+                .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?)
+        } else {
+            None
+        };
+        let handle = regional_handle.as_deref().unwrap_or(provider_handle.as_ref());
+
+        let record = NodeRecord {
+            name: name.clone(),
+            provider: provider.clone(),
+            region: candidate_region.clone(),
+            timeout: timeout_expiration.clone(),
+            soft_timeout: soft_timeout.clone(),
+            tags: tags.clone(),
+            reserved,
+            // Stored prices are summed across nodes, so only keep them in a single currency
+            hourly_cost: estimates.get(instance_type).filter(|e| e.currency == "USD").map(|e| e.hourly),
+            cluster_id: None,
+            identity_file: identity_file.clone(),
+            account: account.clone(),
+        };
+        // Without history for this type there is nothing to base progress on, so keep the spinner
+        let progress = if progress_bar {
+            boot_stats::expected(&provider, instance_type)
+                .unwrap_or_else(|e| {
+                    spinner.println(format!("Warning: could not read boot stats: {}", e));
+                    None
+                })
+                .map(|expected| spinner::boot_progress(&spinner, expected))
+        } else {
+            None
+        };
+        let result = launch_node(handle, &config, &spinner, request_for(instance_type, candidate_region), record).await;
+        drop(progress);
+        match result {
+            Ok(node) => {
+                launched = Some((node, instance_type.clone(), candidate_region.clone()));
+                break;
+            }
+            // Falling back now could leave this instance running alongside the next one
+            Err(e) if e.is::<LaunchAccepted>() || attempt + 1 == candidates.len() => return Err(e),
+            Err(e) => spinner.println(format!(
+                "Could not launch {} in {}: {}",
+                instance_type,
+                candidate_region.as_deref().unwrap_or("the default region"),
+                e
+            )),
+        }
+    }
+    let (launched, instance_type, region) = launched.ok_or("No instance type could be launched")?;
+    if candidates.len() > 1 {
+        spinner.println(format!(
+            "Launched {} in {}",
+            instance_type,
+            region.as_deref().unwrap_or("the default region")
+        ));
+    }
+    let LaunchedNode { id: node_id, ip, user, ssh_port } = launched;
+
+    spinner.finish_with_message("Node created successfully!");
+
+    hooks::run(&config.hooks, Hook::PostCreate, &[
+        ("GML_NODE_ID", node_id.clone()),
+        ("GML_NODE_IP", ip.clone()),
+        ("GML_NODE_USER", user.clone()),
+        ("GML_NODE_PROVIDER", provider.clone()),
+        ("GML_NODE_INSTANCE_TYPE", instance_type.clone()),
+        ("GML_NODE_REGION", region.unwrap_or_default()),
+    ])?;
+
+    // The spinner draws to stderr, so these are the only lines on stdout
+    if output_env {
+        println!("export GML_NODE_ID={}", node_id);
+        println!("export GML_NODE_IP={}", ip);
+        println!("export GML_NODE_USER={}", user);
+        println!("export GML_NODE_SSH_PORT={}", ssh_port.unwrap_or(22));
+    }
+
+    if hold {
+        hold_node(node_id, &user, &ip, &ssh_options(ssh_port, identity_file.as_deref())).await?;
+    }
+    Ok(())
+}
+
+/// What `gml node create --dry-run` resolved, printed in place of launching
+struct LaunchPlan<'a> {
+    provider: &'a str,
+    account: Option<&'a str>,
+    candidates: &'a [(String, Option<String>)],
+    ssh_key: String,
+    zone: Option<&'a str>,
+    subnet: Option<&'a str>,
+    disk_gb: Option<u32>,
+    timeout: Option<&'a str>,
+    grace: Option<chrono::Duration>,
+    estimates: &'a HashMap<String, CostEstimate>,
+    api_call: Result<String, GmlError>,
+}
+
+impl LaunchPlan<'_> {
+    fn print(&self) {
+        match self.account {
+            Some(account) => println!("Provider:       {} (account {})", self.provider, account),
+            None => println!("Provider:       {}", self.provider),
+        }
+        for (i, (instance_type, region)) in self.candidates.iter().enumerate() {
+            let label = if i == 0 { "Instance type:" } else { "  then:" };
+            let cost = self.estimates.get(instance_type)
+                .map(|estimate| format!(", ~{}/hr", format_cost(estimate.hourly, &estimate.currency)))
+                .unwrap_or_default();
+            println!(
+                "{:<15} {} in {}{}",
+                label,
+                instance_type,
+                region.as_deref().unwrap_or("the default region"),
+                cost
+            );
+        }
+        println!("SSH key:        {}", self.ssh_key);
+        if let Some(zone) = self.zone {
+            println!("Zone:           {}", zone);
+        }
+        if let Some(subnet) = self.subnet {
+            println!("Subnet:         {}", subnet);
+        }
+        if let Some(disk_gb) = self.disk_gb {
+            println!("Disk:           {} GB", disk_gb);
+        }
+        match (self.timeout, self.grace) {
+            (Some(timeout), Some(grace)) => println!(
+                "Timeout:        {} plus {} grace",
+                timeout,
+                humantime::format_duration(grace.to_std().unwrap_or_default())
+            ),
+            (Some(timeout), None) => println!("Timeout:        {}", timeout),
+            (None, _) => println!("Timeout:        none (reserved)"),
+        }
+        match &self.api_call {
+            Ok(api_call) => {
+                println!("API call:");
+                for line in api_call.lines() {
+                    println!("  {}", line);
+                }
+            }
+            Err(e) => println!("API call:       can't be shown: {}", e),
+        }
+        println!("Nothing was launched (--dry-run)");
+    }
+}
+
+/// Resolve `--instance-type cheapest` against the provider's current listing
+pub async fn cheapest_instance_type(
+    provider_handle: &dyn NodeProvider,
+    min_vram: Option<u32>,
+    region: Option<&str>,
+) -> Result<NodeType, Box<dyn std::error::Error>> {
+    let node_types = provider_handle.list_node_types()
         .await
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+    let cheapest = gml_core::cheapest_node_type(&node_types, min_vram, region)
+        .ok_or("No priced instance type with capacity matches; check `gml node list-types`")?;
+    Ok(cheapest.clone())
+}
+
+/// An error naming the closest match and every valid type when `instance_type` isn't among
+/// `node_types`, or `None` when it is
+pub fn unknown_instance_type(instance_type: &str, provider: &str, node_types: &[NodeType]) -> Option<String> {
+    if node_types.iter().any(|t| t.name == instance_type) {
+        return None;
+    }
+    let mut names: Vec<&str> = node_types.iter().map(|t| t.name.as_str()).collect();
+    names.sort_unstable();
+    let mut message = format!("Unknown instance type '{}' on {}", instance_type, provider);
+    if let Some(closest) = suggest::closest_matches(instance_type, &names).first() {
+        message.push_str(&format!("; did you mean '{}'?", closest));
+    }
+    message.push_str(&format!("\n  Valid types: {}", names.join(", ")));
+    Some(message)
+}
+
+/// The (instance type, region) pairs to try in order, leaving out those the provider reports have
+/// no capacity. With `auto_region` each type is tried in every region with capacity for it.
+async fn launch_candidates(
+    provider_handle: &dyn NodeProvider,
+    instance_types: &[String],
+    region: Option<&str>,
+    auto_region: bool,
+    spinner: &ProgressBar,
+) -> Result<Vec<(String, Option<String>)>, Box<dyn std::error::Error>> {
+    let node_types = match provider_handle.list_node_types().await {
+        Ok(node_types) => node_types,
+        Err(e) if auto_region => return Err(format!("--auto-region needs the provider's capacity by region: {}", e).into()),
+        Err(e) => {
+            spinner.println(format!("Warning: could not check capacity, trying each type in turn: {}", e));
+            Vec::new()
+        }
+    };
+
+    let mut candidates = Vec::new();
+    for instance_type in instance_types {
+        let listed = node_types.iter().find(|t| &t.name == instance_type);
+        if listed.is_none() && !node_types.is_empty() {
+            spinner.println(format!("Skipping {}: not offered by the provider", instance_type));
+            continue;
+        }
+        match listed.and_then(|t| t.regions.clone()) {
+            Some(mut regions) if auto_region => {
+                if regions.is_empty() {
+                    spinner.println(format!("Skipping {}: no capacity in any region", instance_type));
+                }
+                if let Some(position) = region.and_then(|region| regions.iter().position(|r| r == region)) {
+                    let preferred = regions.remove(position);
+                    regions.insert(0, preferred);
+                }
+                candidates.extend(regions.into_iter().map(|r| (instance_type.clone(), Some(r))));
+            }
+            _ if listed.is_some_and(|t| !region.map_or(t.has_capacity(), |region| t.available_in(region))) => {
+                spinner.println(format!(
+                    "Skipping {}: no capacity in {}",
+                    instance_type,
+                    region.unwrap_or("any region")
+                ));
+            }
+            _ => candidates.push((instance_type.clone(), region.map(str::to_string))),
+        }
+    }
+    if candidates.is_empty() {
+        return Err("None of the instance types has capacity; check `gml node list-types`".into());
+    }
+    Ok(candidates)
+}
+
+/// Tag the node with the current git checkout and return its name. Tags given explicitly win
+/// over the ones derived from git.
+pub fn label_from_git_context(tags: &mut HashMap<String, String>) -> Option<String> {
+    match git_context() {
+        Some(git) => {
+            for (key, value) in git.tags() {
+                tags.entry(key).or_insert(value);
+            }
+            Some(git.node_name())
+        }
+        None => {
+            eprintln!("Warning: --label-from-git: the current directory is not in a git repository; not labelling the node");
+            None
+        }
+    }
+}
+
+/// Read `--instance-type -` from the first line of stdin
+fn read_instance_type_from_stdin() -> Result<String, Box<dyn std::error::Error>> {
+    if std::io::stdin().is_terminal() {
+        return Err("--instance-type - reads the type from stdin; pipe it in, e.g. from fzf".into());
+    }
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    let instance_type = line.trim();
+    if instance_type.is_empty() {
+        return Err("--instance-type -: no instance type on stdin".into());
+    }
+    Ok(instance_type.to_string())
+}
+
+/// Attach to a tmux session on a freshly created node, then offer to delete it once the session ends.
+///
+/// Detaching (or losing the connection) only ends the session; the node keeps running unless deletion
+/// is confirmed. While attached the node is marked in use so the daemon won't delete it on timeout.
+async fn hold_node(node_id: String, user: &str, ip: &str, ssh_options: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let in_use = GmlState::mark_node_in_use(&node_id)?;
+
+    eprintln!("Attaching to {}@{} (detach with Ctrl-b d)...", user, ip);
+    // Fall back to a login shell on images without tmux
+    let session_cmd = format!(
+        "ssh -t -o StrictHostKeyChecking=no{} {}@{} 'command -v tmux >/dev/null && exec tmux new-session -A -s gml || exec bash -l'",
+        ssh_options, user, ip
+    );
+    if let Err(e) = sh::run(&session_cmd) {
+        eprintln!("Warning: session ended with an error: {}", e);
+    }
+    drop(in_use);
+
+    let question = format!("Delete node {} now? Otherwise it keeps running until its timeout", node_id);
+    if prompt::confirm_default_no(&question)? {
+        handle_delete_node(Some(node_id), false, true, true).await
+    } else {
+        eprintln!("Node {} left running", node_id);
+        Ok(())
+    }
+}
+
+pub async fn handle_delete_node(id: Option<String>, force: bool, now: bool, yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
+    let spinner = spinner::create_spinner();
+
+    spinner.set_message("Locating node...");
     
+    // Find the node in state, by gml ID or by the instance ID from the provider's console
+    let node = GmlState::load()?.find_node(&id)?.clone();
+    let id = node.id.clone();
+
+    if let Some(pid) = node.active_session() {
+        if !force {
+            return Err(format!(
+                "Node '{}' is in use by a gml session (pid {}); pass --force to delete it anyway",
+                id, pid
+            ).into());
+        }
+    }
+
+    spinner.set_message("Parsing configuration...");
+    let config = config::parse_config()?;
+
+    // With a grace period, only schedule the deletion; the daemon carries it out unless undone
+    if !now && !config.delete_grace_period.is_zero() {
+        if let Some(at) = &node.pending_delete_at {
+            spinner.finish_and_clear();
+            return Err(format!(
+                "Node '{}' is already scheduled for deletion at {}; pass --now to delete it immediately",
+                id, at
+            ).into());
+        }
+        let grace = chrono::Duration::from_std(config.delete_grace_period)
+            .map_err(|e| format!("Invalid delete-grace-period: {}", e))?;
+        let delete_at = (Utc::now() + grace).to_rfc3339();
+        daemon::ensure_daemon_running(&spinner).await?;
+        GmlState::update(|state| {
+            state.node_mut(&id)?.pending_delete_at = Some(delete_at.clone());
+            Ok(())
+        })?;
+        spinner.finish_with_message(format!(
+            "Node {} will be deleted in {} (at {}); run `gml node undo {}` to cancel",
+            id, humantime::format_duration(config.delete_grace_period), delete_at, id
+        ));
+        return Ok(());
+    }
+
+    let question = format!(
+        "Delete node {} ({} {} at {})? This terminates the instance",
+        id, node.provider, node.instance_type, node.ip
+    );
+    if !spinner.suspend(|| prompt::confirm(&question, yes))? {
+        spinner.finish_and_clear();
+        println!("Aborted");
+        return Ok(());
+    }
+
+    let hooks = &config.hooks;
+    let hook_env = [
+        ("GML_NODE_ID", node.id.clone()),
+        ("GML_NODE_IP", node.ip.clone()),
+        ("GML_NODE_PROVIDER", node.provider.clone()),
+    ];
+    spinner.suspend(|| hooks::run(hooks, Hook::PreDelete, &hook_env))?;
+
+    let phase = timings::phase("create provider handle");
+    let provider_handle = provider_handle_for_node(&config, &node).await?;
+    drop(phase);
+
+    spinner.set_message(format!("Stopping node with provider {}...", node.provider));
+    terminate_node(provider_handle.as_ref(), &node).await?;
+
+    spinner.finish_with_message("Node deleted successfully!");
+    hooks::run(hooks, Hook::PostDelete, &hook_env)?;
+    Ok(())
+}
+
+/// Cancel a deletion scheduled by `gml node delete` during its grace period
+pub fn handle_undo_delete(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
+    GmlState::update(|state| {
+        let node = state.node_mut(&id)?;
+        if node.pending_delete_at.take().is_none() {
+            return Err(GmlError::from(format!("Node '{}' is not scheduled for deletion", id)));
+        }
+        Ok(())
+    })?;
+    println!("Deletion of node {} cancelled", id);
+    Ok(())
+}
+
+/// What to store about a node once its launch succeeds
+pub struct NodeRecord {
+    pub name: Option<String>,
+    pub provider: String,
+    pub region: Option<String>,
+    /// Expiration time as an RFC3339 timestamp
+    pub timeout: Option<String>,
+    /// Time the `soft-timeout` hook runs, before `timeout`, as an RFC3339 timestamp
+    pub soft_timeout: Option<String>,
+    pub tags: HashMap<String, String>,
+    pub reserved: bool,
+    pub hourly_cost: Option<f64>,
+    pub cluster_id: Option<String>,
+    /// Private key the node is logged into with, from `--private-key-from`
+    pub identity_file: Option<String>,
+    /// Named provider account the node is launched in, from `--account`
+    pub account: Option<String>,
+}
+
+/// A launch that failed after the provider accepted it, so the instance may still be running
+#[derive(Debug)]
+pub struct LaunchAccepted(String);
+
+impl std::fmt::Display for LaunchAccepted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for LaunchAccepted {}
+
+/// A node that is up and saved in state
+pub struct LaunchedNode {
+    pub id: String,
+    pub ip: String,
+    pub user: String,
+    pub ssh_port: Option<u16>,
+}
+
+/// Launch one node and save it to state, journaling the launch so an interrupted run can be
+/// settled with `gml resume`. Shared by `gml node create` and cluster provisioning.
+pub async fn launch_node(
+    provider_handle: &dyn NodeProvider,
+    config: &Config,
+    spinner: &ProgressBar,
+    mut request: NodeRequest,
+    record: NodeRecord,
+) -> Result<LaunchedNode, Box<dyn std::error::Error>> {
+    // Journal the launch so `gml resume` can terminate the instance if we die before saving it
+    let operation = Operation::launch(&record.provider, record.region.clone(), request.zone.clone())
+        .in_account(record.account.clone());
+    journal::record(&operation)?;
+    let launched_at: Arc<Mutex<Option<Instant>>> = Arc::new(Mutex::new(None));
+    request.on_launched = Some({
+        let operation = operation.clone();
+        let launched_at = launched_at.clone();
+        let spinner = spinner.clone();
+        Arc::new(move |provider_id: &str, _region: Option<&str>| {
+            *launched_at.lock().unwrap() = Some(Instant::now());
+            if let Err(e) = operation.launched(provider_id) {
+                spinner.println(format!("Warning: could not journal launch: {}", e));
+            }
+        })
+    });
+
+    spinner.set_message(format!("Creating node with provider {}...", record.provider));
+    let launch_slot = launch::acquire_slot(config).await;
+    let instance_type = request.instance_type.clone();
+    let request_disk_gb = request.disk_gb;
+    let launch_started = Instant::now();
+    let result = provider_handle.start_node(request).await;
+    let launch_elapsed = launch_started.elapsed();
+    // The provider reports the instance ID between accepting the launch and waiting for boot
+    let launched_at = *launched_at.lock().unwrap();
+    match launched_at {
+        Some(at) => {
+            timings::record("launch request", at - launch_started);
+            timings::record("boot poll", at.elapsed());
+        }
+        None => timings::record("launch", launch_started.elapsed()),
+    }
+    let details = match result {
+        Ok(details) => details,
+        Err(e) if launched_at.is_some() => {
+            return Err(Box::new(LaunchAccepted(format!(
+                "{}\nThe instance may still be running; run `gml resume` to terminate it",
+                e
+            ))));
+        }
+        Err(e) => {
+            operation.done()?;
+            return Err(Box::from(e));
+        }
+    };
+    drop(launch_slot);
+
+    if let Err(e) = boot_stats::record(&record.provider, &instance_type, launch_elapsed) {
+        spinner.println(format!("Warning: could not record boot time: {}", e));
+    }
+
+    if !record.tags.is_empty() {
+        let _phase = timings::phase("apply tags");
+        spinner.set_message("Applying tags...");
+        // Tags are always kept locally; failing to push them to the provider shouldn't lose the node
+        if let Err(e) = provider_handle.set_tags(&details, &record.tags).await {
+            spinner.println(format!("Warning: tags were only saved locally: {}", e));
+        }
+    }
+
     let user = provider_handle.get_user()
         .await
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
-    
-    // Parse timeout duration and calculate expiration time
-    let timeout_expiration = parse_timeout_duration(&timeout)
-        .map(|duration| {
-            let expiration = Utc::now() + duration;
-            expiration.to_rfc3339()
-        });
-    
-    GmlState::add_node(details, provider.clone(), instance_type.clone(), timeout_expiration, user)
+
+    let ip = details.ip.clone();
+    let ssh_port = details.ssh_port;
+    let _phase = timings::phase("save state");
+    let new_node = NewNode {
+        details,
+        name: record.name,
+        provider: record.provider,
+        region: record.region,
+        instance_type,
+        timeout: record.timeout,
+        soft_timeout: record.soft_timeout,
+        user: user.clone(),
+        tags: record.tags,
+        reserved: record.reserved,
+        hourly_cost: record.hourly_cost,
+        disk_gb: request_disk_gb,
+        cluster_id: record.cluster_id,
+        identity_file: record.identity_file,
+        account: record.account,
+    };
+    let id = GmlState::update(|state| state.add_node(new_node))
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+    operation.done()?;
 
-    spinner.finish_with_message("Node created successfully!");
+    Ok(LaunchedNode { id, ip, user, ssh_port })
+}
+
+/// Stop a tracked node with its provider and remove it from state, journaling the termination
+pub async fn terminate_node(provider_handle: &dyn NodeProvider, node: &NodeEntry) -> Result<(), Box<dyn std::error::Error>> {
+    let operation = Operation::terminate(&node.provider, &node.id, &node.provider_id, node.region.clone(), node.zone.clone())
+        .in_account(node.account.clone());
+    journal::record(&operation)?;
+
+    let phase = timings::phase("stop node");
+    let result = provider_handle.stop_node(node_details(node)).await;
+    drop(phase);
+    if let Err(e) = result {
+        // The node is still running and still tracked, so there is nothing to resume
+        operation.done()?;
+        return Err(Box::from(e));
+    }
+
+    let _phase = timings::phase("save state");
+    GmlState::update(|state| state.remove_node(&node.id))?;
+    operation.done()?;
     Ok(())
 }
 
-pub async fn handle_delete_node(id: String) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn handle_pause_node(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
     let spinner = spinner::create_spinner();
 
     spinner.set_message("Locating node...");
-    
-    // Find the node in state
-    let node = match GmlState::get_node(&id)? {
+    let node = match GmlState::load()?.node(&id).cloned() {
         Some(n) => n,
         None => return Err(format!("Node with ID '{}' not found", id).into()),
     };
+    if node.paused {
+        return Err(format!("Node '{}' is already paused", id).into());
+    }
+    if let Some(pid) = node.active_session() {
+        return Err(format!("Node '{}' is in use by a gml session (pid {})", id, pid).into());
+    }
 
     spinner.set_message("Parsing configuration...");
     let config = config::parse_config()?;
-    let provider_config = config.get_provider(&node.provider)
-        .ok_or_else(|| format!("Provider '{}' not found in config", node.provider))?;
+    let provider_handle = provider_handle_for_node(&config, &node).await?;
 
-    let provider_handle = providers::create_provider_handle(
+    spinner.set_message(format!("Pausing node with provider {}...", node.provider));
+    provider_handle.pause_node(&node_details(&node))
+        .await
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    GmlState::update(|state| {
+        let node = state.node_mut(&id)?;
+        node.paused = true;
+        node.status = state::STATUS_PAUSED.to_string();
+        Ok(())
+    })?;
+
+    spinner.finish_with_message(format!("Node paused; resume it with `gml node resume {}`", id));
+    Ok(())
+}
+
+pub async fn handle_resume_node(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
+    let spinner = spinner::create_spinner();
+
+    spinner.set_message("Locating node...");
+    let node = match GmlState::load()?.node(&id).cloned() {
+        Some(n) => n,
+        None => return Err(format!("Node with ID '{}' not found", id).into()),
+    };
+    if !node.paused {
+        return Err(format!("Node '{}' is not paused", id).into());
+    }
+
+    spinner.set_message("Parsing configuration...");
+    let config = config::parse_config()?;
+    let provider_handle = provider_handle_for_node(&config, &node).await?;
+
+    spinner.set_message(format!("Resuming node with provider {}...", node.provider));
+    let details = provider_handle.resume_node(&node_details(&node))
+        .await
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    // Providers hand out a fresh public IP on restart; keep the old one if none was reported
+    let ip = Some(details.ip).filter(|ip| !ip.is_empty());
+    GmlState::update(|state| {
+        let node = state.node_mut(&id)?;
+        node.paused = false;
+        node.status = state::STATUS_ACTIVE.to_string();
+        if let Some(ip) = &ip {
+            node.ip = ip.clone();
+        }
+        Ok(())
+    })?;
+
+    spinner.finish_with_message(format!("Node resumed at {}", ip.unwrap_or(node.ip)));
+    Ok(())
+}
+
+/// Error out early when a node is paused, since it can't be reached until it is resumed
+pub fn ensure_not_paused(node: &NodeEntry) -> Result<(), Box<dyn std::error::Error>> {
+    if node.paused {
+        return Err(format!("Node '{}' is paused; run `gml node resume {}` first", node.id, node.id).into());
+    }
+    Ok(())
+}
+
+/// Provider handle for an existing node, configured from the current config of its account
+pub async fn provider_handle_for_node(config: &Config, node: &NodeEntry) -> Result<Box<dyn NodeProvider>, Box<dyn std::error::Error>> {
+    let provider_config = config.get_provider_account(&node.provider, node.account.as_deref())?;
+
+    providers::create_provider_handle(
         &node.provider,
         provider_config,
         None,
         config.ssh_public_key.clone(),
     )
         .await
-        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)
+}
 
-    let details = NodeDetails {
+pub fn node_details(node: &NodeEntry) -> NodeDetails {
+    NodeDetails {
         id: node.provider_id.clone(),
         ip: node.ip.clone(),
-    };
+        zone: node.zone.clone(),
+        ssh_port: node.ssh_port,
+        status: Some(node.status.clone()),
+    }
+}
 
-    spinner.set_message(format!("Stopping node with provider {}...", node.provider));
-    provider_handle.stop_node(details)
+/// Time TCP connections to a node's SSH port, to see how responsive interactive work on it will be
+pub fn handle_ping_node(id: Option<String>, count: u32) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
+    let node = GmlState::load()?
+        .node(&id)
+        .cloned()
+        .ok_or_else(|| format!("Node with ID '{}' not found", id))?;
+    ensure_not_paused(&node)?;
+
+    let details = node_details(&node);
+    let address = format!("{}:{}", node.ip, node.ssh_port.unwrap_or(22));
+    let mut latencies = Vec::new();
+    for attempt in 0..count {
+        if attempt > 0 {
+            std::thread::sleep(std::time::Duration::from_secs(1));
+        }
+        match net::tcp_latency(&details, net::CONNECT_TIMEOUT) {
+            Ok(latency) => {
+                println!("Connected to {} in {}", address, format_latency(latency));
+                latencies.push(latency);
+            }
+            Err(e) => println!("No connection to {}: {}", address, e.message()),
+        }
+    }
+
+    if latencies.is_empty() {
+        return Err(format!("Node {} did not answer on {}", node.id, address).into());
+    }
+    let min = latencies.iter().min().copied().unwrap_or_default();
+    let avg = latencies.iter().sum::<std::time::Duration>() / latencies.len() as u32;
+    println!(
+        "{} of {} connected, min {}, avg {}",
+        latencies.len(), count, format_latency(min), format_latency(avg)
+    );
+    Ok(())
+}
+
+/// Latency in milliseconds, e.g. `23.4 ms`
+pub fn format_latency(latency: std::time::Duration) -> String {
+    format!("{:.1} ms", latency.as_secs_f64() * 1000.0)
+}
+
+/// Open an interactive SSH session on a node, with the terminal passed straight through
+pub async fn handle_ssh_node(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
+    let node = GmlState::load()?
+        .node(&id)
+        .cloned()
+        .ok_or_else(|| format!("Node with ID '{}' not found", id))?;
+    ensure_not_paused(&node)?;
+
+    let config = config::parse_config()?;
+    let provider_handle = provider_handle_for_node(&config, &node).await?;
+    let user = provider_handle.get_user()
         .await
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
 
-    spinner.set_message("Removing from state...");
-    GmlState::remove_node(&id)?;
+    let _in_use = GmlState::mark_node_in_use(&node.id)?;
+    let mut ssh = Command::new("ssh");
+    ssh.args(["-o", "StrictHostKeyChecking=no"]);
+    if let Some(identity_file) = &node.identity_file {
+        ssh.args(["-i", identity_file]);
+    }
+    if let Some(port) = node.ssh_port {
+        ssh.args(["-p", &port.to_string()]);
+    }
+    let status = ssh.arg(format!("{}@{}", user, node.ip))
+        .status()
+        .map_err(|e| format!("Failed to run ssh: {}", e))?;
+    // 255 is ssh's own failure; anything else is the exit status of the remote shell
+    if status.code() == Some(255) {
+        return Err(format!("ssh to {}@{} failed", user, node.ip).into());
+    }
+    Ok(())
+}
+
+pub fn handle_describe_node(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
+    let node = match GmlState::load()?.node(&id).cloned() {
+        Some(n) => n,
+        None => return Err(format!("Node with ID '{}' not found", id).into()),
+    };
+
+    println!("ID:             {}", node.id);
+    println!("Name:           {}", node.name.as_deref().unwrap_or("-"));
+    println!("Cluster:        {}", node.cluster_id.as_deref().unwrap_or("-"));
+    println!("Provider ID:    {}", node.provider_id);
+    println!("Provider:       {}", node.provider);
+    println!("Account:        {}", node.account.as_deref().unwrap_or("default"));
+    println!("Instance Type:  {}", node.instance_type);
+    println!("IP:             {}", node.ip);
+    println!("User:           {}", node.user);
+    println!("Region:         {}", node.region.as_deref().unwrap_or("-"));
+    println!("Zone:           {}", node.zone.as_deref().unwrap_or("-"));
+    println!("Created At:     {}", node.created_at);
+    println!("Timeout:        {}", node.timeout.as_deref().unwrap_or("None"));
+    if let Some(soft_timeout) = &node.soft_timeout {
+        let fired = if node.soft_timeout_fired { " (hook has run)" } else { "" };
+        println!("Soft timeout:   {}{}", soft_timeout, fired);
+    }
+    println!("Reserved:       {}", if node.reserved { "yes" } else { "no" });
+    println!("Status:         {}", node.status);
+    println!("Disk:           {}", node.disk_gb.map_or("default".to_string(), |gb| format!("{} GB", gb)));
+    println!("Hourly Cost:    {}", node.hourly_cost.map_or("-".to_string(), |cost| format_cost(cost, "USD")));
+
+    let mut tags: Vec<_> = node.tags.iter().collect();
+    tags.sort();
+    if tags.is_empty() {
+        println!("Tags:           -");
+    } else {
+        println!("Tags:");
+        for (key, value) in tags {
+            println!("  {}={}", key, value);
+        }
+    }
 
-    spinner.finish_with_message("Node deleted successfully!");
     Ok(())
 }
 
-pub fn handle_connect_command(id: String) -> Result<(), Box<dyn std::error::Error>> {
+pub fn handle_connect_command(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
     let spinner = spinner::create_spinner();
 
     spinner.set_message("Locating node...");
     
     // Get node data from state with id
-    let node = match GmlState::get_node(&id)? {
+    let node = match GmlState::load()?.node(&id).cloned() {
         Some(n) => n,
         None => return Err(format!("Node with ID '{}' not found", id).into()),
     };
+    ensure_not_paused(&node)?;
 
     spinner.set_message("Getting current working directory...");
     let current_dir = env::current_dir()?;
@@ -130,34 +1219,33 @@ pub fn handle_connect_command(id: String) -> Result<(), Box<dyn std::error::Erro
     // Check if in a git directory
     let is_git_dir = current_dir.join(".git").exists();
 
-    spinner.set_message(format!("Copying directory to {}@{}...", node.user, node.ip));
-    
-    // Create remote directory first
-    let remote_dir = format!("/home/{}/{}", node.user, dir_name);
-    let ssh_cmd = format!("ssh -o StrictHostKeyChecking=no {}@{}", node.user, node.ip);
-    let mkdir_cmd = format!("mkdir -p {}", remote_dir);
-    
-    sh::run(&format!("{} '{}'", ssh_cmd, mkdir_cmd))
-        .map_err(|e| format!("Failed to create remote directory: {}", e))?;
+    // Keep the daemon from deleting the node on timeout while we set it up
+    let _in_use = GmlState::mark_node_in_use(&node.id)?;
 
-    // Build rsync exclude patterns from .gitignore
-    let mut exclude_patterns = vec!["--exclude".to_string(), ".git".to_string()];
-    if let Ok(patterns) = read_gitignore_patterns(&current_dir) {
-        for pattern in patterns {
-            exclude_patterns.push("--exclude".to_string());
-            exclude_patterns.push(pattern);
-        }
-    }
+    let remote_dir = remote_workspace_dir(&node, dir_name);
+    let ssh_cmd = format!("ssh -o StrictHostKeyChecking=no{} {}@{}", ssh_options(node.ssh_port, node.identity_file.as_deref()), node.user, node.ip);
 
-    // Copy FROM local TO remote
-    let exclude_args = exclude_patterns.join(" ");
-    let rsync_cmd = format!(
-        "rsync -avz --quiet {} {}/ {}@{}:{}/",
-        exclude_args, current_dir.display(), node.user, node.ip, remote_dir
+    spinner.set_message("Copying SSH key...");
+    let app_config = config::parse_config().map_err(|e| e.to_string())?;
+    let key_path = ssh::get_ssh_public_key(app_config.ssh_public_key.as_deref())
+        .map_err(|e| e.to_string())?;
+
+    // Copy SSH public key to remote machine's authorized_keys, once
+    let copy_key_cmd = format!(
+        "cat {} | {} 'mkdir -p ~/.ssh && key=$(cat) && (grep -qxF \"$key\" ~/.ssh/authorized_keys 2>/dev/null || echo \"$key\" >> ~/.ssh/authorized_keys) && chmod 600 ~/.ssh/authorized_keys && chmod 700 ~/.ssh'",
+        sh::quote(&key_path.display().to_string()),
+        ssh_cmd
     );
+    sh::run(&copy_key_cmd)
+        .map_err(|e| format!("Failed to copy SSH key: {}", e))?;
 
-    sh::run(&rsync_cmd)
-        .map_err(|_| -> Box<dyn std::error::Error> { "Failed to copy directory to remote machine".into() })?;
+    // The editor connects through this host entry, which also carries the port and agent forwarding
+    spinner.set_message("Configuring SSH host...");
+    let home_dir = paths::home_dir()?;
+    let host = configure_ssh_host(&home_dir, &node)?;
+
+    spinner.set_message(format!("Copying directory to {}@{}...", node.user, node.ip));
+    sync_workspace(&node, &current_dir, &remote_dir, "-avz --quiet")?;
 
     // If in a git directory, copy .git directory and configure git ssh
     if is_git_dir {
@@ -172,8 +1260,8 @@ pub fn handle_connect_command(id: String) -> Result<(), Box<dyn std::error::Erro
         // Using trailing slashes to copy contents (not the directory itself)
         // Using --delete to ensure clean sync and remove stale files
         let git_rsync_cmd = format!(
-            "rsync -avz --quiet --delete {}/.git/ {}@{}:{}/.git/",
-            current_dir.display(), node.user, node.ip, remote_dir
+            "rsync -avz --quiet --delete{} {}/.git/ {}@{}:{}/.git/",
+            rsync_shell_args(&node), current_dir.display(), node.user, node.ip, remote_dir
         );
 
         sh::run(&git_rsync_cmd)
@@ -181,20 +1269,6 @@ pub fn handle_connect_command(id: String) -> Result<(), Box<dyn std::error::Erro
 
         spinner.set_message("Configuring Git SSH...");
 
-        let app_config = config::parse_config().map_err(|e| e.to_string())?;
-        let key_path = ssh::get_ssh_public_key(app_config.ssh_public_key.as_deref())
-            .map_err(|e| e.to_string())?;
-
-        // Copy SSH public key to remote machine's authorized_keys
-        let copy_key_cmd = format!(
-            "cat {} | {} 'mkdir -p ~/.ssh && cat >> ~/.ssh/authorized_keys && chmod 600 ~/.ssh/authorized_keys && chmod 700 ~/.ssh'",
-            key_path.display(),
-            ssh_cmd
-        );
-
-        sh::run(&copy_key_cmd)
-            .map_err(|e| format!("Failed to copy SSH key: {}", e))?;
-
         // Configure git to use SSH for this repository (local config, not global)
         let git_config_cmd = format!(
             "{} 'cd {} && git config --local url.\"git@github.com:\".insteadOf \"https://github.com/\"'",
@@ -223,12 +1297,6 @@ pub fn handle_connect_command(id: String) -> Result<(), Box<dyn std::error::Erro
                 .map_err(|e| format!("Failed to set git user.email: {}", e))?;
         }
 
-        // Configure LOCAL SSH config to enable agent forwarding when connecting to this host
-        // This allows Cursor's SSH connection to forward your local SSH agent
-        spinner.set_message("Configuring SSH agent forwarding...");
-        let home_dir = dirs::home_dir().ok_or("Unable to determine home directory")?;
-        configure_local_ssh_agent_forwarding(&home_dir, &node.ip)?;
-
         // Add GitHub to known_hosts on remote to avoid host verification prompts
         let add_known_hosts_cmd = format!(
             "{} 'ssh-keyscan -t ed25519,rsa github.com >> ~/.ssh/known_hosts 2>/dev/null || true'",
@@ -247,68 +1315,267 @@ pub fn handle_connect_command(id: String) -> Result<(), Box<dyn std::error::Erro
             .map_err(|e| format!("Failed to reset git index: {}", e))?;
     }
 
-    spinner.set_message("Connecting with Cursor...");
-    
-    // Run cursor --folder-uri vscode-remote://ssh-remote+<user>@<hostname>/<folder_path>
-    let folder_uri = format!("vscode-remote://ssh-remote+{}@{}/{}", node.user, node.ip, remote_dir);
-    let cursor_cmd = format!("cursor --folder-uri {}", folder_uri);
+    // Prefer Cursor, falling back to VS Code, which takes the same arguments
+    let (editor, editor_name) = if on_path("cursor") {
+        ("cursor", "Cursor")
+    } else if on_path("code") {
+        ("code", "VS Code")
+    } else {
+        spinner.finish_and_clear();
+        return Err(format!(
+            "Neither Cursor nor VS Code is in your PATH. The workspace is synced; connect to host {} at {}",
+            host, remote_dir
+        ).into());
+    };
 
-    spinner.finish_with_message("Opening Cursor...");
-    
-    sh::spawn(&cursor_cmd)
-        .map_err(|e| format!("Failed to launch Cursor: {}. Make sure Cursor is installed and in your PATH.", e))?;
+    // Run <editor> --folder-uri vscode-remote://ssh-remote+<host><folder_path>
+    let folder_uri = format!("vscode-remote://ssh-remote+{}{}", host, remote_dir);
+    let editor_cmd = format!("{} --folder-uri {}", editor, sh::quote(&folder_uri));
+
+    spinner.finish_with_message(format!("Opening {}...", editor_name));
+
+    sh::spawn(&editor_cmd)
+        .map_err(|e| format!("Failed to launch {}: {}", editor_name, e))?;
 
     Ok(())
 }
 
-pub fn handle_node_timeout_reset(id: String, duration: String) -> Result<(), Box<dyn std::error::Error>> {
+/// Re-sync the current directory to a node that was already set up with `gml connect`,
+/// printing each changed file, without touching git/SSH config or opening an editor.
+pub fn handle_restart_workspace(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
     let spinner = spinner::create_spinner();
 
     spinner.set_message("Locating node...");
-    
-    // Verify the node exists
-    let _node = match GmlState::get_node(&id)? {
+    let node = match GmlState::load()?.node(&id).cloned() {
         Some(n) => n,
         None => return Err(format!("Node with ID '{}' not found", id).into()),
     };
+    ensure_not_paused(&node)?;
+
+    let current_dir = env::current_dir()?;
+    let dir_name = current_dir.file_name()
+        .ok_or("Failed to get directory name")?
+        .to_str()
+        .ok_or("Directory name contains invalid UTF-8")?;
+    let remote_dir = remote_workspace_dir(&node, dir_name);
+
+    let _in_use = GmlState::mark_node_in_use(&node.id)?;
+
+    spinner.set_message(format!("Syncing changes to {}@{}:{}...", node.user, node.ip, remote_dir));
+    // rsync lists each transferred file; keep the spinner out of its way
+    spinner.suspend(|| sync_workspace(&node, &current_dir, &remote_dir, "-az --itemize-changes"))?;
+
+    if current_dir.join(".git").exists() {
+        spinner.set_message("Syncing .git directory...");
+        let git_rsync_cmd = format!(
+            "rsync -az --quiet --delete{} {}/.git/ {}@{}:{}/.git/",
+            rsync_shell_args(&node), current_dir.display(), node.user, node.ip, remote_dir
+        );
+        sh::run(&git_rsync_cmd)
+            .map_err(|e| format!("Failed to copy .git directory: {}", e))?;
+    }
+
+    spinner.finish_with_message("Workspace synced!");
+    Ok(())
+}
+
+/// Extra `ssh` arguments for a node that doesn't listen on port 22 or was launched with
+/// `--private-key-from`
+pub fn ssh_options(ssh_port: Option<u16>, identity_file: Option<&str>) -> String {
+    let mut options = String::new();
+    if let Some(identity_file) = identity_file {
+        options.push_str(&format!(" -i {}", sh::quote(identity_file)));
+    }
+    if let Some(port) = ssh_port {
+        options.push_str(&format!(" -p {}", port));
+    }
+    options
+}
+
+/// Extra `rsync` arguments for a node that needs [`ssh_options`]
+fn rsync_shell_args(node: &NodeEntry) -> String {
+    let options = ssh_options(node.ssh_port, node.identity_file.as_deref());
+    if options.is_empty() {
+        return String::new();
+    }
+    format!(" -e {}", sh::quote(&format!("ssh{}", options)))
+}
+
+/// Directory on the node that a local directory named `dir_name` is synced to
+fn remote_workspace_dir(node: &NodeEntry, dir_name: &str) -> String {
+    format!("/home/{}/{}", node.user, dir_name)
+}
+
+/// Copy `local_dir` to `remote_dir` on the node with rsync, skipping `.git` and anything
+/// matched by `.gitignore`. `rsync_flags` controls verbosity, e.g. `--quiet` or `--itemize-changes`.
+fn sync_workspace(node: &NodeEntry, local_dir: &Path, remote_dir: &str, rsync_flags: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let _phase = timings::phase("sync workspace");
+    // Create remote directory first
+    let ssh_cmd = format!("ssh -o StrictHostKeyChecking=no{} {}@{}", ssh_options(node.ssh_port, node.identity_file.as_deref()), node.user, node.ip);
+    sh::run(&format!("{} 'mkdir -p {}'", ssh_cmd, remote_dir))
+        .map_err(|e| format!("Failed to create remote directory: {}", e))?;
+
+    // Build rsync exclude patterns from .gitignore
+    let mut exclude_patterns = vec!["--exclude".to_string(), ".git".to_string()];
+    if let Ok(patterns) = read_gitignore_patterns(local_dir) {
+        for pattern in patterns {
+            exclude_patterns.push("--exclude".to_string());
+            exclude_patterns.push(pattern);
+        }
+    }
+
+    // Copy FROM local TO remote
+    let exclude_args = exclude_patterns.join(" ");
+    let rsync_cmd = format!(
+        "rsync {}{} {} {}/ {}@{}:{}/",
+        rsync_flags, rsync_shell_args(node), exclude_args, local_dir.display(), node.user, node.ip, remote_dir
+    );
+
+    sh::run(&rsync_cmd)
+        .map_err(|_| -> Box<dyn std::error::Error> { "Failed to copy directory to remote machine".into() })?;
+    Ok(())
+}
+
+/// Selects the nodes a bulk timeout change applies to, instead of a single `--id`
+#[derive(Args, Debug)]
+pub struct NodeSelection {
+    /// Apply to every node
+    #[arg(long, conflicts_with_all = ["tag", "provider"])]
+    pub all: bool,
+    /// Only nodes tagged key=value; repeat to require several tags
+    #[arg(long, value_parser = parse_tag)]
+    pub tag: Vec<(String, String)>,
+    /// Only nodes on this provider
+    #[arg(long)]
+    pub provider: Option<String>,
+}
 
-    spinner.set_message("Parsing timeout duration...");
-    // Parse timeout duration and calculate expiration time
+impl NodeSelection {
+    fn is_bulk(&self) -> bool {
+        self.all || !self.tag.is_empty() || self.provider.is_some()
+    }
+
+    fn matches(&self, node: &NodeEntry) -> bool {
+        self.provider.as_ref().is_none_or(|provider| &node.provider == provider)
+            && self.tag.iter().all(|(key, value)| node.tags.get(key) == Some(value))
+    }
+}
+
+pub fn handle_node_timeout_reset(
+    id: Option<String>,
+    selection: NodeSelection,
+    duration: String,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Parse timeout duration and calculate expiration time; a node's grace period is kept on top
     let timeout_expiration = parse_timeout_duration(&duration)
-        .map(|duration| {
-            let expiration = Utc::now() + duration;
-            expiration.to_rfc3339()
-        })
+        .map(|duration| Utc::now() + duration)
         .ok_or_else(|| format!("Invalid duration format: '{}'. Use formats like '1h30m', '2h', '30m'", duration))?;
 
+    if selection.is_bulk() {
+        return set_selected_timeouts(&selection, Some(timeout_expiration), "reset");
+    }
+    let id = prompt::resolve_node_id(id)?;
+    let spinner = spinner::create_spinner();
+
     spinner.set_message("Updating timeout...");
-    GmlState::update_node_timeout(&id, Some(timeout_expiration))
+    GmlState::update(|state| {
+        state.node_mut(&id)?.reset_timeout(Some(timeout_expiration));
+        Ok(())
+    })
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
 
     spinner.finish_with_message("Timeout reset successfully!");
     Ok(())
 }
 
-pub fn handle_node_timeout_remove(id: String) -> Result<(), Box<dyn std::error::Error>> {
-    let spinner = spinner::create_spinner();
+pub fn handle_rename_node(id: String, name: String) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(Some(id))?;
+    let name = Some(name.trim().to_string()).filter(|name| !name.is_empty());
+    GmlState::update(|state| state.set_node_name(&id, name.clone()))
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+    match name {
+        Some(name) => println!("Node {} is now called '{}'", id, name),
+        None => println!("Removed the name of node {}", id),
+    }
+    Ok(())
+}
 
-    spinner.set_message("Locating node...");
-    
-    // Verify the node exists
-    let _node = match GmlState::get_node(&id)? {
-        Some(n) => n,
-        None => return Err(format!("Node with ID '{}' not found", id).into()),
-    };
+/// Run the `soft-timeout` hook for a node. The daemon calls this once when a node created with
+/// `--grace` reaches its soft timeout.
+pub fn handle_soft_timeout(id: Option<String>) -> Result<(), Box<dyn std::error::Error>> {
+    let id = prompt::resolve_node_id(id)?;
+    let node = GmlState::load()?
+        .node(&id)
+        .cloned()
+        .ok_or_else(|| format!("Node with ID '{}' not found", id))?;
+    let config = config::parse_config()?;
+    if config.hooks.soft_timeout.is_none() {
+        eprintln!("No soft-timeout hook is configured under [hooks]; nothing to run");
+        return Ok(());
+    }
+    hooks::run(&config.hooks, Hook::SoftTimeout, &[
+        ("GML_NODE_ID", node.id.clone()),
+        ("GML_NODE_IP", node.ip.clone()),
+        ("GML_NODE_USER", node.user.clone()),
+        ("GML_NODE_PROVIDER", node.provider.clone()),
+        ("GML_NODE_SSH_PORT", node.ssh_port.unwrap_or(22).to_string()),
+        ("GML_NODE_DELETE_AT", node.timeout.clone().unwrap_or_default()),
+    ])
+}
+
+pub fn handle_node_timeout_remove(id: Option<String>, selection: NodeSelection) -> Result<(), Box<dyn std::error::Error>> {
+    if selection.is_bulk() {
+        return set_selected_timeouts(&selection, None, "removed");
+    }
+    let id = prompt::resolve_node_id(id)?;
+    let spinner = spinner::create_spinner();
 
     spinner.set_message("Removing timeout...");
-    GmlState::update_node_timeout(&id, None)
+    GmlState::update(|state| {
+        state.node_mut(&id)?.reset_timeout(None);
+        Ok(())
+    })
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
 
     spinner.finish_with_message("Timeout removed successfully!");
     Ok(())
 }
 
-pub async fn handle_list_node_types(provider: String) -> Result<(), Box<dyn std::error::Error>> {
+/// Set the timeout of every node `selection` matches, in one state update, and report which ones
+/// changed
+fn set_selected_timeouts(
+    selection: &NodeSelection,
+    timeout: Option<chrono::DateTime<Utc>>,
+    action: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let updated = GmlState::update(|state| {
+        let mut updated = Vec::new();
+        for node in state.nodes.iter_mut().filter(|node| selection.matches(node)) {
+            node.reset_timeout(timeout);
+            updated.push(node.id.clone());
+        }
+        Ok(updated)
+    })
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    if updated.is_empty() {
+        println!("No nodes match; no timeouts changed.");
+        return Ok(());
+    }
+    for id in &updated {
+        println!("  {}", id);
+    }
+    println!("Timeout {} for {} node(s).", action, updated.len());
+    Ok(())
+}
+
+pub async fn handle_list_node_types(
+    provider: String,
+    available_only: bool,
+    min_vram: Option<u32>,
+    raw: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
     let spinner = spinner::create_spinner();
 
     spinner.set_message("Parsing configuration...");
@@ -326,7 +1593,41 @@ pub async fn handle_list_node_types(provider: String) -> Result<(), Box<dyn std:
         .await
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
 
-    let node_types_json = provider_handle.get_node_types()
+    if !raw {
+        let mut node_types = provider_handle.list_node_types()
+            .await
+            .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+        spinner.finish_with_message("Node types retrieved successfully!");
+
+        if available_only {
+            node_types.retain(|t| t.has_capacity());
+        }
+        if let Some(min) = min_vram {
+            node_types.retain(|t| t.memory_gb.is_some_and(|gb| gb >= min));
+        }
+        node_types.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut table = Table::new();
+        table.set_header(vec!["Name", "GPUs", "VRAM", "Price/hr", "Regions", "Description"]);
+        for node_type in &node_types {
+            table.add_row(vec![
+                Cell::new(&node_type.name),
+                Cell::new(node_type.accelerator_count),
+                Cell::new(node_type.memory_gb.map_or("-".to_string(), |gb| format!("{} GB", gb))),
+                Cell::new(node_type.price.as_ref().map_or("-".to_string(), |p| format_cost(p.hourly, &p.currency))),
+                Cell::new(match &node_type.regions {
+                    None => "-".to_string(),
+                    Some(regions) if regions.is_empty() => "no capacity".to_string(),
+                    Some(regions) => regions.join(", "),
+                }),
+                Cell::new(&node_type.description),
+            ]);
+        }
+        println!("{}", table);
+        return Ok(());
+    }
+
+    let node_types_json = provider_handle.get_node_types(available_only)
         .await
         .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
 
@@ -344,42 +1645,26 @@ pub async fn handle_list_node_types(provider: String) -> Result<(), Box<dyn std:
     Ok(())
 }
 
-async fn ensure_daemon_running(_spinner: &ProgressBar) -> Result<(), Box<dyn std::error::Error>> {
-    let mut system = System::new_all();
-    system.refresh_all();
-    
-    let daemon_running = system.processes().values().any(|process| {
-        // Check for exact name match or if it contains gmld (handles cases with extensions etc)
-        process.name().contains("gmld")
-    });
-
-    if !daemon_running {
-        let current_exe = env::current_exe()?;
-        let daemon_path = current_exe.parent()
-            .ok_or("Failed to get parent directory")?
-            .join("gmld");
-            
-        if !daemon_path.exists() {
-             return Err(format!("Daemon executable not found at {:?}", daemon_path).into());
-        }
+/// Format an amount of money, using a $ prefix for USD and the currency code otherwise
+pub fn format_cost(amount: f64, currency: &str) -> String {
+    if currency == "USD" {
+        format!("${:.2}", amount)
+    } else {
+        format!("{:.2} {}", amount, currency)
+    }
+}
 
-        // Suppress daemon output to avoid interfering with spinner
-        Command::new(daemon_path)
-            .stdout(Stdio::null())
-            .stderr(Stdio::null())
-            .spawn()
-            .map_err(|e| format!("Failed to start daemon: {}", e))?;
-            
-        // Give it a moment to start
-        tokio::time::sleep(Duration::from_secs(1)).await;
+/// Parse a `key=value` tag argument
+pub fn parse_tag(tag: &str) -> Result<(String, String), String> {
+    match tag.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.trim().to_string())),
+        _ => Err(format!("invalid tag '{}', expected key=value", tag)),
     }
-    
-    Ok(())
 }
 
 /// Parse a timeout duration string (e.g., "1h", "30m", "2h 30m") into a chrono::Duration
 /// Uses the humantime crate to parse human-readable duration strings
-fn parse_timeout_duration(timeout_str: &str) -> Option<chrono::Duration> {
+pub fn parse_timeout_duration(timeout_str: &str) -> Option<chrono::Duration> {
     parse_duration(timeout_str)
         .ok()
         .and_then(|std_duration| chrono::Duration::from_std(std_duration).ok())
@@ -412,6 +1697,52 @@ fn read_gitignore_patterns(dir: &Path) -> Result<Vec<String>, Box<dyn std::error
     Ok(patterns)
 }
 
+/// The git checkout a node was created from, for `--label-from-git`
+struct GitContext {
+    repo: String,
+    /// `None` on a detached HEAD
+    branch: Option<String>,
+    commit: String,
+}
+
+impl GitContext {
+    /// `<repo>-<branch>`, or `<repo>-<short commit>` on a detached HEAD
+    fn node_name(&self) -> String {
+        let suffix = self.branch.as_deref().unwrap_or(&self.commit[..self.commit.len().min(7)]);
+        format!("{}-{}", self.repo, suffix)
+    }
+
+    fn tags(&self) -> Vec<(String, String)> {
+        let mut tags = vec![
+            ("repo".to_string(), self.repo.clone()),
+            ("commit".to_string(), self.commit.clone()),
+        ];
+        if let Some(branch) = &self.branch {
+            tags.push(("branch".to_string(), branch.clone()));
+        }
+        tags
+    }
+}
+
+/// Repo, branch and commit of the current directory, or `None` outside a git repository
+fn git_context() -> Option<GitContext> {
+    let git = |args: &[&str]| {
+        Command::new("git")
+            .args(args)
+            .output()
+            .ok()
+            .filter(|o| o.status.success())
+            .map(|o| String::from_utf8_lossy(&o.stdout).trim().to_string())
+            .filter(|s| !s.is_empty())
+    };
+
+    let toplevel = git(&["rev-parse", "--show-toplevel"])?;
+    let repo = Path::new(&toplevel).file_name()?.to_string_lossy().to_string();
+    let commit = git(&["rev-parse", "HEAD"])?;
+    let branch = git(&["rev-parse", "--abbrev-ref", "HEAD"]).filter(|branch| branch != "HEAD");
+    Some(GitContext { repo, branch, commit })
+}
+
 /// Get the local git user identity (name and email) from git config
 /// Returns None if either user.name or user.email is not configured
 fn get_local_git_identity() -> Option<(String, String)> {
@@ -436,52 +1767,62 @@ fn get_local_git_identity() -> Option<(String, String)> {
     Some((name, email))
 }
 
-/// Configure local SSH config to enable agent forwarding for a specific host
-/// This adds a Host entry to ~/.ssh/config with ForwardAgent yes
-fn configure_local_ssh_agent_forwarding(home_dir: &Path, host_ip: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let ssh_config_path = home_dir.join(".ssh/config");
-    
-    // Read existing config if it exists
+/// Whether `program` is an executable in one of the directories on `PATH`
+fn on_path(program: &str) -> bool {
+    env::var_os("PATH").is_some_and(|path| env::split_paths(&path).any(|dir| dir.join(program).is_file()))
+}
+
+/// Add a `gml-<node id>` Host entry to ~/.ssh/config with the node's address, user and port, and
+/// agent forwarding so git on the node can use your local keys. An earlier entry for the same node
+/// is replaced, so its address stays current. Returns the host alias.
+pub fn configure_ssh_host(home_dir: &Path, node: &NodeEntry) -> Result<String, Box<dyn std::error::Error>> {
+    let ssh_dir = home_dir.join(".ssh");
+    let ssh_config_path = ssh_dir.join("config");
+    let host = format!("gml-{}", node.id);
+    let marker = format!("# Added by gml for node {}", node.id);
+
     let existing_config = if ssh_config_path.exists() {
         fs::read_to_string(&ssh_config_path)?
     } else {
         String::new()
     };
-    
-    // Check if this host already has ForwardAgent configured
-    let host_pattern = format!("Host {}", host_ip);
-    if existing_config.contains(&host_pattern) {
-        // Host entry exists, check if it has ForwardAgent
-        // For simplicity, we'll skip if the host is already configured
-        return Ok(());
+
+    // Drop the previous entry: the marker, its Host line and the indented options below it
+    let mut config = String::new();
+    let mut lines = existing_config.lines().peekable();
+    while let Some(line) = lines.next() {
+        if line == marker {
+            lines.next();
+            while lines.peek().is_some_and(|next| next.starts_with(char::is_whitespace) && !next.trim().is_empty()) {
+                lines.next();
+            }
+            continue;
+        }
+        config.push_str(line);
+        config.push('\n');
     }
-    
-    // Create the SSH config directory if it doesn't exist
-    let ssh_dir = home_dir.join(".ssh");
-    if !ssh_dir.exists() {
-        fs::create_dir_all(&ssh_dir)?;
+
+    if !config.is_empty() && !config.ends_with("\n\n") {
+        config.push('\n');
     }
-    
-    // Append the new host configuration
-    let new_config = format!(
-        "\n# Added by gml connect for SSH agent forwarding\nHost {}\n  ForwardAgent yes\n  AddKeysToAgent yes\n",
-        host_ip
-    );
-    
-    let mut file = fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&ssh_config_path)?;
-    
-    use std::io::Write;
-    file.write_all(new_config.as_bytes())?;
-    
+    config.push_str(&format!("{}\nHost {}\n  HostName {}\n  User {}\n", marker, host, node.ip, node.user));
+    if let Some(port) = node.ssh_port {
+        config.push_str(&format!("  Port {}\n", port));
+    }
+    if let Some(identity_file) = &node.identity_file {
+        config.push_str(&format!("  IdentityFile \"{}\"\n", identity_file));
+    }
+    config.push_str("  StrictHostKeyChecking no\n  ForwardAgent yes\n  AddKeysToAgent yes\n");
+
+    fs::create_dir_all(&ssh_dir)?;
+    fs::write(&ssh_config_path, config)?;
+
     // Set proper permissions on the config file
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt;
         fs::set_permissions(&ssh_config_path, fs::Permissions::from_mode(0o600))?;
     }
-    
-    Ok(())
+
+    Ok(host)
 }
@@ -0,0 +1,96 @@
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Select};
+use gml_core::state::GmlState;
+use std::io::IsTerminal;
+
+use crate::config;
+
+/// Return the given node id, or the id of the node with that name, or let the user pick one of
+/// their nodes interactively.
+///
+/// The picker is only shown when stdin is a terminal; scripts must keep passing the id.
+pub fn resolve_node_id(id: Option<String>) -> Result<String, Box<dyn std::error::Error>> {
+    if let Some(id) = id {
+        // A node's name stands in for its ID
+        let state = GmlState::load()?;
+        if state.node(&id).is_none() {
+            if let Some(node) = state.node_named(&id)? {
+                return Ok(node.id.clone());
+            }
+        }
+        return Ok(id);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err("A node ID is required when not running interactively".into());
+    }
+
+    let nodes = GmlState::load()?.nodes;
+    if nodes.is_empty() {
+        return Err("No nodes found".into());
+    }
+
+    let items: Vec<String> = nodes.iter()
+        .map(|n| format!("{}  {}  {}  {}", n.id, n.provider, n.instance_type, n.ip))
+        .collect();
+
+    let selection = Select::with_theme(&ColorfulTheme::default())
+        .with_prompt("Select a node")
+        .items(&items)
+        .default(0)
+        .interact_opt()?;
+
+    match selection {
+        Some(index) => Ok(nodes[index].id.clone()),
+        None => Err("No node selected".into()),
+    }
+}
+
+/// Env var that answers every confirmation prompt with yes; overrides `[gml] assume-yes`
+const ASSUME_YES_ENV: &str = "GML_ASSUME_YES";
+
+/// Ask a yes/no question, defaulting to no.
+///
+/// `assume_yes` (from `--yes`), `GML_ASSUME_YES` or `[gml] assume-yes` skip the question.
+/// Without any of them, a non-interactive stdin is an error rather than a silent yes, so
+/// scripts have to opt in explicitly.
+pub fn confirm(question: &str, assume_yes: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    if assume_yes || assume_yes_globally() {
+        return Ok(true);
+    }
+
+    if !std::io::stdin().is_terminal() {
+        return Err("Confirmation required; pass --yes to continue non-interactively".into());
+    }
+
+    let answer = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(question)
+        .default(false)
+        .interact()?;
+    Ok(answer)
+}
+
+/// Ask an optional yes/no question whose default is to do nothing.
+///
+/// Unlike `confirm`, neither `GML_ASSUME_YES` nor `[gml] assume-yes` answers it, since they only
+/// exist to unblock actions the user already asked for. Without a terminal the answer is no.
+pub fn confirm_default_no(question: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    let answer = Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(question)
+        .default(false)
+        .interact()?;
+    Ok(answer)
+}
+
+/// Whether automation has opted out of prompts via `GML_ASSUME_YES` or `[gml] assume-yes`.
+/// A set env var wins either way, so `GML_ASSUME_YES=0` re-enables prompts for one command.
+fn assume_yes_globally() -> bool {
+    match std::env::var(ASSUME_YES_ENV) {
+        Ok(value) => matches!(value.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"),
+        Err(_) => config::parse_config().map(|c| c.assume_yes).unwrap_or(false),
+    }
+}
@@ -1,8 +1,10 @@
-use gml_core::NodeProvider;
+use gml_core::{ClusterProvider, NodeProvider};
 use gml_core::error::GmlError;
 use gml_lambda::Lambda;
 use gml_google::Google;
-use crate::config::ProviderConfig;
+use gml_runpod::RunPod;
+use crate::config::{self, ProviderConfig};
+use crate::secrets;
 
 pub async fn create_provider_handle(
     provider_name: &str,
@@ -11,22 +13,7 @@ pub async fn create_provider_handle(
     gml_ssh_public_key: Option<String>,
 ) -> Result<Box<dyn NodeProvider>, GmlError> {
     match provider_name {
-        "lambda" => {
-            let api_key = provider_config.api_key
-                .as_ref()
-                .ok_or_else(|| GmlError::from("api-key is required for lambda provider, set it in your gml config"))?
-                .clone();
-            let ssh_key_id = provider_config.ssh_key
-                .as_ref()
-                .ok_or_else(|| GmlError::from("ssh-key is required for lambda provider, set it in your gml config"))?
-                .clone();
-            // Use CLI region if provided, otherwise fall back to config
-            let region = region_override
-                .or_else(|| provider_config.region.clone())
-                .ok_or_else(|| GmlError::from("region is required: provide --region flag or set it in your gml config"))?;
-            
-            Ok(Box::new(Lambda::new(api_key, ssh_key_id, region)))
-        }
+        "lambda" => Ok(Box::new(lambda_handle(provider_config, region_override)?)),
         "google" => {
             let google = Google::new(
                 provider_config.project.clone(),
@@ -36,6 +23,54 @@ pub async fn create_provider_handle(
             .await?;
             Ok(Box::new(google))
         }
+        "runpod" => {
+            let api_key = api_key(provider_name, provider_config)?;
+            Ok(Box::new(RunPod::new(api_key, gml_ssh_public_key, provider_config.boot_polling()?)))
+        }
         _ => Err(GmlError::from(format!("Unimplemented provider: {}", provider_name)))
     }
-}
\ No newline at end of file
+}
+/// Handle for launching whole clusters; only providers that implement `ClusterProvider` have one
+pub fn create_cluster_handle(
+    provider_name: &str,
+    provider_config: &ProviderConfig,
+    region_override: Option<String>,
+) -> Result<Box<dyn ClusterProvider>, GmlError> {
+    match provider_name {
+        "lambda" => Ok(Box::new(lambda_handle(provider_config, region_override)?)),
+        _ => Err(GmlError::unsupported("Creating clusters from flags")),
+    }
+}
+
+/// The API key of the account `provider_config` was read from, so each named account
+/// authenticates with its own credentials
+fn api_key(provider_name: &str, provider_config: &ProviderConfig) -> Result<String, GmlError> {
+    let table = provider_config.table_name(provider_name);
+    secrets::resolve_api_key(&table, provider_config.api_key.as_deref())
+        .map_err(|e| GmlError::from(e.to_string()))?
+        .ok_or_else(|| GmlError::from(format!(
+            "api-key is required for {} provider: set {}",
+            provider_name, config::key_sources(&table, "api-key")
+        )))
+}
+
+fn lambda_handle(provider_config: &ProviderConfig, region_override: Option<String>) -> Result<Lambda, GmlError> {
+    let api_key = api_key("lambda", provider_config)?;
+    let table = provider_config.table_name("lambda");
+    let ssh_key_names = provider_config.ssh_key
+        .as_ref()
+        .map(|key| key.names())
+        .filter(|names| !names.is_empty())
+        .ok_or_else(|| GmlError::from(format!("ssh-key-name is required for lambda provider: set {}", config::key_sources(&table, "ssh-key-name"))))?;
+    // Use CLI region if provided, otherwise fall back to config
+    let region = region_override
+        .or_else(|| provider_config.region.clone())
+        .ok_or_else(|| GmlError::from(format!("region is required: pass --region or set {}", config::key_sources(&table, "region"))))?;
+
+    let max_attempts = provider_config.max_attempts.unwrap_or(gml_lambda::DEFAULT_MAX_ATTEMPTS);
+    if max_attempts == 0 {
+        return Err(GmlError::from("max-attempts must be at least 1"));
+    }
+
+    Ok(Lambda::new(api_key, ssh_key_names, region, provider_config.boot_polling()?, max_attempts))
+}
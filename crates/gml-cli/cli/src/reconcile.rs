@@ -0,0 +1,128 @@
+//! `gml node sync`: compare local state with what each provider actually has running.
+//!
+//! Nodes that no longer exist on their provider (terminated from its console, or reclaimed) are
+//! removed from state after confirmation. Instances gml doesn't track, and tracked nodes whose
+//! address has changed, are only reported. Status changes the provider reports are saved.
+
+use gml_core::error::ErrorKind;
+use gml_core::state::{self, GmlState, NodeEntry};
+
+use crate::config;
+use crate::prompt;
+use crate::providers;
+use crate::spinner;
+
+pub async fn handle_node_sync(yes: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::parse_config()?;
+    let state = GmlState::load()?;
+
+    let spinner = spinner::create_spinner();
+    let mut stale: Vec<&NodeEntry> = Vec::new();
+    let mut findings: Vec<String> = Vec::new();
+    // (node ID, status) for tracked nodes whose provider reports a different status
+    let mut statuses: Vec<(String, String)> = Vec::new();
+    let mut unreachable = 0;
+    // Each account is a separate set of instances, so they are listed one by one
+    for (provider, provider_config) in config.provider_accounts() {
+        let label = provider_config.table_name(provider);
+        spinner.set_message(format!("Listing nodes on {}...", label));
+        let listed = match providers::create_provider_handle(
+            provider,
+            provider_config,
+            provider_config.region.clone(),
+            config.ssh_public_key.clone(),
+        ).await {
+            Ok(handle) => handle.list_nodes().await,
+            Err(e) => Err(e),
+        };
+        let listed = match listed {
+            Ok(listed) => listed,
+            Err(e) if e.kind() == ErrorKind::Unsupported => {
+                spinner.suspend(|| eprintln!("Skipping {}: listing nodes is not supported", label));
+                continue;
+            }
+            Err(e) => {
+                spinner.suspend(|| eprintln!("Warning: could not list nodes on {}: {}", label, e));
+                unreachable += 1;
+                continue;
+            }
+        };
+
+        let tracked: Vec<&NodeEntry> = state.nodes
+            .iter()
+            .filter(|node| &node.provider == provider && node.account == provider_config.account)
+            .collect();
+        for node in &tracked {
+            let Some(details) = listed.iter().find(|details| details.id == node.provider_id) else {
+                stale.push(node);
+                continue;
+            };
+            if !details.ip.is_empty() && details.ip != node.ip {
+                findings.push(format!("Node {} is at {} on {}, but {} locally", node.id, details.ip, label, node.ip));
+            }
+            if let Some(status) = details.status.as_ref().filter(|status| **status != node.status) {
+                statuses.push((node.id.clone(), status.clone()));
+            }
+        }
+        let untracked = listed.iter().filter(|details| !tracked.iter().any(|node| node.provider_id == details.id));
+        for details in untracked {
+            findings.push(format!(
+                "{} instance {} ({}) is not tracked by gml",
+                label,
+                details.id,
+                if details.ip.is_empty() { "no IP yet" } else { &details.ip }
+            ));
+        }
+    }
+    spinner.finish_and_clear();
+
+    if !statuses.is_empty() {
+        GmlState::update(|state| {
+            for (id, status) in &statuses {
+                // Another command may have removed it since we loaded state
+                if let Ok(node) = state.node_mut(id) {
+                    // A node stopped or started outside gml counts as paused or resumed
+                    match status.as_str() {
+                        state::STATUS_PAUSED => node.paused = true,
+                        state::STATUS_ACTIVE => node.paused = false,
+                        _ => {}
+                    }
+                    node.status = status.clone();
+                }
+            }
+            Ok(())
+        })?;
+        for (id, status) in &statuses {
+            println!("Node {} is now {}", id, status);
+        }
+    }
+    for finding in &findings {
+        println!("{}", finding);
+    }
+    if stale.is_empty() {
+        if findings.is_empty() && unreachable == 0 {
+            println!("Local state matches the providers.");
+        }
+        return Ok(());
+    }
+
+    println!("No longer on their provider:");
+    for node in &stale {
+        println!("  {} ({} instance {})", node.id, node.provider, node.provider_id);
+    }
+    if !prompt::confirm(&format!("Remove {} stale node(s) from local state?", stale.len()), yes)? {
+        return Ok(());
+    }
+    GmlState::update(|state| {
+        for node in &stale {
+            // Another command may have removed it since we loaded state
+            if state.node(&node.id).is_some() {
+                state.remove_node(&node.id)?;
+            }
+        }
+        Ok(())
+    })?;
+    println!("Removed {} stale node(s).", stale.len());
+    Ok(())
+}
+
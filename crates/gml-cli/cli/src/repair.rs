@@ -0,0 +1,41 @@
+use crate::config;
+use gml_core::state::GmlState;
+
+/// Convert the state file to match `[gml] compress-state`, if it is set and the file doesn't
+/// already. A config that fails to load is left for the command itself to report.
+pub fn apply_compress_state_setting() -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(config) = config::parse_config() else {
+        return Ok(());
+    };
+    let Some(compress) = config.compress_state else {
+        return Ok(());
+    };
+    if GmlState::set_compressed(compress)? {
+        let form = if compress { "state.json.gz" } else { "state.json" };
+        eprintln!("Converted the state file to {} (compress-state = {})", form, compress);
+    }
+    Ok(())
+}
+
+/// Rewrite a corrupt `state.json` with whatever entries can still be read, reporting the rest
+pub fn handle_state_repair() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(repair) = GmlState::repair()? else {
+        println!("State file is fine; nothing to repair.");
+        return Ok(());
+    };
+
+    println!("Saved a copy of the corrupt state file to {}", repair.backup.display());
+    println!(
+        "Recovered {} node(s) and {} cluster(s).",
+        repair.state.nodes.len(),
+        repair.state.clusters.len()
+    );
+    if !repair.dropped.is_empty() {
+        println!("Could not recover:");
+        for dropped in &repair.dropped {
+            println!("  - {}", dropped);
+        }
+        println!("Nodes that were lost may still be running; check `gml node sync` or the provider console.");
+    }
+    Ok(())
+}
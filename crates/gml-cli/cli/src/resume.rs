@@ -0,0 +1,152 @@
+use gml_core::NodeDetails;
+use gml_core::journal::{self, Operation, OperationKind, OperationStatus};
+use gml_core::state::GmlState;
+
+use crate::config::{self, Config};
+use crate::prompt;
+use crate::providers;
+use crate::spinner;
+
+/// Settle operations left unfinished in the journal by an interrupted command.
+///
+/// Interrupted launches are rolled back, since the node never made it into state and nothing
+/// would ever time it out. Interrupted deletions are finished.
+pub async fn handle_resume_command(yes: bool, discard: bool) -> Result<(), Box<dyn std::error::Error>> {
+    // A command that is still running, e.g. a launch waiting for boot, hasn't been interrupted
+    let (in_flight, pending): (Vec<Operation>, Vec<Operation>) =
+        journal::pending()?.into_iter().partition(Operation::is_in_flight);
+    if !in_flight.is_empty() {
+        println!("Skipping {} operation(s) still in progress in another gml process.", in_flight.len());
+    }
+    if pending.is_empty() {
+        println!("No interrupted operations.");
+        return Ok(());
+    }
+
+    println!("Interrupted operations:");
+    for op in &pending {
+        println!("  {}", describe(op));
+    }
+
+    if discard {
+        if !prompt::confirm("Forget these operations without touching the provider?", yes)? {
+            return Ok(());
+        }
+        for op in &pending {
+            op.done()?;
+        }
+        println!("Discarded {} operation(s).", pending.len());
+        return Ok(());
+    }
+
+    if !prompt::confirm("Roll back the launches and finish the deletions?", yes)? {
+        return Ok(());
+    }
+
+    let config = config::parse_config()?;
+    let spinner = spinner::create_spinner();
+    let mut failures = 0;
+    for op in &pending {
+        spinner.set_message(format!("Settling {}...", describe(op)));
+        match settle(&config, op).await {
+            Ok(outcome) => {
+                op.done()?;
+                spinner.suspend(|| println!("{}", outcome));
+            }
+            Err(e) => {
+                failures += 1;
+                spinner.suspend(|| eprintln!("Failed to settle {}: {}", describe(op), e));
+            }
+        }
+    }
+
+    if failures > 0 {
+        spinner.finish_and_clear();
+        return Err(format!(
+            "{} operation(s) could not be settled; check the provider console, then run `gml resume --discard` to forget them",
+            failures
+        ).into());
+    }
+    spinner.finish_with_message("All interrupted operations settled");
+    Ok(())
+}
+
+/// Bring the provider and state in line for one operation, returning what was done
+async fn settle(config: &Config, op: &Operation) -> Result<String, Box<dyn std::error::Error>> {
+    match op.kind {
+        OperationKind::Launch => {
+            let Some(provider_id) = &op.provider_id else {
+                return Ok(format!(
+                    "Launch on {} was interrupted before an instance ID was assigned; check the provider console for a stray instance",
+                    op.provider
+                ));
+            };
+            // The command may have died after saving the node but before journaling that
+            let saved = GmlState::load()?
+                .nodes
+                .into_iter()
+                .find(|n| n.provider == op.provider && &n.provider_id == provider_id);
+            if let Some(node) = saved {
+                return Ok(format!("Launch of {} already completed as node {}", provider_id, node.id));
+            }
+
+            let details = NodeDetails {
+                id: provider_id.clone(),
+                ip: String::new(),
+                zone: op.zone.clone(),
+                ssh_port: None,
+                status: None,
+            };
+            stop(config, op, details).await?;
+            Ok(format!("Terminated untracked instance {}", provider_id))
+        }
+        OperationKind::Terminate => {
+            let node_id = op.node_id.as_deref().ok_or("Journal entry is missing its node ID")?;
+            let Some(node) = GmlState::load()?.node(node_id).cloned() else {
+                return Ok(format!("Node {} was already deleted", node_id));
+            };
+
+            let details = NodeDetails {
+                id: node.provider_id.clone(),
+                ip: node.ip.clone(),
+                zone: node.zone.clone(),
+                ssh_port: node.ssh_port,
+                status: Some(node.status.clone()),
+            };
+            stop(config, op, details).await?;
+            GmlState::update(|state| state.remove_node(node_id))?;
+            Ok(format!("Deleted node {}", node_id))
+        }
+    }
+}
+
+async fn stop(config: &Config, op: &Operation, details: NodeDetails) -> Result<(), Box<dyn std::error::Error>> {
+    let provider_config = config.get_provider_account(&op.provider, op.account.as_deref())?;
+    let provider_handle = providers::create_provider_handle(
+        &op.provider,
+        provider_config,
+        op.region.clone(),
+        config.ssh_public_key.clone(),
+    )
+        .await
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+
+    provider_handle.stop_node(details)
+        .await
+        .map_err(|e| Box::from(e) as Box<dyn std::error::Error>)?;
+    Ok(())
+}
+
+fn describe(op: &Operation) -> String {
+    match (op.kind, op.status) {
+        (OperationKind::Launch, OperationStatus::Launched) => format!(
+            "launch of {} on {} (started {})",
+            op.provider_id.as_deref().unwrap_or("?"), op.provider, op.recorded_at
+        ),
+        (OperationKind::Launch, _) => format!("launch on {} (started {})", op.provider, op.recorded_at),
+        (OperationKind::Terminate, _) => format!(
+            "deletion of node {} on {} (started {})",
+            op.node_id.as_deref().unwrap_or("?"), op.provider, op.recorded_at
+        ),
+    }
+}
@@ -0,0 +1,75 @@
+//! Provider secrets kept in the OS keyring rather than in plaintext config.
+//!
+//! A config value of `keyring:<name>` refers to the keyring entry `<name>` under the `gml`
+//! service. `GML_<PROVIDER>_API_KEY`, when set, replaces the configured value while the config
+//! is parsed, so the keyring is never consulted. Otherwise a keyring that can't be reached (e.g.
+//! a headless Linux box without a Secret Service) is an error that names the variable to set. A
+//! named account such as `[lambda.work]` has its own entry and variable, `GML_LAMBDA_WORK_API_KEY`.
+
+use dialoguer::Password;
+use std::io::{self, BufRead, IsTerminal};
+
+use crate::config;
+
+const KEYRING_SERVICE: &str = "gml";
+const KEYRING_PREFIX: &str = "keyring:";
+
+/// The API key for `provider` (a config table name such as `lambda` or `lambda.work`): the
+/// configured value, or the keyring entry it points to. `GML_<PROVIDER>_API_KEY` has already
+/// replaced the configured value when it is set, see [`config::parse_config`].
+pub fn resolve_api_key(provider: &str, configured: Option<&str>) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let Some(configured) = configured else {
+        return Ok(None);
+    };
+    let Some(entry_name) = configured.strip_prefix(KEYRING_PREFIX) else {
+        return Ok(Some(configured.to_string()));
+    };
+
+    match keyring::Entry::new(KEYRING_SERVICE, entry_name).and_then(|entry| entry.get_password()) {
+        Ok(secret) => Ok(Some(secret)),
+        Err(keyring::Error::NoEntry) => Err(format!(
+            "No keyring entry '{}' for {}.api-key; run `gml config set-secret {}.api-key` or set {}",
+            entry_name, provider, provider, api_key_env_var(provider)
+        ).into()),
+        Err(e) => Err(format!(
+            "Could not read '{}' from the OS keyring: {}; set {} instead",
+            entry_name, e, api_key_env_var(provider)
+        ).into()),
+    }
+}
+
+/// Store a secret in the keyring and point the config at it.
+/// `key` has the form `<provider>.api-key`, or `<provider>.<account>.api-key`; the secret is
+/// read from a hidden prompt, or from stdin when piped.
+pub fn handle_set_secret(key: String) -> Result<(), Box<dyn std::error::Error>> {
+    let provider = match key.rsplit_once('.') {
+        Some((provider, "api-key")) if !provider.is_empty() => provider,
+        _ => return Err(format!("Unsupported secret '{}'; expected <provider>.api-key, e.g. lambda.api-key", key).into()),
+    };
+
+    let secret = if io::stdin().is_terminal() {
+        Password::new().with_prompt(format!("{} api-key", provider)).interact()?
+    } else {
+        let mut line = String::new();
+        io::stdin().lock().read_line(&mut line)?;
+        line.trim().to_string()
+    };
+    if secret.is_empty() {
+        return Err("Secret is empty".into());
+    }
+
+    keyring::Entry::new(KEYRING_SERVICE, provider)
+        .and_then(|entry| entry.set_password(&secret))
+        .map_err(|e| format!(
+            "Could not store the secret in the OS keyring: {}; keep it in config or set {} instead",
+            e, api_key_env_var(provider)
+        ))?;
+
+    config::set_provider_value(provider, "api-key", &format!("{}{}", KEYRING_PREFIX, provider))?;
+    println!("Stored {} in the OS keyring; config now refers to it as \"{}{}\"", key, KEYRING_PREFIX, provider);
+    Ok(())
+}
+
+pub fn api_key_env_var(provider: &str) -> String {
+    config::env_var_name(provider, "api-key")
+}
@@ -15,9 +15,28 @@ use std::process::Command;
 /// Returns `Ok(())` if the command executes successfully, or an error
 /// if the command fails to execute or returns a non-zero exit status.
 pub fn run(cmd: &str) -> Result<(), Box<dyn std::error::Error>> {
+    run_with_env(cmd, &[])
+}
+
+/// Runs a shell command with extra environment variables and waits for completion
+/// 
+/// Behaves like [`run`], but the command also sees each `(name, value)` pair
+/// in `env` as an environment variable, on top of this process's environment.
+/// 
+/// # Arguments
+/// 
+/// * `cmd` - The shell command to execute as a string
+/// * `env` - Environment variables to set for the command
+/// 
+/// # Returns
+/// 
+/// Returns `Ok(())` if the command executes successfully, or an error
+/// if the command fails to execute or returns a non-zero exit status.
+pub fn run_with_env(cmd: &str, env: &[(&str, String)]) -> Result<(), Box<dyn std::error::Error>> {
     let status = Command::new("sh")
         .arg("-c")
         .arg(cmd)
+        .envs(env.iter().map(|(name, value)| (*name, value.as_str())))
         .status()
         .map_err(|e| format!("Failed to execute command: {}", e))?;
     
@@ -51,3 +70,19 @@ pub fn spawn(cmd: &str) -> Result<(), Box<dyn std::error::Error>> {
     
     Ok(())
 }
+
+/// Quotes a string for use as a single word in a POSIX shell command
+/// 
+/// Wraps the string in single quotes, so that nothing inside it is expanded,
+/// and escapes any single quotes it contains.
+/// 
+/// # Arguments
+/// 
+/// * `s` - The string to quote
+/// 
+/// # Returns
+/// 
+/// Returns the quoted string, e.g. `'it'\''s'` for `it's`.
+pub fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
@@ -1,5 +1,8 @@
+use gml_core::StatusCallback;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
 
 /// Creates and configures a new progress spinner with consistent styling
 /// 
@@ -10,12 +13,73 @@ use std::time::Duration;
 /// - 100ms tick interval
 pub fn create_spinner() -> ProgressBar {
     let spinner = ProgressBar::new_spinner();
+    spinner.set_style(spinner_style());
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    spinner
+}
+
+fn spinner_style() -> ProgressStyle {
+    ProgressStyle::default_spinner()
+        .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏")
+        .template("{spinner:.green} {msg}")
+        .unwrap()
+}
+
+/// Creates a status callback that shows provider status transitions on the spinner
+///
+/// The first report is shown as `status: booting`, subsequent changes as
+/// `status: booting → active`.
+pub fn status_callback(spinner: &ProgressBar) -> StatusCallback {
+    let spinner = spinner.clone();
+    let last_status: Mutex<Option<String>> = Mutex::new(None);
+    Arc::new(move |status: &str| {
+        let mut last = last_status.lock().unwrap();
+        let message = match last.as_deref() {
+            Some(previous) => format!("status: {} → {}", previous, status),
+            None => format!("status: {}", status),
+        };
+        spinner.set_message(message);
+        *last = Some(status.to_string());
+    })
+}
+
+/// Turns the spinner into a progress bar that fills over `expected`, the usual boot time
+///
+/// The bar advances with elapsed time and holds at 99% until the returned guard is dropped, so it
+/// never claims to be done before the node actually is. Dropping the guard restores the spinner.
+pub fn boot_progress(spinner: &ProgressBar, expected: Duration) -> BootProgress {
+    let length = expected.as_millis().max(1) as u64;
     spinner.set_style(
-        ProgressStyle::default_spinner()
+        ProgressStyle::default_bar()
             .tick_chars("⠋⠙⠹⠸⠼⠴⠦⠧⠇⠏")
-            .template("{spinner:.green} {msg}")
+            .template("{spinner:.green} {msg} [{bar:30.green/white}] {percent}% (ETA {eta})")
             .unwrap()
+            .progress_chars("=> ")
     );
-    spinner.enable_steady_tick(Duration::from_millis(100));
-    spinner
+    spinner.set_length(length);
+    spinner.set_position(0);
+
+    let bar = spinner.clone();
+    let started = Instant::now();
+    let task = tokio::spawn(async move {
+        loop {
+            let elapsed = started.elapsed().as_millis() as u64;
+            bar.set_position(elapsed.min(length * 99 / 100));
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    });
+    BootProgress { spinner: spinner.clone(), task }
+}
+
+/// Keeps a [`boot_progress`] bar advancing until dropped
+pub struct BootProgress {
+    spinner: ProgressBar,
+    task: JoinHandle<()>,
+}
+
+impl Drop for BootProgress {
+    fn drop(&mut self) {
+        self.task.abort();
+        self.spinner.set_style(spinner_style());
+    }
 }
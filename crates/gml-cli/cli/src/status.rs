@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use gml_core::daemon_pid;
+use gml_core::paths;
+use gml_core::state::GmlState;
+use std::fs;
+use std::time::{Duration, SystemTime};
+
+use crate::config;
+use crate::ls;
+use crate::node;
+use crate::providers;
+
+/// The daemon logs at least once per minute, so a log untouched for longer than this means it is stuck
+const DAEMON_STALE_AFTER: Duration = Duration::from_secs(180);
+
+/// Print a one-screen overview of the daemon, config, nodes and clusters
+///
+/// Only local state is read unless `online` is set, in which case every configured
+/// provider is also contacted to check that its credentials work.
+pub async fn handle_status_command(online: bool) -> Result<(), Box<dyn std::error::Error>> {
+    println!("Daemon:    {}", daemon_summary());
+
+    let config = config::parse_config();
+    match &config {
+        Ok(config) => {
+            let mut names = config.provider_names();
+            names.sort();
+            let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+            println!("Config:    ok (providers: {})", if names.is_empty() { "none".to_string() } else { names.join(", ") });
+        }
+        Err(e) => println!("Config:    invalid ({})", e),
+    }
+
+    let state = GmlState::load()?;
+    let expired_nodes: Vec<&str> = state.nodes.iter()
+        .filter(|n| !n.reserved && is_expired(&n.timeout))
+        .map(|n| n.id.as_str())
+        .collect();
+    let expired_clusters: Vec<&str> = state.clusters.iter()
+        .filter(|c| is_expired(&c.timeout))
+        .map(|c| c.id.as_str())
+        .collect();
+
+    println!("Nodes:     {}{}", state.nodes.len(), expired_suffix(expired_nodes.len()));
+    println!("Clusters:  {}{}", state.clusters.len(), expired_suffix(expired_clusters.len()));
+    let summary = ls::summarize(&state.nodes);
+    let unpriced = if summary.unpriced > 0 { format!(" ({} without a price)", summary.unpriced) } else { String::new() };
+    println!("Cost:      ~{}/hr{}", node::format_cost(summary.hourly_cost, "USD"), unpriced);
+
+    for id in expired_nodes {
+        println!("  expired node {} has not been cleaned up", id);
+    }
+    for id in expired_clusters {
+        println!("  expired cluster {} has not been cleaned up", id);
+    }
+    for node in state.nodes.iter().filter(|n| n.delete_is_stuck()) {
+        println!(
+            "  node {} failed to delete {} times and may still be billing; run `gml node delete {}`",
+            node.id, node.delete_attempts, node.id
+        );
+    }
+
+    if online {
+        if let Ok(config) = &config {
+            let mut names = config.provider_names();
+            names.sort();
+            println!("Providers:");
+            for name in names {
+                println!("  {:<8} {}", name, check_provider(config, name).await);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Describe whether gmld is running and whether its log is still being written
+fn daemon_summary() -> String {
+    let pid = match daemon_pid::running_daemon_pid() {
+        Ok(Some(pid)) => pid,
+        Ok(None) => return "not running".to_string(),
+        Err(e) => return format!("unknown ({})", e.message()),
+    };
+
+    let last_log = paths::gml_dir().ok()
+        .and_then(|dir| fs::metadata(dir.join("gmld.log")).ok())
+        .and_then(|meta| meta.modified().ok())
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+
+    match last_log {
+        Some(age) if age > DAEMON_STALE_AFTER => format!(
+            "stale (pid {}, last log {}s ago)",
+            pid,
+            age.as_secs()
+        ),
+        Some(age) => format!("running (pid {}, last log {}s ago)", pid, age.as_secs()),
+        None => format!("running (pid {})", pid),
+    }
+}
+
+/// Build a provider handle and make a cheap API call to confirm the provider is reachable
+async fn check_provider(config: &config::Config, name: &str) -> String {
+    let Some(provider_config) = config.get_provider(name) else {
+        return "not configured".to_string();
+    };
+    let handle = match providers::create_provider_handle(name, provider_config, None, config.ssh_public_key.clone()).await {
+        Ok(handle) => handle,
+        Err(e) => return format!("error: {}", e),
+    };
+    match handle.get_node_types(false).await {
+        Ok(_) => "ok".to_string(),
+        Err(e) => format!("error: {}", e),
+    }
+}
+
+fn expired_suffix(count: usize) -> String {
+    if count == 0 {
+        String::new()
+    } else {
+        format!(" ({} expired, awaiting cleanup)", count)
+    }
+}
+
+fn is_expired(timeout: &Option<String>) -> bool {
+    timeout.as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .is_some_and(|t| Utc::now() >= t.with_timezone(&Utc))
+}
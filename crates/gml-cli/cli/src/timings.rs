@@ -0,0 +1,55 @@
+//! Per-phase timings for `--timings`. Phases are only recorded once [`enable`] has been called,
+//! so instrumented code costs nothing in a normal run.
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+static TIMINGS: OnceLock<Mutex<Vec<(String, Duration)>>> = OnceLock::new();
+
+pub fn enable() {
+    TIMINGS.get_or_init(|| Mutex::new(Vec::new()));
+}
+
+/// Record a phase measured elsewhere
+pub fn record(phase: &str, elapsed: Duration) {
+    if let Some(timings) = TIMINGS.get() {
+        timings.lock().unwrap().push((phase.to_string(), elapsed));
+    }
+}
+
+/// Time a phase until the returned guard is dropped, including when it ends early with an error
+pub fn phase(name: &'static str) -> Phase {
+    Phase {
+        name,
+        start: Instant::now(),
+    }
+}
+
+pub struct Phase {
+    name: &'static str,
+    start: Instant,
+}
+
+impl Drop for Phase {
+    fn drop(&mut self) {
+        record(self.name, self.start.elapsed());
+    }
+}
+
+/// Print the recorded phases to stderr, so they never mix with a command's data output
+pub fn report() {
+    let Some(timings) = TIMINGS.get() else {
+        return;
+    };
+    let timings = timings.lock().unwrap();
+    if timings.is_empty() {
+        return;
+    }
+
+    eprintln!("Timings:");
+    for (phase, elapsed) in timings.iter() {
+        eprintln!("  {:<24} {:>9.2?}", phase, elapsed);
+    }
+    let total: Duration = timings.iter().map(|(_, elapsed)| *elapsed).sum();
+    eprintln!("  {:<24} {:>9.2?}", "total", total);
+}
@@ -0,0 +1,106 @@
+//! How long launches have taken, per provider and instance type, kept in `~/.gml/boot-stats.json`.
+//!
+//! Each successful launch records the time from the launch request until the node was reachable.
+//! Only the most recent samples are kept, so the estimate follows changes in provider behaviour.
+
+use crate::error::GmlError;
+use crate::lock::{self, FileLock};
+use crate::paths;
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const STATS_FILE: &str = "boot-stats.json";
+const STATS_LOCK_FILE: &str = "boot-stats.lock";
+
+/// Samples kept per instance type
+const MAX_SAMPLES: usize = 10;
+
+/// Boot times in whole seconds, most recent last, keyed by `<provider>/<instance type>`
+type BootStats = HashMap<String, Vec<u64>>;
+
+/// Record that launching `instance_type` on `provider` took `elapsed`
+pub fn record(provider: &str, instance_type: &str, elapsed: Duration) -> Result<(), GmlError> {
+    let _lock = lock_stats()?;
+    let mut stats = load()?;
+    push_sample(stats.entry(key(provider, instance_type)).or_default(), elapsed.as_secs());
+
+    let json = serde_json::to_string_pretty(&stats)
+        .map_err(|e| GmlError::from(format!("Failed to serialize boot stats: {}", e)))?;
+    let path = stats_path()?;
+    fs::write(&path, json)
+        .map_err(|e| GmlError::from(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Typical launch time for `instance_type` on `provider`, or `None` without any history
+pub fn expected(provider: &str, instance_type: &str) -> Result<Option<Duration>, GmlError> {
+    let stats = load()?;
+    Ok(stats.get(&key(provider, instance_type))
+        .and_then(|samples| median(samples))
+        .map(Duration::from_secs))
+}
+
+fn push_sample(samples: &mut Vec<u64>, secs: u64) {
+    samples.push(secs);
+    if samples.len() > MAX_SAMPLES {
+        samples.drain(..samples.len() - MAX_SAMPLES);
+    }
+}
+
+/// Median of the samples, which is less thrown off by one slow launch than the mean
+fn median(samples: &[u64]) -> Option<u64> {
+    if samples.is_empty() {
+        return None;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_unstable();
+    Some(sorted[sorted.len() / 2])
+}
+
+fn key(provider: &str, instance_type: &str) -> String {
+    format!("{}/{}", provider, instance_type)
+}
+
+fn load() -> Result<BootStats, GmlError> {
+    let path = stats_path()?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BootStats::new()),
+        Err(e) => return Err(GmlError::from(format!("Failed to read {}: {}", path.display(), e))),
+    };
+    serde_json::from_str(&contents)
+        .map_err(|e| GmlError::from(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+fn stats_path() -> Result<PathBuf, GmlError> {
+    let dir = paths::gml_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| GmlError::from(format!("Failed to create state directory: {}", e)))?;
+    Ok(dir.join(STATS_FILE))
+}
+
+fn lock_stats() -> Result<FileLock, GmlError> {
+    let dir = paths::gml_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| GmlError::from(format!("Failed to create state directory: {}", e)))?;
+    FileLock::acquire(&dir.join(STATS_LOCK_FILE), lock::lock_timeout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_recent_samples_and_takes_median() {
+        let mut samples = Vec::new();
+        for secs in 1..=15 {
+            push_sample(&mut samples, secs * 10);
+        }
+        assert_eq!(samples.len(), MAX_SAMPLES);
+        assert_eq!(samples[0], 60);
+        assert_eq!(median(&samples), Some(110));
+        assert_eq!(median(&[]), None);
+    }
+}
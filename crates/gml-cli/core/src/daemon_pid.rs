@@ -0,0 +1,97 @@
+//! `~/.gml/gmld.pid`: the PID of the running daemon, so that only one `gmld` runs at a time and
+//! `gml` can tell whether to start one.
+
+use crate::error::GmlError;
+use crate::lock::{self, FileLock};
+use crate::paths;
+use std::fs;
+use std::path::{Path, PathBuf};
+use sysinfo::{Pid, System};
+
+/// `~/.gml/gmld.pid`
+pub fn pid_path() -> Result<PathBuf, GmlError> {
+    Ok(paths::gml_dir()?.join("gmld.pid"))
+}
+
+/// The PID of the running daemon, or `None` if there is no PID file or the process it names is
+/// gone (or has since been reused by something other than `gmld`)
+pub fn running_daemon_pid() -> Result<Option<u32>, GmlError> {
+    Ok(read_pid(&pid_path()?).filter(|pid| is_daemon_process(*pid)))
+}
+
+/// The PID file of this daemon process; it is removed on drop
+#[derive(Debug)]
+pub struct DaemonPidFile {
+    path: PathBuf,
+}
+
+impl DaemonPidFile {
+    /// Record this process as the running daemon. Returns `None`, leaving the file alone, if it
+    /// already names another live daemon.
+    pub fn claim() -> Result<Option<DaemonPidFile>, GmlError> {
+        let path = pid_path()?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        claim_at(&path, is_daemon_process)
+    }
+}
+
+impl Drop for DaemonPidFile {
+    fn drop(&mut self) {
+        // Only remove the file while it still names this process
+        if read_pid(&self.path) == Some(std::process::id()) {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+/// Claim the PID file at `path` unless `is_running` says the PID in it belongs to a live daemon.
+/// Two daemons starting together could both find the file stale, so the check and write happen
+/// under a lock.
+fn claim_at(path: &Path, is_running: impl Fn(u32) -> bool) -> Result<Option<DaemonPidFile>, GmlError> {
+    let _lock = FileLock::acquire(&path.with_extension("pid.lock"), lock::lock_timeout())?;
+    let own = std::process::id();
+    if read_pid(path).is_some_and(|pid| pid != own && is_running(pid)) {
+        return Ok(None);
+    }
+    fs::write(path, format!("{}\n", own))?;
+    Ok(Some(DaemonPidFile { path: path.to_path_buf() }))
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// Whether `pid` is a live `gmld` process, not merely a live process that reused a dead daemon's PID
+fn is_daemon_process(pid: u32) -> bool {
+    let pid = Pid::from_u32(pid);
+    let mut system = System::new();
+    system.refresh_process(pid);
+    system.process(pid).is_some_and(|process| process.name().contains("gmld"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_pid_path() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gml-pid-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("gmld.pid")
+    }
+
+    #[test]
+    fn refuses_a_live_daemon_and_replaces_a_dead_one() {
+        let path = temp_pid_path();
+        fs::write(&path, "4242\n").unwrap();
+
+        assert!(claim_at(&path, |pid| pid == 4242).unwrap().is_none());
+        assert_eq!(read_pid(&path), Some(4242));
+
+        let claimed = claim_at(&path, |_| false).unwrap().expect("a dead daemon's PID file is taken over");
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+        drop(claimed);
+        assert!(!path.exists());
+    }
+}
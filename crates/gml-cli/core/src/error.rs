@@ -1,24 +1,143 @@
 #[derive(Debug)]
-pub struct GmlError {
-    pub message: String,
+pub enum GmlError {
+    /// A request to a provider's API could not be sent, or its response could not be read
+    Http(reqwest::Error),
+    /// A response or file did not have the expected shape
+    Parse(serde_json::Error),
+    Io(std::io::Error),
+    /// The provider's API rejected a request or returned an error. `body` is the provider's
+    /// message, or the raw response body when it couldn't be parsed.
+    Provider { status: Option<u16>, body: String },
+    /// The provider doesn't implement the operation
+    Unsupported(String),
+    Other(String),
+}
+
+/// Broad category of a failure, so callers can tell failures apart without parsing messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The provider's API couldn't be reached
+    Http,
+    /// The provider's API rejected a request or returned an error
+    Provider,
+    /// The provider doesn't implement the operation
+    Unsupported,
+    Parse,
+    Io,
+    Other,
+}
+
+impl ErrorKind {
+    /// Stable name used in machine-readable output
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Http => "http",
+            ErrorKind::Provider => "provider",
+            ErrorKind::Unsupported => "unsupported",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Io => "io",
+            ErrorKind::Other => "other",
+        }
+    }
 }
 
 impl std::fmt::Display for GmlError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "GmlError: {}", self.message)
+        write!(f, "GmlError: {}", self.message())
+    }
+}
+
+impl std::error::Error for GmlError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            GmlError::Http(e) => Some(e),
+            GmlError::Parse(e) => Some(e),
+            GmlError::Io(e) => Some(e),
+            GmlError::Provider { .. } | GmlError::Unsupported(_) | GmlError::Other(_) => None,
+        }
     }
 }
 
-impl std::error::Error for GmlError {}
+impl GmlError {
+    /// Error returned by optional `NodeProvider` operations a provider does not implement
+    pub fn unsupported(operation: &str) -> Self {
+        GmlError::Unsupported(format!("{} is not supported by this provider", operation))
+    }
+
+    /// Error reported by a provider's API without an HTTP status, e.g. from an SDK or GraphQL
+    pub fn provider(message: impl Into<String>) -> Self {
+        GmlError::Provider { status: None, body: message.into() }
+    }
+
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            GmlError::Http(_) => ErrorKind::Http,
+            GmlError::Parse(_) => ErrorKind::Parse,
+            GmlError::Io(_) => ErrorKind::Io,
+            GmlError::Provider { .. } => ErrorKind::Provider,
+            GmlError::Unsupported(_) => ErrorKind::Unsupported,
+            GmlError::Other(_) => ErrorKind::Other,
+        }
+    }
+
+    /// The error as a human-readable message, without the `GmlError:` prefix
+    pub fn message(&self) -> String {
+        match self {
+            GmlError::Http(e) => format!("Request failed: {}", e),
+            GmlError::Parse(e) => format!("Failed to parse response: {}", e),
+            GmlError::Io(e) => e.to_string(),
+            GmlError::Provider { body, .. } => body.clone(),
+            GmlError::Unsupported(message) | GmlError::Other(message) => message.clone(),
+        }
+    }
+}
 
 impl From<String> for GmlError {
     fn from(message: String) -> Self {
-        GmlError { message }
+        GmlError::Other(message)
     }
 }
 
 impl From<&str> for GmlError {
     fn from(message: &str) -> Self {
-        GmlError { message: message.to_string() }
+        GmlError::Other(message.to_string())
+    }
+}
+
+impl From<reqwest::Error> for GmlError {
+    fn from(e: reqwest::Error) -> Self {
+        GmlError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for GmlError {
+    fn from(e: serde_json::Error) -> Self {
+        GmlError::Parse(e)
+    }
+}
+
+impl From<std::io::Error> for GmlError {
+    fn from(e: std::io::Error) -> Self {
+        GmlError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::error::Error;
+
+    #[test]
+    fn wrapped_errors_are_reachable_through_source() {
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "state.json missing");
+        let err = GmlError::from(io);
+        assert_eq!(err.kind(), ErrorKind::Io);
+        let source = err.source().expect("io errors keep their source");
+        assert!(source.downcast_ref::<std::io::Error>().is_some());
+        assert_eq!(err.to_string(), "GmlError: state.json missing");
+
+        let parse = serde_json::from_str::<u32>("nope").unwrap_err();
+        assert!(GmlError::from(parse).source().is_some());
+        assert!(GmlError::from("plain").source().is_none());
     }
 }
@@ -0,0 +1,237 @@
+//! Journal of in-flight provider operations, kept in `~/.gml/pending.jsonl`.
+//!
+//! Each launch or terminate is recorded before it is sent to the provider and again whenever
+//! its status changes, one JSON line per record. The latest record for an operation wins, so a
+//! command that dies part-way leaves behind exactly the operations it never finished, which
+//! `gml resume` can then settle. Once nothing is pending the file is removed.
+
+use crate::error::GmlError;
+use crate::lock::{self, FileLock};
+use crate::paths;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+const JOURNAL_FILE: &str = "pending.jsonl";
+const JOURNAL_LOCK_FILE: &str = "pending.lock";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Launch,
+    Terminate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationStatus {
+    /// Recorded, but the provider hasn't acknowledged it yet
+    Planned,
+    /// The provider accepted a launch and assigned `provider_id`, but the node isn't in state yet
+    Launched,
+    Done,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Operation {
+    pub id: String,
+    pub kind: OperationKind,
+    pub status: OperationStatus,
+    pub provider: String,
+    /// Named provider account, for operations outside the provider's default account
+    #[serde(default)]
+    pub account: Option<String>,
+    pub region: Option<String>,
+    pub zone: Option<String>,
+    /// gml node ID; set for terminations, and for launches once the node is in state
+    pub node_id: Option<String>,
+    pub provider_id: Option<String>,
+    pub recorded_at: String, // RFC3339 timestamp in UTC
+    /// PID of the gml process running the operation; unset in journals from older versions
+    #[serde(default)]
+    pub owner_pid: Option<u32>,
+}
+
+impl Operation {
+    /// A launch that is about to be sent to `provider`
+    pub fn launch(provider: &str, region: Option<String>, zone: Option<String>) -> Operation {
+        Operation::new(OperationKind::Launch, provider, region, zone)
+    }
+
+    /// A termination of the tracked node `node_id`
+    pub fn terminate(
+        provider: &str,
+        node_id: &str,
+        provider_id: &str,
+        region: Option<String>,
+        zone: Option<String>,
+    ) -> Operation {
+        let mut op = Operation::new(OperationKind::Terminate, provider, region, zone);
+        op.node_id = Some(node_id.to_string());
+        op.provider_id = Some(provider_id.to_string());
+        op
+    }
+
+    fn new(kind: OperationKind, provider: &str, region: Option<String>, zone: Option<String>) -> Operation {
+        Operation {
+            id: uuid::Uuid::new_v4().to_string(),
+            kind,
+            status: OperationStatus::Planned,
+            provider: provider.to_string(),
+            account: None,
+            region,
+            zone,
+            node_id: None,
+            provider_id: None,
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            owner_pid: Some(std::process::id()),
+        }
+    }
+
+    /// The same operation, run in the named provider account
+    pub fn in_account(mut self, account: Option<String>) -> Operation {
+        self.account = account;
+        self
+    }
+
+    /// True while another gml process that is still running owns the operation, so it is in
+    /// progress rather than interrupted
+    pub fn is_in_flight(&self) -> bool {
+        self.owner_pid
+            .is_some_and(|pid| pid != std::process::id() && lock::is_process_alive(pid))
+    }
+
+    /// Record that the provider assigned `provider_id` to this launch
+    pub fn launched(&self, provider_id: &str) -> Result<(), GmlError> {
+        let mut op = self.clone();
+        op.status = OperationStatus::Launched;
+        op.provider_id = Some(provider_id.to_string());
+        record(&op)
+    }
+
+    /// Record that the operation finished (or was settled by `gml resume`)
+    pub fn done(&self) -> Result<(), GmlError> {
+        let mut op = self.clone();
+        op.status = OperationStatus::Done;
+        record(&op)
+    }
+}
+
+/// Append a record for `op`, replacing any earlier record with the same ID
+pub fn record(op: &Operation) -> Result<(), GmlError> {
+    let _lock = lock_journal()?;
+    let path = journal_path()?;
+
+    let mut line = serde_json::to_string(op)
+        .map_err(|e| GmlError::from(format!("Failed to serialize operation: {}", e)))?;
+    line.push('\n');
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| GmlError::from(format!("Failed to open {}: {}", path.display(), e)))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| GmlError::from(format!("Failed to write {}: {}", path.display(), e)))?;
+    drop(file);
+
+    // Completing the last pending operation leaves nothing worth keeping
+    if op.status == OperationStatus::Done && replay(&read_journal(&path)?).is_empty() {
+        let _ = fs::remove_file(&path);
+    }
+    Ok(())
+}
+
+/// Operations that were started but never recorded as done, oldest first
+pub fn pending() -> Result<Vec<Operation>, GmlError> {
+    let _lock = lock_journal()?;
+    Ok(replay(&read_journal(&journal_path()?)?))
+}
+
+/// Fold journal lines into the unfinished operations, keeping the latest record of each.
+/// Lines that don't parse (e.g. a write cut short by a crash) are skipped.
+fn replay(contents: &str) -> Vec<Operation> {
+    let mut ops: Vec<Operation> = Vec::new();
+    for op in contents.lines().filter_map(|line| serde_json::from_str::<Operation>(line).ok()) {
+        match ops.iter_mut().find(|existing| existing.id == op.id) {
+            Some(existing) => *existing = op,
+            None => ops.push(op),
+        }
+    }
+    ops.retain(|op| op.status != OperationStatus::Done);
+    ops
+}
+
+fn read_journal(path: &Path) -> Result<String, GmlError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => Ok(contents),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(String::new()),
+        Err(e) => Err(GmlError::from(format!("Failed to read {}: {}", path.display(), e))),
+    }
+}
+
+fn journal_path() -> Result<PathBuf, GmlError> {
+    let dir = paths::gml_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| GmlError::from(format!("Failed to create state directory: {}", e)))?;
+    Ok(dir.join(JOURNAL_FILE))
+}
+
+fn lock_journal() -> Result<FileLock, GmlError> {
+    let dir = paths::gml_dir()?;
+    fs::create_dir_all(&dir)
+        .map_err(|e| GmlError::from(format!("Failed to create state directory: {}", e)))?;
+    FileLock::acquire(&dir.join(JOURNAL_LOCK_FILE), lock::lock_timeout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(op: &Operation) -> String {
+        serde_json::to_string(op).unwrap() + "\n"
+    }
+
+    #[test]
+    fn replay_keeps_latest_unfinished_records() {
+        let launch = Operation::launch("lambda", Some("us-east-1".to_string()), None);
+        let mut launched = launch.clone();
+        launched.status = OperationStatus::Launched;
+        launched.provider_id = Some("i-123".to_string());
+
+        let terminate = Operation::terminate("google", "node-1", "projects/p/nodes/n", None, None);
+        let mut terminated = terminate.clone();
+        terminated.status = OperationStatus::Done;
+
+        let journal = [line(&launch), line(&terminate), line(&launched), line(&terminated)].concat();
+        let pending = replay(&journal);
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].id, launch.id);
+        assert_eq!(pending[0].status, OperationStatus::Launched);
+        assert_eq!(pending[0].provider_id.as_deref(), Some("i-123"));
+    }
+
+    #[test]
+    fn only_operations_of_other_live_processes_are_in_flight() {
+        let mut op = Operation::launch("lambda", None, None);
+        assert!(!op.is_in_flight());
+
+        let mut other = std::process::Command::new("sleep").arg("30").spawn().unwrap();
+        op.owner_pid = Some(other.id());
+        assert!(op.is_in_flight());
+        other.kill().unwrap();
+        other.wait().unwrap();
+        assert!(!op.is_in_flight());
+
+        op.owner_pid = None;
+        assert!(!op.is_in_flight());
+    }
+
+    #[test]
+    fn replay_skips_truncated_lines() {
+        let launch = Operation::launch("lambda", None, None);
+        let journal = line(&launch) + "{\"id\":\"cut-sho";
+        assert_eq!(replay(&journal).len(), 1);
+    }
+}
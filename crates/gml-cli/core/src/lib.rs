@@ -1,26 +1,571 @@
+pub mod boot_stats;
+pub mod daemon_pid;
 pub mod error;
+pub mod journal;
+pub mod lock;
+pub mod net;
+pub mod paths;
 pub mod ssh;
 pub mod state;
+pub mod suggest;
 
 use async_trait::async_trait;
 use error::GmlError;
+use futures::future;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
 
 #[async_trait]
 pub trait NodeProvider: Send + Sync {
     async fn start_node(&self, request: NodeRequest) -> Result<NodeDetails, GmlError>;
     async fn stop_node(&self, details: NodeDetails) -> Result<NodeDetails, GmlError>;
     async fn get_user(&self) -> Result<String, GmlError>;
-    async fn get_node_types(&self) -> Result<String, GmlError>;
+    /// The provider's own node type listing as pretty JSON. With `available_only`, types the
+    /// provider reports as having no capacity anywhere are left out, where it reports capacity.
+    async fn get_node_types(&self, available_only: bool) -> Result<String, GmlError>;
+
+    /// Catch mistakes the provider would otherwise only report after a launch is attempted,
+    /// such as a misconfigured SSH key name
+    async fn validate_request(&self, _request: &NodeRequest) -> Result<(), GmlError> {
+        Ok(())
+    }
+
+    /// The API call `start_node` would make for `request`, for `--dry-run`. Nothing is sent.
+    fn describe_launch(&self, _request: &NodeRequest) -> Result<String, GmlError> {
+        Err(GmlError::unsupported("Describing launches"))
+    }
+
+    /// Apply tags to the node on the provider side (e.g. cloud console labels used for cost allocation)
+    async fn set_tags(&self, _details: &NodeDetails, _tags: &HashMap<String, String>) -> Result<(), GmlError> {
+        Err(GmlError::unsupported("Tagging nodes"))
+    }
+
+    /// List the node types this provider can launch, with their specs
+    async fn list_node_types(&self) -> Result<Vec<NodeType>, GmlError> {
+        Err(GmlError::unsupported("Listing node types"))
+    }
+
+    /// Look up what the requested node would cost to run
+    async fn estimate_cost(&self, _request: &NodeRequest) -> Result<CostEstimate, GmlError> {
+        Err(GmlError::unsupported("Estimating cost"))
+    }
+
+    /// Power the node off but keep its disk, so compute stops billing until it is resumed
+    async fn pause_node(&self, _details: &NodeDetails) -> Result<(), GmlError> {
+        Err(GmlError::unsupported("Pausing nodes"))
+    }
+
+    /// Power a paused node back on; the returned details carry its new IP, which may have changed
+    async fn resume_node(&self, _details: &NodeDetails) -> Result<NodeDetails, GmlError> {
+        Err(GmlError::unsupported("Resuming nodes"))
+    }
+
+    /// Current state of the node with provider ID `id`, in the same terms as `NodeDetails::status`.
+    /// A node that no longer exists on the provider side is an error rather than a status.
+    async fn get_node_status(&self, _id: &str) -> Result<String, GmlError> {
+        Err(GmlError::unsupported("Querying node status"))
+    }
+
+    /// Every node that currently exists in the account, including ones not launched by gml.
+    /// Terminated nodes are left out, even if the provider still reports them for a while.
+    async fn list_nodes(&self) -> Result<Vec<NodeDetails>, GmlError> {
+        Err(GmlError::unsupported("Listing nodes"))
+    }
+
+    /// Names of the regions this provider can launch into, as used for `region` in the config
+    async fn list_regions(&self) -> Result<Vec<String>, GmlError> {
+        Err(GmlError::unsupported("Listing regions"))
+    }
 }
 
+#[derive(Debug, Clone)]
 pub struct NodeDetails {
     pub ip: String,
-    pub id: String
+    pub id: String,
+    /// Availability zone the node was placed in, for providers that have zones
+    pub zone: Option<String>,
+    /// SSH port on `ip`, for providers that don't expose SSH on 22
+    pub ssh_port: Option<u16>,
+    /// State the provider reported for the node, in gml's terms where they overlap (`active`,
+    /// `paused`), otherwise the provider's own lowercased name. `None` when the call doesn't report one.
+    pub status: Option<String>,
 }
 
+/// How long a provider waits for a launched node to become reachable, and how often it checks
+#[derive(Debug, Clone, Copy)]
+pub struct BootPolling {
+    pub timeout: std::time::Duration,
+    pub interval: std::time::Duration,
+}
+
+impl Default for BootPolling {
+    fn default() -> Self {
+        BootPolling {
+            timeout: std::time::Duration::from_secs(10 * 60),
+            interval: std::time::Duration::from_secs(10),
+        }
+    }
+}
+
+impl BootPolling {
+    /// Number of status checks that fit in the timeout, at least one
+    pub fn attempts(&self) -> u32 {
+        let attempts = self.timeout.as_secs_f64() / self.interval.as_secs_f64();
+        (attempts.ceil() as u32).max(1)
+    }
+}
+
+/// Receives status strings reported by a provider (e.g. "booting", "active") while a node launches.
+pub type StatusCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Receives the provider ID of a launching node as soon as the provider has assigned it, before
+/// the node finishes booting, along with the region it was launched in when the provider knows
+/// it from the request.
+pub type LaunchedCallback = Arc<dyn Fn(&str, Option<&str>) + Send + Sync>;
+
+#[derive(Clone)]
 pub struct NodeRequest {
-    pub instance_type: String
+    pub instance_type: String,
+    /// Region to launch in instead of the one the handle was created for; ignored by providers
+    /// whose handles are tied to a region
+    pub region: Option<String>,
+    /// Availability zone to launch into; ignored by providers without zones
+    pub zone: Option<String>,
+    /// Subnet (or subnetwork) to attach the node to; ignored by providers without VPC placement
+    pub subnet: Option<String>,
+    /// Boot/ephemeral disk size; providers that can't size disks must reject the request
+    pub disk_gb: Option<u32>,
+    /// OpenSSH public key line to authorize instead of the configured one, from `--private-key-from`
+    pub public_key: Option<String>,
+    /// Called whenever the provider reports a new status for the launching node
+    pub on_status: Option<StatusCallback>,
+    /// Called once the provider has accepted the launch; must be called before any further waiting
+    pub on_launched: Option<LaunchedCallback>,
 }
 
-pub trait ClusterProvider {}
+/// Price of running a node, as quoted by its provider
+#[derive(Debug, Clone)]
+pub struct CostEstimate {
+    pub hourly: f64,
+    /// ISO 4217 currency code, e.g. "USD"
+    pub currency: String,
+}
+
+impl CostEstimate {
+    /// Total cost of running for the given duration
+    pub fn total_for(&self, duration: std::time::Duration) -> f64 {
+        self.hourly * duration.as_secs_f64() / 3600.0
+    }
+}
+
+/// A launchable node type, as listed by its provider
+#[derive(Debug, Clone)]
+pub struct NodeType {
+    pub name: String,
+    pub description: String,
+    /// Number of GPUs (or TPU chips) on the node
+    pub accelerator_count: u32,
+    /// Memory per accelerator in GB, when the provider reports it
+    pub memory_gb: Option<u32>,
+    pub price: Option<CostEstimate>,
+    /// Regions with capacity right now, or `None` if the provider doesn't report availability
+    pub regions: Option<Vec<String>>,
+}
+
+impl NodeType {
+    /// False only when the provider reports that no region has capacity
+    pub fn has_capacity(&self) -> bool {
+        self.regions.as_ref().is_none_or(|regions| !regions.is_empty())
+    }
+
+    /// False only when the provider reports that `region` has no capacity
+    pub fn available_in(&self, region: &str) -> bool {
+        self.regions.as_ref().is_none_or(|regions| regions.iter().any(|r| r == region))
+    }
+}
+
+/// The cheapest priced type with at least `min_memory_gb` per accelerator and capacity in `region`
+pub fn cheapest_node_type<'a>(
+    types: &'a [NodeType],
+    min_memory_gb: Option<u32>,
+    region: Option<&str>,
+) -> Option<&'a NodeType> {
+    types
+        .iter()
+        .filter(|t| t.has_capacity())
+        .filter(|t| region.is_none_or(|region| t.available_in(region)))
+        .filter(|t| min_memory_gb.is_none_or(|min| t.memory_gb.is_some_and(|gb| gb >= min)))
+        .filter_map(|t| t.price.as_ref().map(|price| (price.hourly, t)))
+        .min_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, t)| t)
+}
+
+/// A request for a group of nodes launched together
+pub struct ClusterRequest {
+    pub node_count: usize,
+    /// Regions to spread members across, assigned round-robin; empty uses the provider's default.
+    /// Members in different regions can't share a private network, so they must talk over public IPs.
+    pub regions: Vec<String>,
+    /// What to launch for each member
+    pub member: NodeRequest,
+    /// Launch slots shared with the caller's other launches; each member holds one while the
+    /// provider launches it. Without them every member is launched at once.
+    pub launch_slots: Option<Arc<Semaphore>>,
+}
+
+impl ClusterRequest {
+    /// Region for the member at `index`, cycling through `regions`
+    pub fn region_for(&self, index: usize) -> Option<&str> {
+        round_robin_region(&self.regions, index)
+    }
+
+    /// True when members will land in more than one region
+    pub fn is_multi_region(&self) -> bool {
+        spans_multiple_regions(self.regions.iter().take(self.node_count))
+    }
+}
+
+/// Region for the member at `index` when members are assigned to `regions` in turn
+pub fn round_robin_region(regions: &[String], index: usize) -> Option<&str> {
+    if regions.is_empty() {
+        None
+    } else {
+        Some(regions[index % regions.len()].as_str())
+    }
+}
+
+/// True when the given per-member regions contain more than one distinct region
+pub fn spans_multiple_regions<'a>(regions: impl IntoIterator<Item = &'a String>) -> bool {
+    let mut regions: Vec<&String> = regions.into_iter().collect();
+    regions.sort();
+    regions.dedup();
+    regions.len() > 1
+}
 
+/// The members of a running cluster, in launch order
+#[derive(Debug, Clone)]
+pub struct ClusterDetails {
+    pub nodes: Vec<NodeDetails>,
+}
+
+/// Cluster operations on top of a provider's node operations. The defaults launch and stop each
+/// member as an independent node, so a provider with native clusters or placement groups only
+/// needs to override them.
+#[async_trait]
+pub trait ClusterProvider: NodeProvider {
+    /// Launch every member of the cluster. Either all members come up, or the ones that did are
+    /// stopped again before the error is returned.
+    ///
+    /// By default every member is launched with this handle's `start_node`, in parallel up to the
+    /// request's launch slots. A handle
+    /// is bound to one region, so a request spread over several regions is unsupported unless the
+    /// provider overrides this.
+    async fn create_cluster(&self, request: ClusterRequest) -> Result<ClusterDetails, GmlError> {
+        if request.is_multi_region() {
+            return Err(GmlError::unsupported("Spreading cluster members across regions"));
+        }
+        let launches = (0..request.node_count)
+            .map(|_| (self, request.member.clone()))
+            .collect();
+        let nodes = start_nodes_with_rollback(launches, request.launch_slots.as_deref()).await?;
+        Ok(ClusterDetails { nodes })
+    }
+
+    /// Stop every member, carrying on past failures so one bad node doesn't leave the rest running
+    async fn delete_cluster(&self, details: ClusterDetails) -> Result<(), GmlError> {
+        stop_nodes(details.nodes.into_iter().map(|node| (self, node)).collect()).await
+    }
+}
+
+/// Check `region` against the provider's region list. Providers that can't list their regions
+/// pass every region, since the launch itself will still reject a bad one.
+pub async fn check_region(provider: &dyn NodeProvider, region: &str) -> Result<(), GmlError> {
+    match provider.list_regions().await {
+        Ok(regions) if regions.iter().any(|r| r == region) => Ok(()),
+        Ok(regions) => Err(GmlError::from(unknown_region_message(region, &regions))),
+        Err(GmlError::Unsupported(_)) => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+/// "Unknown region" error text, suggesting the closest of `regions` or listing them all
+pub fn unknown_region_message(region: &str, regions: &[String]) -> String {
+    let names: Vec<&str> = regions.iter().map(String::as_str).collect();
+    let mut message = format!("Unknown region '{}'", region);
+    match suggest::closest_matches(region, &names).first() {
+        Some(closest) => message.push_str(&format!("; did you mean '{}'?", closest)),
+        None => message.push_str(&format!("; available: {}", names.join(", "))),
+    }
+    message
+}
+
+/// Launch each `(provider, request)` pair concurrently, each holding one of `slots` if given while
+/// the provider launches it. If any launch fails, the nodes that did
+/// start are stopped, and the error lists the failures along with any node that couldn't be stopped.
+///
+/// A launch that fails after the provider accepted it, e.g. while waiting for boot, may have left
+/// an instance running, so that instance is stopped as well.
+pub async fn start_nodes_with_rollback<P: NodeProvider + ?Sized>(
+    launches: Vec<(&P, NodeRequest)>,
+    slots: Option<&Semaphore>,
+) -> Result<Vec<NodeDetails>, GmlError> {
+    let total = launches.len();
+    let results = future::join_all(launches.into_iter().map(|(provider, mut request)| async move {
+        let accepted: Arc<Mutex<Option<String>>> = Arc::default();
+        let on_launched = request.on_launched.take();
+        request.on_launched = Some({
+            let accepted = accepted.clone();
+            Arc::new(move |provider_id: &str, region: Option<&str>| {
+                *accepted.lock().unwrap() = Some(provider_id.to_string());
+                if let Some(on_launched) = &on_launched {
+                    on_launched(provider_id, region);
+                }
+            })
+        });
+        let zone = request.zone.clone();
+        let slot = match slots {
+            Some(slots) => Some(slots.acquire().await.expect("launch semaphore is never closed")),
+            None => None,
+        };
+        let result = provider.start_node(request).await;
+        drop(slot);
+        let accepted = accepted.lock().unwrap().take()
+            .map(|id| NodeDetails { ip: String::new(), id, zone, ssh_port: None, status: None });
+        (provider, result, accepted)
+    }))
+    .await;
+
+    let mut started = Vec::new();
+    let mut failures = Vec::new();
+    for (index, (provider, result, accepted)) in results.into_iter().enumerate() {
+        match result {
+            Ok(details) => started.push((provider, details)),
+            Err(e) => {
+                failures.push(format!("node {}: {}", index, e.message()));
+                if let Some(details) = accepted {
+                    started.push((provider, details));
+                }
+            }
+        }
+    }
+    if failures.is_empty() {
+        return Ok(started.into_iter().map(|(_, details)| details).collect());
+    }
+
+    let mut leftovers = Vec::new();
+    for (provider, details) in &started {
+        if let Err(e) = provider.stop_node(details.clone()).await {
+            leftovers.push(format!("{} ({})", details.id, e.message()));
+        }
+    }
+    let mut message = format!(
+        "{} of {} node(s) failed to launch:\n  - {}",
+        failures.len(), total, failures.join("\n  - ")
+    );
+    if leftovers.is_empty() {
+        message.push_str(&format!("\nStopped the {} node(s) that had launched", started.len()));
+    } else {
+        message.push_str(&format!(
+            "\nThese nodes could not be stopped and may still be running: {}",
+            leftovers.join(", ")
+        ));
+    }
+    Err(GmlError::from(message))
+}
+
+/// Stop each `(provider, node)` pair, attempting every node even after a failure
+pub async fn stop_nodes<P: NodeProvider + ?Sized>(nodes: Vec<(&P, NodeDetails)>) -> Result<(), GmlError> {
+    let results = future::join_all(nodes.into_iter().map(|(provider, details)| async move {
+        let id = details.id.clone();
+        provider.stop_node(details).await.map_err(|e| format!("{} ({})", id, e.message()))
+    }))
+    .await;
+    let failures: Vec<String> = results.into_iter().filter_map(Result::err).collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(GmlError::from(format!("Failed to stop node(s): {}", failures.join(", "))))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_type(name: &str, memory_gb: u32, hourly: f64, regions: &[&str]) -> NodeType {
+        NodeType {
+            name: name.to_string(),
+            description: String::new(),
+            accelerator_count: 1,
+            memory_gb: Some(memory_gb),
+            price: Some(CostEstimate { hourly, currency: "USD".to_string() }),
+            regions: Some(regions.iter().map(|r| r.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn cheapest_respects_memory_and_region() {
+        let types = vec![
+            node_type("a10", 24, 0.75, &["us-east-1"]),
+            node_type("a100", 40, 1.29, &["us-west-1"]),
+            node_type("h100", 80, 2.49, &["us-east-1"]),
+            node_type("gh200", 96, 1.49, &[]),
+        ];
+        assert_eq!(cheapest_node_type(&types, None, None).unwrap().name, "a10");
+        assert_eq!(cheapest_node_type(&types, Some(40), None).unwrap().name, "a100");
+        assert_eq!(cheapest_node_type(&types, Some(40), Some("us-east-1")).unwrap().name, "h100");
+        assert!(cheapest_node_type(&types, Some(100), None).is_none());
+    }
+
+    fn node_request(instance_type: &str) -> NodeRequest {
+        NodeRequest {
+            instance_type: instance_type.to_string(),
+            region: None,
+            zone: None,
+            subnet: None,
+            disk_gb: None,
+            public_key: None,
+            on_status: None,
+            on_launched: None,
+        }
+    }
+
+    #[test]
+    fn boot_polling_attempts_cover_the_timeout() {
+        let secs = std::time::Duration::from_secs;
+        assert_eq!(BootPolling::default().attempts(), 60);
+        assert_eq!(BootPolling { timeout: secs(20 * 60), interval: secs(10) }.attempts(), 120);
+        assert_eq!(BootPolling { timeout: secs(25), interval: secs(10) }.attempts(), 3);
+        assert_eq!(BootPolling { timeout: secs(5), interval: secs(10) }.attempts(), 1);
+    }
+
+    #[test]
+    fn cluster_regions_round_robin() {
+        let request = ClusterRequest {
+            node_count: 3,
+            regions: vec!["us-east-1".to_string(), "us-west-1".to_string()],
+            member: node_request("a10"),
+            launch_slots: None,
+        };
+        assert_eq!(request.region_for(0), Some("us-east-1"));
+        assert_eq!(request.region_for(1), Some("us-west-1"));
+        assert_eq!(request.region_for(2), Some("us-east-1"));
+        assert!(request.is_multi_region());
+
+        let single = ClusterRequest { node_count: 1, regions: request.regions.clone(), member: node_request("a10"), launch_slots: None };
+        assert!(!single.is_multi_region());
+    }
+
+    #[test]
+    fn unknown_region_suggests_the_closest_name() {
+        let regions = vec!["us-east-1".to_string(), "us-west-1".to_string(), "europe-central-1".to_string()];
+        assert_eq!(unknown_region_message("us-eas-1", &regions), "Unknown region 'us-eas-1'; did you mean 'us-east-1'?");
+        assert_eq!(
+            unknown_region_message("mars-1", &regions),
+            "Unknown region 'mars-1'; available: us-east-1, us-west-1, europe-central-1"
+        );
+    }
+
+    /// Launches every type except "broken", and remembers which nodes it was asked to stop and how
+    /// many of `slots` were free during each launch
+    #[derive(Default)]
+    struct FakeProvider {
+        stopped: std::sync::Mutex<Vec<String>>,
+        slots: Option<Arc<Semaphore>>,
+        free_slots: std::sync::Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl NodeProvider for FakeProvider {
+        async fn start_node(&self, request: NodeRequest) -> Result<NodeDetails, GmlError> {
+            if let Some(slots) = &self.slots {
+                self.free_slots.lock().unwrap().push(slots.available_permits());
+            }
+            if request.instance_type == "broken" {
+                return Err(GmlError::provider("no capacity"));
+            }
+            if request.instance_type == "slow" {
+                // Accepted, then never finishes booting
+                if let Some(on_launched) = &request.on_launched {
+                    on_launched("slow", None);
+                }
+                return Err(GmlError::from("timed out waiting for boot"));
+            }
+            Ok(NodeDetails { ip: String::new(), id: request.instance_type, zone: None, ssh_port: None, status: None })
+        }
+        async fn stop_node(&self, details: NodeDetails) -> Result<NodeDetails, GmlError> {
+            self.stopped.lock().unwrap().push(details.id.clone());
+            Ok(details)
+        }
+        async fn get_user(&self) -> Result<String, GmlError> {
+            Ok("ubuntu".to_string())
+        }
+        async fn get_node_types(&self, _available_only: bool) -> Result<String, GmlError> {
+            Ok(String::new())
+        }
+    }
+
+    #[test]
+    fn failed_launch_stops_the_nodes_that_started() {
+        let provider = FakeProvider::default();
+        let launches = ["a", "broken", "b"].iter()
+            .map(|t| (&provider as &dyn NodeProvider, node_request(t)))
+            .collect();
+        let error = futures::executor::block_on(start_nodes_with_rollback(launches, None)).unwrap_err();
+        assert!(error.message().contains("node 1: no capacity"));
+        assert_eq!(*provider.stopped.lock().unwrap(), vec!["a", "b"]);
+
+        provider.stopped.lock().unwrap().clear();
+        let launches = ["a", "slow"].iter()
+            .map(|t| (&provider as &dyn NodeProvider, node_request(t)))
+            .collect();
+        let error = futures::executor::block_on(start_nodes_with_rollback(launches, None)).unwrap_err();
+        assert!(error.message().contains("node 1: timed out waiting for boot"));
+        assert_eq!(*provider.stopped.lock().unwrap(), vec!["a", "slow"]);
+
+        let launches = ["a", "b"].iter()
+            .map(|t| (&provider as &dyn NodeProvider, node_request(t)))
+            .collect();
+        let nodes = futures::executor::block_on(start_nodes_with_rollback(launches, None)).unwrap();
+        assert_eq!(nodes.iter().map(|n| n.id.as_str()).collect::<Vec<_>>(), vec!["a", "b"]);
+    }
+
+    impl ClusterProvider for FakeProvider {}
+
+    #[test]
+    fn default_cluster_launches_and_stops_every_member() {
+        let provider = FakeProvider::default();
+        let request = |regions: &[&str]| ClusterRequest {
+            node_count: 3,
+            regions: regions.iter().map(|r| r.to_string()).collect(),
+            member: node_request("a"),
+            launch_slots: None,
+        };
+
+        let details = futures::executor::block_on(provider.create_cluster(request(&["us-east-1"]))).unwrap();
+        assert_eq!(details.nodes.len(), 3);
+        futures::executor::block_on(provider.delete_cluster(details)).unwrap();
+        assert_eq!(*provider.stopped.lock().unwrap(), vec!["a", "a", "a"]);
+
+        let error = futures::executor::block_on(provider.create_cluster(request(&["us-east-1", "us-west-1"]))).unwrap_err();
+        assert!(matches!(error, GmlError::Unsupported(_)));
+    }
+
+    #[test]
+    fn default_cluster_holds_a_launch_slot_per_member() {
+        let slots = Arc::new(Semaphore::new(2));
+        let provider = FakeProvider { slots: Some(slots.clone()), ..FakeProvider::default() };
+        let request = ClusterRequest {
+            node_count: 3,
+            regions: Vec::new(),
+            member: node_request("a"),
+            launch_slots: Some(slots.clone()),
+        };
+
+        futures::executor::block_on(provider.create_cluster(request)).unwrap();
+        assert_eq!(*provider.free_slots.lock().unwrap(), vec![1, 1, 1]);
+        assert_eq!(slots.available_permits(), 2);
+    }
+}
@@ -0,0 +1,125 @@
+//! Lock file guarding read-modify-write cycles on `~/.gml/state.json`.
+//!
+//! Mutual exclusion comes from an OS advisory lock on the file (`flock` on Unix, `LockFileEx` on
+//! Windows), which the OS releases when the holder exits, even if it crashes. So no lock is ever
+//! left stale. The file also records the PID of the current holder, for error messages.
+
+use crate::error::GmlError;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{Seek, Write};
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+use sysinfo::{Pid, System};
+
+/// How long to wait for another process to release a held lock
+pub const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Overrides [`DEFAULT_LOCK_TIMEOUT`], in whole seconds
+const LOCK_TIMEOUT_ENV: &str = "GML_LOCK_TIMEOUT";
+
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// An exclusive lock owned by this process, released on drop.
+///
+/// The lock file itself is left in place: removing it could let a waiter lock the removed file
+/// while a newcomer locks a fresh one at the same path, and both would think they hold the lock.
+#[derive(Debug)]
+pub struct FileLock {
+    // Closing the file releases the OS lock
+    _file: File,
+}
+
+impl FileLock {
+    /// Acquire the lock at `path`, waiting up to `timeout` for the current holder to release it
+    pub fn acquire(path: &Path, timeout: Duration) -> Result<FileLock, GmlError> {
+        let start = Instant::now();
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(|e| GmlError::from(format!("Failed to open lock file {}: {}", path.display(), e)))?;
+
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) => {}
+                Err(TryLockError::Error(e)) => {
+                    return Err(GmlError::from(format!("Failed to lock {}: {}", path.display(), e)));
+                }
+            }
+
+            if start.elapsed() >= timeout {
+                let holder = read_holder_pid(path).map_or("another process".to_string(), |pid| format!("process {}", pid));
+                return Err(GmlError::from(format!(
+                    "Timed out after {}s waiting for lock {} held by {}",
+                    timeout.as_secs(),
+                    path.display(),
+                    holder
+                )));
+            }
+            thread::sleep(RETRY_INTERVAL);
+        }
+
+        // Only informational, so failing to record the holder doesn't fail the lock
+        let _ = file.set_len(0)
+            .and_then(|()| file.rewind())
+            .and_then(|()| writeln!(file, "{}", std::process::id()));
+        Ok(FileLock { _file: file })
+    }
+}
+
+/// Lock timeout from `GML_LOCK_TIMEOUT` (seconds), falling back to [`DEFAULT_LOCK_TIMEOUT`]
+pub fn lock_timeout() -> Duration {
+    std::env::var(LOCK_TIMEOUT_ENV)
+        .ok()
+        .and_then(|secs| secs.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_LOCK_TIMEOUT)
+}
+
+/// Returns true if a process with the given PID is currently running
+pub fn is_process_alive(pid: u32) -> bool {
+    let mut system = System::new();
+    system.refresh_process(Pid::from_u32(pid))
+}
+
+fn read_holder_pid(path: &Path) -> Option<u32> {
+    std::fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_lock_path() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("gml-lock-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("state.lock")
+    }
+
+    #[test]
+    fn leftover_lock_file_is_acquired_right_away() {
+        let path = temp_lock_path();
+        // What a crashed holder leaves behind: the file, but no OS lock on it
+        fs::write(&path, "4294967\n").unwrap();
+
+        let lock = FileLock::acquire(&path, Duration::ZERO).unwrap();
+        assert_eq!(read_holder_pid(&path), Some(std::process::id()));
+
+        drop(lock);
+        assert!(FileLock::acquire(&path, Duration::ZERO).is_ok());
+    }
+
+    #[test]
+    fn times_out_while_held() {
+        let path = temp_lock_path();
+        let _held = FileLock::acquire(&path, Duration::from_millis(100)).unwrap();
+
+        let err = FileLock::acquire(&path, Duration::from_millis(100)).unwrap_err();
+        assert!(err.to_string().contains(&format!("held by process {}", std::process::id())), "{}", err);
+    }
+}
@@ -0,0 +1,44 @@
+//! Best-effort network checks against nodes.
+
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::NodeDetails;
+use crate::error::GmlError;
+
+/// How long to wait for a node to answer before calling it unreachable
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Time a TCP handshake with the node's SSH port. One round trip, without needing ICMP, which
+/// many providers' firewalls drop.
+pub fn tcp_latency(details: &NodeDetails, timeout: Duration) -> Result<Duration, GmlError> {
+    let port = details.ssh_port.unwrap_or(22);
+    let addr = (details.ip.as_str(), port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("No address for {}", details.ip))?;
+    let started = Instant::now();
+    TcpStream::connect_timeout(&addr, timeout)?;
+    Ok(started.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    fn details(ip: &str, ssh_port: Option<u16>) -> NodeDetails {
+        NodeDetails { ip: ip.to_string(), id: "i-1".to_string(), zone: None, ssh_port, status: None }
+    }
+
+    #[test]
+    fn times_a_handshake_and_reports_refusals() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert!(tcp_latency(&details("127.0.0.1", Some(port)), CONNECT_TIMEOUT).is_ok());
+
+        drop(listener);
+        assert!(tcp_latency(&details("127.0.0.1", Some(port)), CONNECT_TIMEOUT).is_err());
+        assert!(tcp_latency(&details("", None), CONNECT_TIMEOUT).is_err());
+    }
+}
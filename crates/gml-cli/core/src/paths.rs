@@ -0,0 +1,15 @@
+//! Locations of gml's files. Every crate resolves the home directory through here so that
+//! config, state and daemon logs always end up in the same `~/.gml`.
+
+use crate::error::GmlError;
+use std::path::PathBuf;
+
+/// The user's home directory, as reported by the OS
+pub fn home_dir() -> Result<PathBuf, GmlError> {
+    dirs::home_dir().ok_or_else(|| GmlError::from("Unable to determine home directory"))
+}
+
+/// `~/.gml`, which holds the config, state and daemon log
+pub fn gml_dir() -> Result<PathBuf, GmlError> {
+    Ok(home_dir()?.join(".gml"))
+}
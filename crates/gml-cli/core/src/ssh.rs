@@ -1,12 +1,14 @@
 //! Shared SSH public key resolution for `gml connect` and providers (e.g. Google TPU metadata).
 
 use crate::error::GmlError;
+use crate::paths;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::Command;
 
 fn expand_user_path(path: &str) -> PathBuf {
     if let Some(rest) = path.strip_prefix("~/") {
-        if let Some(home) = dirs::home_dir() {
+        if let Ok(home) = paths::home_dir() {
             return home.join(rest);
         }
     }
@@ -29,7 +31,7 @@ pub fn get_ssh_public_key(config_ssh_public_key: Option<&str>) -> Result<PathBuf
             path.display()
         )));
     }
-    let home = dirs::home_dir().ok_or_else(|| {
+    let home = paths::home_dir().map_err(|_| {
         GmlError::from(
             "SSH public key: set [gml] ssh-public-key in ~/.gml/config.toml, or ensure HOME is set to search ~/.ssh/",
         )
@@ -54,11 +56,7 @@ pub fn read_ssh_public_key_line(path: &Path) -> Result<String, GmlError> {
     if line.is_empty() {
         return Err(GmlError::from(format!("Empty SSH public key file: {}", path.display())));
     }
-    if !(line.starts_with("ssh-rsa")
-        || line.starts_with("ssh-ed25519")
-        || line.starts_with("ecdsa-")
-        || line.starts_with("ssh-dss"))
-    {
+    if !is_public_key_line(line) {
         return Err(GmlError::from(format!(
             "File {} does not look like an SSH public key (expected ssh-rsa, ssh-ed25519, ...)",
             path.display()
@@ -66,3 +64,111 @@ pub fn read_ssh_public_key_line(path: &Path) -> Result<String, GmlError> {
     }
     Ok(line.to_string())
 }
+
+fn is_public_key_line(line: &str) -> bool {
+    line.starts_with("ssh-rsa")
+        || line.starts_with("ssh-ed25519")
+        || line.starts_with("ecdsa-")
+        || line.starts_with("ssh-dss")
+}
+
+/// A private key supplied with `--private-key-from`, and the public key to authorize for it
+#[derive(Debug, Clone)]
+pub struct PrivateKey {
+    pub path: PathBuf,
+    pub public_key: String,
+}
+
+/// Loads a private key for `--private-key-from`.
+///
+/// The key file must not be readable by group or others, since `ssh` refuses such keys. The
+/// public key is read from the sibling `<path>.pub` if there is one, and otherwise derived with
+/// `ssh-keygen -y`, which prompts for the passphrase of an encrypted key.
+pub fn load_private_key(path: &str) -> Result<PrivateKey, GmlError> {
+    let path = expand_user_path(path.trim());
+    let path = fs::canonicalize(&path).map_err(|e| {
+        GmlError::from(format!("Failed to read private key {}: {}", path.display(), e))
+    })?;
+    check_private_key_permissions(&path)?;
+
+    let mut sibling = path.clone().into_os_string();
+    sibling.push(".pub");
+    let sibling = PathBuf::from(sibling);
+    if sibling.is_file() {
+        let public_key = read_ssh_public_key_line(&sibling)?;
+        return Ok(PrivateKey { path, public_key });
+    }
+
+    let output = Command::new("ssh-keygen")
+        .arg("-y")
+        .arg("-f")
+        .arg(&path)
+        .output()
+        .map_err(|e| GmlError::from(format!("Failed to run ssh-keygen to derive the public key: {}", e)))?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let public_key = stdout.lines().next().unwrap_or("").trim();
+    if !output.status.success() || !is_public_key_line(public_key) {
+        return Err(GmlError::from(format!(
+            "Could not derive a public key from {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(PrivateKey { path, public_key: public_key.to_string() })
+}
+
+#[cfg(unix)]
+fn check_private_key_permissions(path: &Path) -> Result<(), GmlError> {
+    use std::os::unix::fs::PermissionsExt;
+    let metadata = fs::metadata(path).map_err(|e| {
+        GmlError::from(format!("Failed to read private key {}: {}", path.display(), e))
+    })?;
+    if !metadata.is_file() {
+        return Err(GmlError::from(format!("Private key {} is not a file", path.display())));
+    }
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        return Err(GmlError::from(format!(
+            "Permissions {:04o} on private key {} are too open; run `chmod 600 {}`",
+            mode,
+            path.display(),
+            path.display()
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn check_private_key_permissions(path: &Path) -> Result<(), GmlError> {
+    if !path.is_file() {
+        return Err(GmlError::from(format!("Private key {} is not a file", path.display())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn private_key_needs_tight_permissions_and_uses_sibling_public_key() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("gml-key-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let key = dir.join("id_byo");
+        fs::write(&key, "not checked until ssh uses it").unwrap();
+        fs::write(dir.join("id_byo.pub"), "ssh-ed25519 AAAAC3Nza byo@laptop\n").unwrap();
+
+        fs::set_permissions(&key, fs::Permissions::from_mode(0o644)).unwrap();
+        let err = load_private_key(key.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().contains("too open"), "{}", err);
+
+        fs::set_permissions(&key, fs::Permissions::from_mode(0o600)).unwrap();
+        let loaded = load_private_key(key.to_str().unwrap()).unwrap();
+        assert_eq!(loaded.public_key, "ssh-ed25519 AAAAC3Nza byo@laptop");
+        assert_eq!(loaded.path, fs::canonicalize(&key).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
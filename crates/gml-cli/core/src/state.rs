@@ -1,10 +1,28 @@
 use crate::NodeDetails;
 use crate::error::GmlError;
+use crate::lock::{self, FileLock};
+use crate::paths;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
 use std::fs;
-use std::path::PathBuf;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
 
-const STATE_PATH: &str = "~/.gml/state.json";
+const STATE_FILE: &str = "state.json";
+/// Used instead of [`STATE_FILE`] whenever it exists
+const COMPRESSED_STATE_FILE: &str = "state.json.gz";
+const LOCK_FILE: &str = "state.lock";
+
+/// Status of a node that is up, as recorded on creation and resume
+pub const STATUS_ACTIVE: &str = "active";
+/// Status of a node powered off with `gml node pause`
+pub const STATUS_PAUSED: &str = "paused";
+
+/// Failed automatic deletions after which a node is reported as stuck rather than just retried
+pub const DELETE_ATTEMPTS_BEFORE_ESCALATION: u32 = 5;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GmlState {
@@ -15,13 +33,102 @@ pub struct GmlState {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NodeEntry {
     pub id: String,
+    /// Human-readable label, e.g. `<repo>-<branch>` with `--label-from-git`
+    #[serde(default)]
+    pub name: Option<String>,
     pub provider_id: String,
     pub ip: String,
     pub provider: String,
     pub created_at: String,
     pub instance_type: String,
+    /// RFC3339 time (UTC) at which the daemon deletes the node
+    pub timeout: Option<String>,
+    pub user: String,
+    /// RFC3339 time before `timeout` at which the daemon runs the `soft-timeout` hook, for nodes
+    /// created with `--grace`
+    #[serde(default)]
+    pub soft_timeout: Option<String>,
+    /// Set once the daemon has run the `soft-timeout` hook, so it runs only once per deadline
+    #[serde(default)]
+    pub soft_timeout_fired: bool,
+    #[serde(default)]
+    pub region: Option<String>,
+    #[serde(default)]
+    pub zone: Option<String>,
+    /// SSH port when it isn't 22
+    #[serde(default)]
+    pub ssh_port: Option<u16>,
+    #[serde(default)]
+    pub tags: HashMap<String, String>,
+    /// PID of a `gml` process with an interactive session open on the node; the daemon won't
+    /// delete the node on timeout while that process is alive
+    #[serde(default)]
+    pub in_use_by: Option<u32>,
+    /// Reserved nodes are long-lived capacity the daemon never deletes, whatever their timeout says
+    #[serde(default)]
+    pub reserved: bool,
+    /// Hourly price in USD quoted by the provider at creation time, if it could be looked up
+    #[serde(default)]
+    pub hourly_cost: Option<f64>,
+    /// Disk size requested at creation, if not the provider default
+    #[serde(default)]
+    pub disk_gb: Option<u32>,
+    /// Powered off with `gml node pause`; the disk is kept but the node can't be reached
+    #[serde(default)]
+    pub paused: bool,
+    /// Last known state, e.g. `active` or `paused`: set on creation, by pause and resume, and
+    /// from the provider by `gml node sync`. Entries saved before it existed read as `unknown`.
+    #[serde(default = "unknown_status")]
+    pub status: String,
+    /// Consecutive failed attempts by the daemon to delete the node after it expired
+    #[serde(default)]
+    pub delete_attempts: u32,
+    /// RFC3339 time at which the daemon deletes the node, set by `gml node delete` when a
+    /// grace period is configured; cleared by `gml node undo`
+    #[serde(default)]
+    pub pending_delete_at: Option<String>,
+    /// The cluster this node was launched as a member of
+    #[serde(default)]
+    pub cluster_id: Option<String>,
+    /// Private key to log in with, for nodes launched with `--private-key-from`
+    #[serde(default)]
+    pub identity_file: Option<String>,
+    /// Named provider account the node was launched in, from `--account`; `None` for the default
+    #[serde(default)]
+    pub account: Option<String>,
+}
+
+fn unknown_status() -> String {
+    "unknown".to_string()
+}
+
+/// Outcome of [`GmlState::repair`]
+pub struct Repair {
+    /// The state that was written
+    pub state: GmlState,
+    /// What couldn't be recovered, one description per entry
+    pub dropped: Vec<String>,
+    /// Copy of the corrupt file, taken before it was rewritten
+    pub backup: PathBuf,
+}
+
+/// Everything needed to record a newly created node
+pub struct NewNode {
+    pub details: NodeDetails,
+    pub name: Option<String>,
+    pub provider: String,
+    pub region: Option<String>,
+    pub instance_type: String,
     pub timeout: Option<String>, // RFC3339 timestamp in UTC
+    pub soft_timeout: Option<String>,
     pub user: String,
+    pub tags: HashMap<String, String>,
+    pub reserved: bool,
+    pub hourly_cost: Option<f64>,
+    pub disk_gb: Option<u32>,
+    pub cluster_id: Option<String>,
+    pub identity_file: Option<String>,
+    pub account: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,6 +138,9 @@ pub struct ClusterEntry {
     pub created_at: String,
     pub node_count: usize,
     pub timeout: Option<String>,
+    /// IDs of the member nodes, which are also tracked as ordinary nodes
+    #[serde(default)]
+    pub members: Vec<String>,
 }
 
 impl Default for GmlState {
@@ -43,37 +153,75 @@ impl Default for GmlState {
 }
 
 impl GmlState {
-    /// Load state from the JSON file, creating a new state if the file doesn't exist
+    /// Load state from the JSON file, creating a new state if the file doesn't exist.
+    ///
+    /// Load once per command and read from the result; make changes with [`GmlState::update`].
     pub fn load() -> Result<Self, GmlError> {
-        let state_path = expand_path(STATE_PATH)?;
+        let state_file = StateFile::locate()?;
         
         // Create directory if it doesn't exist
-        if let Some(parent) = state_path.parent() {
+        if let Some(parent) = state_file.path.parent() {
             fs::create_dir_all(parent).map_err(|e| {
                 GmlError::from(format!("Failed to create state directory: {}", e))
             })?;
         }
 
         // Read and parse the file, or return default if it doesn't exist
-        if !state_path.exists() {
+        if !state_file.path.exists() {
             return Ok(GmlState::default());
         }
 
-        let contents = fs::read_to_string(&state_path).map_err(|e| {
+        let contents = state_file.read().map_err(|e| {
             GmlError::from(format!("Failed to read state file: {}", e))
         })?;
 
         serde_json::from_str(&contents).map_err(|e| {
-            GmlError::from(format!("Failed to parse state file: {}", e))
+            GmlError::from(format!(
+                "Failed to parse state file {}: {}\nRun `gml state repair` to salvage the entries that are still readable",
+                state_file.path.display(), e
+            ))
         })
     }
 
-    /// Save state to the JSON file
-    pub fn save(&self) -> Result<(), GmlError> {
-        let state_path = expand_path(STATE_PATH)?;
+    /// Rewrite a state file that no longer parses, keeping every node and cluster entry that can
+    /// still be read. The original is first copied next to it. Returns `None` if the file is fine.
+    pub fn repair() -> Result<Option<Repair>, GmlError> {
+        let _lock = lock_state()?;
+        let state_file = StateFile::locate()?;
+        let contents = match state_file.read_salvageable() {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(GmlError::from(format!("Failed to read state file: {}", e))),
+        };
+        if serde_json::from_str::<GmlState>(&contents).is_ok() {
+            return Ok(None);
+        }
+
+        // Keep the `.gz` suffix on a compressed copy so it can still be opened with gunzip
+        let stamp = chrono::Utc::now().format("%Y%m%dT%H%M%S");
+        let backup_name = if state_file.compressed {
+            format!("{}.corrupt-{}.gz", STATE_FILE, stamp)
+        } else {
+            format!("{}.corrupt-{}", STATE_FILE, stamp)
+        };
+        let backup = state_file.path.with_file_name(backup_name);
+        fs::copy(&state_file.path, &backup).map_err(|e| {
+            GmlError::from(format!("Failed to back up state file to {}: {}", backup.display(), e))
+        })?;
+
+        let (state, dropped) = salvage(&contents);
+        state.save()?;
+        Ok(Some(Repair { state, dropped, backup }))
+    }
+
+    /// Save state to the JSON file, compressed if the existing file is.
+    ///
+    /// Only called with the state lock held; everything else goes through [`GmlState::update`].
+    fn save(&self) -> Result<(), GmlError> {
+        let state_file = StateFile::locate()?;
         
         // Create directory if it doesn't exist
-        if let Some(parent) = state_path.parent() {
+        if let Some(parent) = state_file.path.parent() {
             fs::create_dir_all(parent).map_err(|e| {
                 GmlError::from(format!("Failed to create state directory: {}", e))
             })?;
@@ -83,143 +231,618 @@ impl GmlState {
             GmlError::from(format!("Failed to serialize state: {}", e))
         })?;
 
-        fs::write(&state_path, json).map_err(|e| {
+        state_file.write(&json).map_err(|e| {
             GmlError::from(format!("Failed to write state file: {}", e))
         })
     }
 
-    /// Add a node entry to the state
-    pub fn add_node(
-        node_details: NodeDetails,
-        provider: String,
-        instance_type: String,
-        timeout: Option<String>, // RFC3339 timestamp in UTC
-        user: String,
-    ) -> Result<(), GmlError> {
+    /// Whether the state is stored gzip-compressed as `state.json.gz`
+    pub fn is_compressed() -> Result<bool, GmlError> {
+        Ok(StateFile::locate()?.compressed)
+    }
+
+    /// Convert the state file to or from `state.json.gz`, returning whether anything changed.
+    ///
+    /// The contents are carried over as they are, so a file that needs `gml state repair`
+    /// can still be converted. The new file is in place before the old one is removed.
+    pub fn set_compressed(compress: bool) -> Result<bool, GmlError> {
+        // Usually there is nothing to convert, which needs no lock to tell
+        if StateFile::locate()?.compressed == compress {
+            return Ok(false);
+        }
+        let _lock = lock_state()?;
+        let current = StateFile::locate()?;
+        if current.compressed == compress {
+            return Ok(false);
+        }
+
+        let target = StateFile {
+            path: current.path.with_file_name(if compress { COMPRESSED_STATE_FILE } else { STATE_FILE }),
+            compressed: compress,
+        };
+        let contents = match current.read() {
+            Ok(contents) => contents,
+            // Nothing saved yet; an empty compressed file isn't valid gzip, so start from default
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                serde_json::to_string_pretty(&GmlState::default()).map_err(|e| {
+                    GmlError::from(format!("Failed to serialize state: {}", e))
+                })?
+            }
+            Err(e) => return Err(GmlError::from(format!("Failed to read state file: {}", e))),
+        };
+        target.write(&contents).map_err(|e| {
+            GmlError::from(format!("Failed to write state file: {}", e))
+        })?;
+        match fs::remove_file(&current.path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+            Err(e) => Err(GmlError::from(format!(
+                "Failed to remove {} after converting it: {}",
+                current.path.display(),
+                e
+            ))),
+        }
+    }
+
+    /// Load, modify and save the state as one step while holding the state lock, so the whole
+    /// change is made against a single read of the file. Nothing is saved if `f` fails.
+    pub fn update<T>(f: impl FnOnce(&mut GmlState) -> Result<T, GmlError>) -> Result<T, GmlError> {
+        let _lock = lock_state()?;
         let mut state = Self::load()?;
-        
+        let result = f(&mut state)?;
+        state.save()?;
+        Ok(result)
+    }
+
+    /// Add a node entry, returning the generated node ID. Call it inside [`GmlState::update`] so
+    /// the change is saved under the state lock.
+    pub fn add_node(&mut self, node: NewNode) -> Result<String, GmlError> {
         // Generate a unique ID for the state
         let unique_id = uuid::Uuid::new_v4().to_string();
         
         let entry = NodeEntry {
             id: unique_id,
-            provider_id: node_details.id.clone(),
-            ip: node_details.ip,
-            provider,
+            name: node.name,
+            provider_id: node.details.id,
+            ip: node.details.ip,
+            provider: node.provider,
             created_at: chrono::Utc::now().to_rfc3339(),
-            instance_type,
-            timeout,
-            user,
+            instance_type: node.instance_type,
+            timeout: node.timeout,
+            soft_timeout: node.soft_timeout,
+            soft_timeout_fired: false,
+            user: node.user,
+            region: node.region,
+            zone: node.details.zone,
+            ssh_port: node.details.ssh_port,
+            tags: node.tags,
+            in_use_by: None,
+            reserved: node.reserved,
+            hourly_cost: node.hourly_cost,
+            disk_gb: node.disk_gb,
+            paused: false,
+            status: STATUS_ACTIVE.to_string(),
+            delete_attempts: 0,
+            pending_delete_at: None,
+            cluster_id: node.cluster_id,
+            identity_file: node.identity_file,
+            account: node.account,
         };
 
         // Check if node already exists (by provider_id to avoid duplicates from same provider)
-        if state.nodes.iter().any(|n| n.provider_id == entry.provider_id && n.provider == entry.provider) {
+        if self.nodes.iter().any(|n| n.provider_id == entry.provider_id && n.provider == entry.provider) {
             return Err(GmlError::from(format!("Node with provider_id '{}' from provider '{}' already exists", entry.provider_id, entry.provider)));
         }
 
-        state.nodes.push(entry);
-        state.save()
+        let id = entry.id.clone();
+        self.nodes.push(entry);
+        Ok(id)
     }
 
-    /// Remove a node entry from the state
-    pub fn remove_node(node_id: &str) -> Result<(), GmlError> {
-        let mut state = Self::load()?;
-        let initial_len = state.nodes.len();
-        state.nodes.retain(|n| n.id != node_id);
+    /// Remove a node entry. Like [`GmlState::add_node`], call it inside [`GmlState::update`].
+    pub fn remove_node(&mut self, node_id: &str) -> Result<(), GmlError> {
+        let initial_len = self.nodes.len();
+        self.nodes.retain(|n| n.id != node_id);
         
-        if state.nodes.len() == initial_len {
+        if self.nodes.len() == initial_len {
             return Err(GmlError::from(format!("Node with id '{}' not found", node_id)));
         }
-
-        state.save()
+        Ok(())
     }
 
     /// Get a node entry by ID
-    pub fn get_node(node_id: &str) -> Result<Option<NodeEntry>, GmlError> {
-        let state = Self::load()?;
-        Ok(state.nodes.into_iter().find(|n| n.id == node_id))
+    pub fn node(&self, node_id: &str) -> Option<&NodeEntry> {
+        self.nodes.iter().find(|n| n.id == node_id)
+    }
+
+    /// Get a node entry by gml ID, by the provider's instance ID as shown in its console, or by
+    /// name. An instance ID tracked on more than one provider is an error rather than a guess.
+    pub fn find_node(&self, id: &str) -> Result<&NodeEntry, GmlError> {
+        if let Some(node) = self.node(id) {
+            return Ok(node);
+        }
+        let matches: Vec<&NodeEntry> = self.nodes.iter().filter(|n| n.provider_id == id).collect();
+        match matches.as_slice() {
+            [node] => Ok(node),
+            [] => self.node_named(id)?
+                .ok_or_else(|| GmlError::from(format!("Node with ID or name '{}' not found", id))),
+            _ => {
+                let ids: Vec<String> = matches.iter().map(|n| format!("{} ({})", n.id, n.provider)).collect();
+                Err(GmlError::from(format!(
+                    "Provider ID '{}' matches several nodes: {}; use the gml node ID instead",
+                    id, ids.join(", ")
+                )))
+            }
+        }
     }
 
-    /// List all nodes
-    pub fn list_nodes() -> Result<Vec<NodeEntry>, GmlError> {
-        let state = Self::load()?;
-        Ok(state.nodes)
+    /// Get a node entry by name. A name shared by several nodes, as `--label-from-git` can give,
+    /// is an error rather than a guess.
+    pub fn node_named(&self, name: &str) -> Result<Option<&NodeEntry>, GmlError> {
+        let matches: Vec<&NodeEntry> = self.nodes.iter().filter(|n| n.name.as_deref() == Some(name)).collect();
+        match matches.as_slice() {
+            [] => Ok(None),
+            [node] => Ok(Some(node)),
+            _ => {
+                let ids: Vec<&str> = matches.iter().map(|n| n.id.as_str()).collect();
+                Err(GmlError::from(format!(
+                    "Several nodes are named '{}': {}; use the node ID instead",
+                    name, ids.join(", ")
+                )))
+            }
+        }
     }
 
-    /// Update the timeout for a node
-    pub fn update_node_timeout(node_id: &str, timeout: Option<String>) -> Result<(), GmlError> {
-        let mut state = Self::load()?;
-        
-        // Find the node and update its timeout
-        let node = state.nodes.iter_mut()
+    /// Name a node, or clear its name with `None`. Like [`GmlState::add_node`], call it inside
+    /// [`GmlState::update`]. The name must not already belong to another node, so it can be used
+    /// in place of the ID.
+    pub fn set_node_name(&mut self, node_id: &str, name: Option<String>) -> Result<(), GmlError> {
+        if let Some(name) = &name
+            && let Some(other) = self.nodes.iter().find(|n| n.id != node_id && (n.name.as_ref() == Some(name) || &n.id == name))
+        {
+            return Err(GmlError::from(format!("Node {} is already called '{}'", other.id, name)));
+        }
+        self.node_mut(node_id)?.name = name;
+        Ok(())
+    }
+
+    /// The member nodes of a cluster
+    pub fn list_cluster_nodes(&self, cluster_id: &str) -> Vec<&NodeEntry> {
+        self.nodes.iter().filter(|n| n.cluster_id.as_deref() == Some(cluster_id)).collect()
+    }
+
+    /// Get a node entry by ID for modification, or an error if there is no such node
+    pub fn node_mut(&mut self, node_id: &str) -> Result<&mut NodeEntry, GmlError> {
+        self.nodes.iter_mut()
             .find(|n| n.id == node_id)
-            .ok_or_else(|| GmlError::from(format!("Node with id '{}' not found", node_id)))?;
-        
-        node.timeout = timeout;
-        state.save()
+            .ok_or_else(|| GmlError::from(format!("Node with id '{}' not found", node_id)))
+    }
+
+    /// Mark a node as in use by this process until the returned guard is dropped
+    pub fn mark_node_in_use(node_id: &str) -> Result<InUseGuard, GmlError> {
+        Self::set_node_in_use(node_id, Some(std::process::id()))?;
+        Ok(InUseGuard {
+            node_id: node_id.to_string(),
+        })
     }
 
-    /// Add a cluster entry to the state
+    fn set_node_in_use(node_id: &str, in_use_by: Option<u32>) -> Result<(), GmlError> {
+        Self::update(|state| {
+            state.node_mut(node_id)?.in_use_by = in_use_by;
+            Ok(())
+        })
+    }
+
+    /// Add a cluster entry
     pub fn add_cluster(
+        &mut self,
         cluster_id: String,
         provider: String,
         node_count: usize,
         timeout: Option<String>,
+        members: Vec<String>,
     ) -> Result<(), GmlError> {
-        let mut state = Self::load()?;
-        
         let entry = ClusterEntry {
             id: cluster_id.clone(),
             provider,
             node_count,
             timeout,
+            members,
             created_at: chrono::Utc::now().to_rfc3339(),
         };
 
         // Check if cluster already exists
-        if state.clusters.iter().any(|c| c.id == entry.id) {
+        if self.clusters.iter().any(|c| c.id == entry.id) {
             return Err(GmlError::from(format!("Cluster with id '{}' already exists", entry.id)));
         }
 
-        state.clusters.push(entry);
-        state.save()
+        self.clusters.push(entry);
+        Ok(())
     }
 
-    /// Remove a cluster entry from the state
-    pub fn remove_cluster(cluster_id: &str) -> Result<(), GmlError> {
-        let mut state = Self::load()?;
-        let initial_len = state.clusters.len();
-        state.clusters.retain(|c| c.id != cluster_id);
+    /// Remove a cluster entry
+    pub fn remove_cluster(&mut self, cluster_id: &str) -> Result<(), GmlError> {
+        let initial_len = self.clusters.len();
+        self.clusters.retain(|c| c.id != cluster_id);
         
-        if state.clusters.len() == initial_len {
+        if self.clusters.len() == initial_len {
             return Err(GmlError::from(format!("Cluster with id '{}' not found", cluster_id)));
         }
-
-        state.save()
+        Ok(())
     }
 
     /// Get a cluster entry by ID
-    pub fn get_cluster(cluster_id: &str) -> Result<Option<ClusterEntry>, GmlError> {
-        let state = Self::load()?;
-        Ok(state.clusters.into_iter().find(|c| c.id == cluster_id))
+    pub fn cluster(&self, cluster_id: &str) -> Option<&ClusterEntry> {
+        self.clusters.iter().find(|c| c.id == cluster_id)
+    }
+}
+
+impl NodeEntry {
+    /// Returns the PID of the live process using this node, ignoring markers left by dead processes
+    pub fn active_session(&self) -> Option<u32> {
+        self.in_use_by.filter(|pid| lock::is_process_alive(*pid))
     }
 
-    /// List all clusters
-    pub fn list_clusters() -> Result<Vec<ClusterEntry>, GmlError> {
-        let state = Self::load()?;
-        Ok(state.clusters)
+    /// True once the daemon has failed to delete the node often enough that it needs attention
+    pub fn delete_is_stuck(&self) -> bool {
+        self.delete_attempts >= DELETE_ATTEMPTS_BEFORE_ESCALATION
+    }
+
+    /// Time between the soft timeout and deletion, for nodes created with `--grace`
+    pub fn grace(&self) -> Option<chrono::Duration> {
+        let soft = chrono::DateTime::parse_from_rfc3339(self.soft_timeout.as_deref()?).ok()?;
+        let hard = chrono::DateTime::parse_from_rfc3339(self.timeout.as_deref()?).ok()?;
+        Some(hard - soft)
+    }
+
+    /// Move the node's timeout to `at`, keeping its grace period: the soft timeout moves to `at`
+    /// and deletion to `at` plus the grace. `None` removes both.
+    pub fn reset_timeout(&mut self, at: Option<chrono::DateTime<chrono::Utc>>) {
+        let grace = self.grace();
+        self.soft_timeout_fired = false;
+        match (at, grace) {
+            (Some(at), Some(grace)) => {
+                self.soft_timeout = Some(at.to_rfc3339());
+                self.timeout = Some((at + grace).to_rfc3339());
+            }
+            (at, _) => {
+                self.soft_timeout = None;
+                self.timeout = at.map(|at| at.to_rfc3339());
+            }
+        }
     }
 }
 
-/// Expand a path that may contain `~` to the user's home directory
-fn expand_path(path: &str) -> Result<PathBuf, GmlError> {
-    if path.starts_with("~/") {
-        let home = dirs::home_dir().ok_or_else(|| {
-            GmlError::from("Unable to determine home directory")
+/// Clears a node's in-use marker when dropped (see [`GmlState::mark_node_in_use`])
+pub struct InUseGuard {
+    node_id: String,
+}
+
+impl Drop for InUseGuard {
+    fn drop(&mut self) {
+        // The node may have been deleted during the session, in which case there is nothing to clear
+        let _ = GmlState::set_node_in_use(&self.node_id, None);
+    }
+}
+
+/// Recover what can be read from a corrupt state file.
+///
+/// If the file is still valid JSON, each entry of `nodes` and `clusters` is parsed on its own and
+/// the unreadable ones are dropped. Otherwise (e.g. a truncated write) every complete object in the
+/// text that parses as a node or cluster entry is kept.
+fn salvage(contents: &str) -> (GmlState, Vec<String>) {
+    let mut state = GmlState::default();
+    let mut dropped = Vec::new();
+
+    match serde_json::from_str::<serde_json::Value>(contents) {
+        Ok(value) => {
+            salvage_entries(&value, "nodes", &mut state.nodes, &mut dropped);
+            salvage_entries(&value, "clusters", &mut state.clusters, &mut dropped);
+        }
+        Err(e) => {
+            dropped.push(format!("the file is not valid JSON ({}); anything not listed as recovered was lost", e));
+            for (start, _) in contents.match_indices('{') {
+                let rest = &contents[start..];
+                if let Some(Ok(node)) = serde_json::Deserializer::from_str(rest).into_iter::<NodeEntry>().next() {
+                    if !state.nodes.iter().any(|n| n.id == node.id) {
+                        state.nodes.push(node);
+                    }
+                } else if let Some(Ok(cluster)) = serde_json::Deserializer::from_str(rest).into_iter::<ClusterEntry>().next()
+                    && !state.clusters.iter().any(|c| c.id == cluster.id)
+                {
+                    state.clusters.push(cluster);
+                }
+            }
+        }
+    }
+
+    // A cluster can't point at members that were lost
+    for cluster in &mut state.clusters {
+        let nodes = &state.nodes;
+        cluster.members.retain(|member| nodes.iter().any(|n| &n.id == member));
+    }
+    (state, dropped)
+}
+
+/// Parse each element of the `key` array on its own, keeping the ones that are valid entries
+fn salvage_entries<T: serde::de::DeserializeOwned>(
+    value: &serde_json::Value,
+    key: &str,
+    entries: &mut Vec<T>,
+    dropped: &mut Vec<String>,
+) {
+    let Some(items) = value.get(key).and_then(|items| items.as_array()) else {
+        dropped.push(format!("`{}` is missing or not a list", key));
+        return;
+    };
+    for (index, item) in items.iter().enumerate() {
+        match serde_json::from_value::<T>(item.clone()) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                let id = item.get("id").and_then(|id| id.as_str()).unwrap_or("without an id");
+                dropped.push(format!("{} entry {} ({}): {}", key, index, id, e));
+            }
+        }
+    }
+}
+
+/// The state file in use, and whether it is gzip-compressed
+struct StateFile {
+    path: PathBuf,
+    compressed: bool,
+}
+
+impl StateFile {
+    /// `state.json.gz` if it exists, otherwise `state.json`
+    fn locate() -> Result<StateFile, GmlError> {
+        Ok(Self::in_dir(&paths::gml_dir()?))
+    }
+
+    fn in_dir(dir: &Path) -> StateFile {
+        let compressed = dir.join(COMPRESSED_STATE_FILE);
+        if compressed.exists() {
+            StateFile { path: compressed, compressed: true }
+        } else {
+            StateFile { path: dir.join(STATE_FILE), compressed: false }
+        }
+    }
+
+    fn read(&self) -> io::Result<String> {
+        if !self.compressed {
+            return fs::read_to_string(&self.path);
+        }
+        let mut contents = String::new();
+        GzDecoder::new(fs::File::open(&self.path)?).read_to_string(&mut contents)?;
+        Ok(contents)
+    }
+
+    /// Like [`StateFile::read`], but a compressed file cut short still yields the part that
+    /// decompresses, for [`salvage`] to work on
+    fn read_salvageable(&self) -> io::Result<String> {
+        if !self.compressed {
+            return fs::read_to_string(&self.path);
+        }
+        let mut decoder = GzDecoder::new(fs::File::open(&self.path)?);
+        let mut contents = Vec::new();
+        let mut buf = [0u8; 8192];
+        loop {
+            match decoder.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => contents.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(_) if !contents.is_empty() => break,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(String::from_utf8_lossy(&contents).into_owned())
+    }
+
+    /// Write to a temporary file next to the state file and rename it into place, so a process
+    /// killed mid-write leaves the previous state intact. The temporary file is named after this
+    /// process, so writers can't clobber each other's half-written file.
+    fn write(&self, contents: &str) -> io::Result<()> {
+        let file_name = self.path.file_name().and_then(|n| n.to_str()).unwrap_or(STATE_FILE);
+        let tmp_path = self.path.with_file_name(format!("{}.tmp-{}", file_name, std::process::id()));
+        let result = self.write_file(&tmp_path, contents)
+            .and_then(|()| fs::rename(&tmp_path, &self.path));
+        if result.is_err() {
+            let _ = fs::remove_file(&tmp_path);
+            return result;
+        }
+        sync_dir(&self.path);
+        Ok(())
+    }
+
+    fn write_file(&self, path: &Path, contents: &str) -> io::Result<()> {
+        let mut file = fs::File::create(path)?;
+        if self.compressed {
+            let mut encoder = GzEncoder::new(&mut file, Compression::default());
+            encoder.write_all(contents.as_bytes())?;
+            encoder.finish()?;
+        } else {
+            file.write_all(contents.as_bytes())?;
+        }
+        file.sync_all()
+    }
+}
+
+/// Flush the rename of `path` to disk, so it survives a power loss. Best effort: not every
+/// platform lets a directory be opened and synced.
+fn sync_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent() {
+        let _ = fs::File::open(dir).and_then(|dir| dir.sync_all());
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// Take the state lock for a read-modify-write cycle; released when the returned guard drops
+fn lock_state() -> Result<FileLock, GmlError> {
+    let lock_path = paths::gml_dir()?.join(LOCK_FILE);
+    if let Some(parent) = lock_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| {
+            GmlError::from(format!("Failed to create state directory: {}", e))
         })?;
-        Ok(home.join(&path[2..]))
-    } else {
-        Ok(PathBuf::from(path))
     }
+    FileLock::acquire(&lock_path, lock::lock_timeout())
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NODE: &str = r#"{"id": "n1", "provider_id": "i-1", "ip": "198.51.100.1", "provider": "lambda",
+        "created_at": "2026-01-01T00:00:00Z", "instance_type": "gpu_1x_a10", "timeout": null, "user": "ubuntu"}"#;
+
+    fn state_with_legacy_node() -> GmlState {
+        serde_json::from_str(&format!(r#"{{"nodes": [{}], "clusters": []}}"#, NODE)).unwrap()
+    }
+
+    fn new_node(provider_id: &str, cluster_id: Option<&str>) -> NewNode {
+        NewNode {
+            details: NodeDetails { ip: "198.51.100.2".to_string(), id: provider_id.to_string(), zone: None, ssh_port: None, status: None },
+            name: None,
+            provider: "lambda".to_string(),
+            region: None,
+            instance_type: "gpu_1x_a10".to_string(),
+            timeout: None,
+            soft_timeout: None,
+            user: "ubuntu".to_string(),
+            tags: HashMap::new(),
+            reserved: false,
+            hourly_cost: None,
+            disk_gb: None,
+            cluster_id: cluster_id.map(str::to_string),
+            identity_file: None,
+            account: None,
+        }
+    }
+
+    #[test]
+    fn nodes_without_cluster_id_load_and_members_are_listed() {
+        let mut state = state_with_legacy_node();
+        assert_eq!(state.nodes[0].cluster_id, None);
+
+        let first = state.add_node(new_node("i-2", Some("c1"))).unwrap();
+        state.add_node(new_node("i-3", Some("c2"))).unwrap();
+        state.add_node(new_node("i-4", None)).unwrap();
+
+        let ids: Vec<&str> = state.list_cluster_nodes("c1").iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec![first.as_str()]);
+        assert!(state.list_cluster_nodes("c3").is_empty());
+    }
+
+    #[test]
+    fn find_node_accepts_gml_and_provider_ids() {
+        let mut state = state_with_legacy_node();
+        let id = state.add_node(new_node("i-2", None)).unwrap();
+
+        assert_eq!(state.find_node("i-2").unwrap().id, id);
+        assert_eq!(state.find_node(&id).unwrap().provider_id, "i-2");
+        assert_eq!(state.find_node("i-1").unwrap().id, "n1");
+        assert!(state.find_node("i-9").is_err());
+    }
+
+    #[test]
+    fn status_defaults_to_unknown_only_for_old_entries() {
+        let mut state = state_with_legacy_node();
+        let id = state.add_node(new_node("i-2", None)).unwrap();
+
+        // Entries written before the status field existed don't claim to be active
+        assert_eq!(state.nodes[0].status, "unknown");
+        assert_eq!(state.node(&id).unwrap().status, STATUS_ACTIVE);
+    }
+
+    #[test]
+    fn node_names_are_unique_and_resolve_to_the_node() {
+        let mut state = state_with_legacy_node();
+        let id = state.add_node(new_node("i-2", None)).unwrap();
+
+        state.set_node_name(&id, Some("training-box".to_string())).unwrap();
+        assert_eq!(state.find_node("training-box").unwrap().id, id);
+        assert!(state.set_node_name("n1", Some("training-box".to_string())).is_err());
+        state.set_node_name(&id, None).unwrap();
+        assert!(state.node_named("training-box").unwrap().is_none());
+    }
+
+    #[test]
+    fn compressed_state_round_trips_and_truncation_is_salvageable() {
+        let dir = std::env::temp_dir().join(format!("gml-state-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        assert!(!StateFile::in_dir(&dir).compressed);
+
+        let json = format!(r#"{{"nodes": [{}], "clusters": []}}"#, NODE);
+        let file = StateFile { path: dir.join(COMPRESSED_STATE_FILE), compressed: true };
+        file.write(&json).unwrap();
+        let located = StateFile::in_dir(&dir);
+        assert!(located.compressed);
+        assert_eq!(located.read().unwrap(), json);
+
+        let bytes = fs::read(&located.path).unwrap();
+        fs::write(&located.path, &bytes[..bytes.len() - 8]).unwrap();
+        assert!(located.read().is_err());
+        let (state, _) = salvage(&located.read_salvageable().unwrap());
+        assert_eq!(state.nodes.len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn write_replaces_the_file_without_leaving_temporary_files() {
+        let dir = std::env::temp_dir().join(format!("gml-state-test-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = StateFile::in_dir(&dir);
+        file.write("old").unwrap();
+        file.write("new").unwrap();
+
+        assert_eq!(file.read().unwrap(), "new");
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, vec![std::ffi::OsString::from(STATE_FILE)]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reset_timeout_keeps_the_grace_period() {
+        let mut node: NodeEntry = serde_json::from_str(NODE).unwrap();
+        let at = chrono::DateTime::parse_from_rfc3339("2026-01-01T04:00:00Z").unwrap().to_utc();
+        node.reset_timeout(Some(at));
+        assert_eq!(node.timeout.as_deref(), Some("2026-01-01T04:00:00+00:00"));
+        assert_eq!(node.soft_timeout, None);
+
+        node.soft_timeout = Some("2026-01-01T03:30:00+00:00".to_string());
+        node.soft_timeout_fired = true;
+        node.reset_timeout(Some(at + chrono::Duration::hours(1)));
+        assert_eq!(node.soft_timeout.as_deref(), Some("2026-01-01T05:00:00+00:00"));
+        assert_eq!(node.timeout.as_deref(), Some("2026-01-01T05:30:00+00:00"));
+        assert!(!node.soft_timeout_fired);
+
+        node.reset_timeout(None);
+        assert_eq!((node.timeout, node.soft_timeout), (None, None));
+    }
+
+    #[test]
+    fn salvage_drops_unreadable_entries_from_valid_json() {
+        let contents = format!(
+            r#"{{"nodes": [{}, {{"id": "n2", "ip": 7}}], "clusters": [{{"id": "c1", "provider": "lambda",
+                "created_at": "2026-01-01T00:00:00Z", "node_count": 2, "timeout": null, "members": ["n1", "n2"]}}]}}"#,
+            NODE
+        );
+        let (state, dropped) = salvage(&contents);
+        assert_eq!(state.nodes.len(), 1);
+        assert_eq!(dropped.len(), 1);
+        assert!(dropped[0].contains("n2"));
+        assert_eq!(state.clusters[0].members, vec!["n1".to_string()]);
+    }
+
+    #[test]
+    fn salvage_recovers_complete_entries_from_truncated_file() {
+        let contents = format!(r#"{{"nodes": [{}, {{"id": "n2", "provider_id": "i-"#, NODE);
+        let (state, dropped) = salvage(&contents);
+        assert_eq!(state.nodes.len(), 1);
+        assert_eq!(state.nodes[0].id, "n1");
+        assert!(dropped[0].contains("not valid JSON"));
+    }
+}
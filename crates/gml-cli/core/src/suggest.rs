@@ -0,0 +1,45 @@
+//! "Did you mean" suggestions for names that must match something the provider knows about.
+
+/// Candidates within a small edit distance of `target`, closest first
+pub fn closest_matches<'a>(target: &str, candidates: &[&'a str]) -> Vec<&'a str> {
+    let target = target.to_lowercase();
+    // Allow roughly one typo per four characters, and always at least two
+    let max_distance = (target.chars().count() / 4).max(2);
+
+    let mut matches: Vec<(usize, &str)> = candidates
+        .iter()
+        .map(|candidate| (edit_distance(&target, &candidate.to_lowercase()), *candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    matches.sort();
+    matches.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// Levenshtein distance between two strings, counted in chars
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggests_near_misses_closest_first() {
+        let keys = ["laptop", "workstation", "laptop-2", "ci"];
+        assert_eq!(closest_matches("labtop", &keys), vec!["laptop"]);
+        assert_eq!(closest_matches("laptop-3", &keys), vec!["laptop-2", "laptop"]);
+        assert_eq!(closest_matches("Workstaton", &keys), vec!["workstation"]);
+        assert!(closest_matches("desktop-at-home", &keys).is_empty());
+    }
+}
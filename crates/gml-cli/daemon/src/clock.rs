@@ -0,0 +1,133 @@
+//! Guards expiry checks against wall-clock jumps.
+//!
+//! Timeouts are stored as wall-clock timestamps, so a clock that leaps forward (an NTP
+//! correction, or a laptop resuming from suspend) would make nodes look expired early. The
+//! daemon compares wall-clock progress with the monotonic clock on every tick. A forward jump
+//! it sees is subtracted from "now" for [`JUMP_HOLD`] of monotonic time, which leaves a wrongly
+//! set clock time to be corrected before anything is deleted. After that the wall clock counts
+//! again: time spent suspended is real elapsed time for billing, so nodes that expired during a
+//! suspend are deleted shortly after resume. A backward jump only delays expiry, so it is logged
+//! but not compensated.
+
+use chrono::{DateTime, Duration, Utc};
+use std::time::Instant;
+
+/// Wall-clock and monotonic elapsed time may differ by this much before it counts as a jump
+pub const JUMP_THRESHOLD: Duration = Duration::minutes(2);
+
+/// How long a forward jump is kept out of expiry checks
+pub const JUMP_HOLD: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+pub struct ClockGuard {
+    last_wall: DateTime<Utc>,
+    last_mono: Instant,
+    /// Forward jumps held back from "now", and the monotonic time the hold ends
+    hold: Option<(Duration, Instant)>,
+}
+
+impl ClockGuard {
+    pub fn new() -> Self {
+        Self::starting_at(Utc::now(), Instant::now())
+    }
+
+    fn starting_at(wall: DateTime<Utc>, mono: Instant) -> Self {
+        ClockGuard {
+            last_wall: wall,
+            last_mono: mono,
+            hold: None,
+        }
+    }
+
+    /// Record a tick. Returns the size of the jump (positive when the wall clock moved forward)
+    /// if the wall clock and the monotonic clock disagree by more than [`JUMP_THRESHOLD`].
+    pub fn observe(&mut self, wall: DateTime<Utc>, mono: Instant) -> Option<Duration> {
+        let wall_elapsed = wall - self.last_wall;
+        let mono_elapsed = Duration::from_std(mono.saturating_duration_since(self.last_mono))
+            .unwrap_or(Duration::MAX);
+        self.last_wall = wall;
+        self.last_mono = mono;
+
+        let jump = wall_elapsed - mono_elapsed;
+        if jump.abs() <= JUMP_THRESHOLD {
+            return None;
+        }
+        if jump > Duration::zero() {
+            // A jump during a hold adds to it, and restarts it
+            let held = self.hold.filter(|(_, until)| mono < *until).map_or(Duration::zero(), |(skew, _)| skew);
+            self.hold = Some((held + jump, mono + JUMP_HOLD));
+        }
+        Some(jump)
+    }
+
+    /// The time to compare timeouts against: `wall`, minus any forward jump still held back at
+    /// monotonic time `mono`
+    pub fn effective_now(&self, wall: DateTime<Utc>, mono: Instant) -> DateTime<Utc> {
+        match self.hold {
+            Some((skew, until)) if mono < until => wall - skew,
+            _ => wall,
+        }
+    }
+}
+
+/// True once `now` has reached `timeout`
+pub fn is_expired(timeout: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    now >= timeout
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(secs: i64) -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap()
+    }
+
+    #[test]
+    fn expiry_boundary() {
+        let timeout = at(3600);
+        assert!(!is_expired(timeout, at(3599)));
+        assert!(is_expired(timeout, at(3600)));
+        assert!(is_expired(timeout, at(3601)));
+    }
+
+    #[test]
+    fn steady_clock_is_not_a_jump() {
+        let mono = Instant::now();
+        let mut guard = ClockGuard::starting_at(at(0), mono);
+        // A minute of normal drift either way stays under the threshold
+        assert_eq!(guard.observe(at(120), mono + std::time::Duration::from_secs(60)), None);
+        assert_eq!(guard.effective_now(at(120), mono + std::time::Duration::from_secs(60)), at(120));
+    }
+
+    #[test]
+    fn forward_jump_delays_expiry_only_for_the_hold() {
+        let mono = Instant::now();
+        let mut guard = ClockGuard::starting_at(at(0), mono);
+
+        // Resume from an 8 hour suspend: the wall clock moved 8h, the monotonic clock 60s
+        let resumed = mono + std::time::Duration::from_secs(60);
+        let jump = guard.observe(at(8 * 3600 + 60), resumed);
+        assert_eq!(jump, Some(Duration::hours(8)));
+        assert_eq!(guard.effective_now(at(8 * 3600 + 60), resumed), at(60));
+
+        // A node due an hour after the daemon started is not expired right after resume
+        assert!(!is_expired(at(3600), guard.effective_now(at(8 * 3600 + 60), resumed)));
+
+        // Once the hold is over the suspend counts, so the node is deleted rather than billing on
+        let held = JUMP_HOLD.as_secs() as i64;
+        let later = resumed + JUMP_HOLD;
+        assert_eq!(guard.observe(at(8 * 3600 + 60 + held), later), None);
+        assert_eq!(guard.effective_now(at(8 * 3600 + 60 + held), later), at(8 * 3600 + 60 + held));
+        assert!(is_expired(at(3600), guard.effective_now(at(8 * 3600 + 60 + held), later)));
+    }
+
+    #[test]
+    fn backward_jump_is_reported_but_not_compensated() {
+        let mono = Instant::now();
+        let mut guard = ClockGuard::starting_at(at(3600), mono);
+        let jump = guard.observe(at(60), mono + std::time::Duration::from_secs(60));
+        assert_eq!(jump, Some(Duration::seconds(-3600)));
+        assert_eq!(guard.effective_now(at(60), mono + std::time::Duration::from_secs(60)), at(60));
+    }
+}
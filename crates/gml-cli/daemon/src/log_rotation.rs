@@ -0,0 +1,131 @@
+//! Size-based rotation of `~/.gml/gmld.log`.
+//!
+//! Once the log grows past the size limit, it is renamed to `gmld.log.1`, older backups move up
+//! one (`.1` to `.2` and so on, dropping the oldest), and logging continues in a fresh file. The
+//! limit and the number of backups kept come from `GML_DAEMON_LOG_MAX_MB` and
+//! `GML_DAEMON_LOG_BACKUPS`, since the daemon doesn't read config.toml.
+
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Log size that triggers a rotation, unless overridden
+pub const DEFAULT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Rotated logs kept, unless overridden
+pub const DEFAULT_BACKUPS: u32 = 3;
+
+/// Overrides [`DEFAULT_MAX_BYTES`], in whole megabytes
+const MAX_MB_ENV: &str = "GML_DAEMON_LOG_MAX_MB";
+
+/// Overrides [`DEFAULT_BACKUPS`]; 0 keeps no backups, so the log just starts over
+const BACKUPS_ENV: &str = "GML_DAEMON_LOG_BACKUPS";
+
+pub struct LogRotation {
+    path: PathBuf,
+    max_bytes: u64,
+    backups: u32,
+}
+
+impl LogRotation {
+    /// Rotation of the log at `path` with the limits from the environment
+    pub fn from_env(path: PathBuf) -> Self {
+        let parsed = |name: &str| std::env::var(name).ok().and_then(|value| value.trim().parse::<u64>().ok());
+        LogRotation {
+            path,
+            max_bytes: parsed(MAX_MB_ENV)
+                .filter(|mb| *mb > 0)
+                .map(|mb| mb * 1024 * 1024)
+                .unwrap_or(DEFAULT_MAX_BYTES),
+            backups: parsed(BACKUPS_ENV)
+                .and_then(|backups| u32::try_from(backups).ok())
+                .unwrap_or(DEFAULT_BACKUPS),
+        }
+    }
+
+    /// Open the log for appending, creating it if needed
+    pub fn open(&self) -> io::Result<File> {
+        OpenOptions::new().create(true).append(true).open(&self.path)
+    }
+
+    /// Rotate the log if it has grown past the limit, returning the fresh file to log to
+    pub fn rotate_if_needed(&self) -> io::Result<Option<File>> {
+        let len = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => 0,
+            Err(e) => return Err(e),
+        };
+        if len <= self.max_bytes {
+            return Ok(None);
+        }
+
+        if self.backups == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for index in (1..self.backups).rev() {
+                let from = backup_path(&self.path, index);
+                if from.exists() {
+                    fs::rename(&from, backup_path(&self.path, index + 1))?;
+                }
+            }
+            fs::rename(&self.path, backup_path(&self.path, 1))?;
+        }
+        self.open().map(Some)
+    }
+}
+
+/// `gmld.log.<index>`
+fn backup_path(path: &Path, index: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}", index));
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn rotation(test: &str, max_bytes: u64, backups: u32) -> LogRotation {
+        let dir = std::env::temp_dir().join(format!("gmld-rotation-{}-{}", std::process::id(), test));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        LogRotation { path: dir.join("gmld.log"), max_bytes, backups }
+    }
+
+    fn write(file: &mut File, text: &str) {
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn small_log_is_left_alone() {
+        let rotation = rotation("small", 100, 2);
+        write(&mut rotation.open().unwrap(), "short line\n");
+        assert!(rotation.rotate_if_needed().unwrap().is_none());
+        assert!(!backup_path(&rotation.path, 1).exists());
+    }
+
+    #[test]
+    fn rotation_shifts_backups_and_drops_the_oldest() {
+        let rotation = rotation("shift", 4, 2);
+        for generation in ["first\n", "second\n", "third\n"] {
+            let mut file = rotation.open().unwrap();
+            write(&mut file, generation);
+            rotation.rotate_if_needed().unwrap().expect("log is over the limit");
+        }
+        assert_eq!(fs::read_to_string(&rotation.path).unwrap(), "");
+        assert_eq!(fs::read_to_string(backup_path(&rotation.path, 1)).unwrap(), "third\n");
+        assert_eq!(fs::read_to_string(backup_path(&rotation.path, 2)).unwrap(), "second\n");
+        assert!(!backup_path(&rotation.path, 3).exists());
+    }
+
+    #[test]
+    fn no_backups_starts_the_log_over() {
+        let rotation = rotation("none", 4, 0);
+        write(&mut rotation.open().unwrap(), "too long\n");
+        let mut fresh = rotation.rotate_if_needed().unwrap().expect("log is over the limit");
+        write(&mut fresh, "new\n");
+        assert_eq!(fs::read_to_string(&rotation.path).unwrap(), "new\n");
+        assert!(!backup_path(&rotation.path, 1).exists());
+    }
+}
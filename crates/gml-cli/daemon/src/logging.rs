@@ -0,0 +1,159 @@
+//! Lines written to `~/.gml/gmld.log`, either as plain text or as one JSON object per line.
+//!
+//! The format is chosen once at startup with `--log-format plain|json`, or
+//! `GML_DAEMON_LOG_FORMAT` for a daemon started by `gml`. Plain is the default: the time in
+//! RFC 3339 UTC, then the message, with `ERROR: ` in front of errors.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::json;
+use std::io::Write;
+
+/// Read when `--log-format` isn't given, so a daemon started by `gml` can log JSON too
+const LOG_FORMAT_ENV: &str = "GML_DAEMON_LOG_FORMAT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Plain,
+    /// `{"timestamp": ..., "level": ..., "message": ...}` per line, for log aggregators
+    Json,
+}
+
+impl LogFormat {
+    fn parse(value: &str) -> Result<LogFormat, String> {
+        match value {
+            "plain" => Ok(LogFormat::Plain),
+            "json" => Ok(LogFormat::Json),
+            other => Err(format!("Unknown log format '{}'; expected plain or json", other)),
+        }
+    }
+}
+
+/// The log format from the command line (`--log-format json` or `--log-format=json`), falling back
+/// to `GML_DAEMON_LOG_FORMAT` and then to plain
+pub fn log_format(mut args: impl Iterator<Item = String>) -> Result<LogFormat, String> {
+    let mut format = None;
+    while let Some(arg) = args.next() {
+        let value = match arg.strip_prefix("--log-format") {
+            Some("") => args.next().ok_or("--log-format needs a value: plain or json")?,
+            Some(rest) if rest.starts_with('=') => rest[1..].to_string(),
+            _ => return Err(format!("Unexpected argument '{}'; usage: gmld [--log-format plain|json]", arg)),
+        };
+        format = Some(LogFormat::parse(&value)?);
+    }
+    match format {
+        Some(format) => Ok(format),
+        None => match std::env::var(LOG_FORMAT_ENV) {
+            Ok(value) if !value.trim().is_empty() => LogFormat::parse(value.trim()),
+            _ => Ok(LogFormat::Plain),
+        },
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Level {
+    Info,
+    Error,
+}
+
+impl Level {
+    fn as_str(self) -> &'static str {
+        match self {
+            Level::Info => "info",
+            Level::Error => "error",
+        }
+    }
+}
+
+/// Where the daemon logs to, and in which format
+pub struct Logger<W> {
+    out: W,
+    format: LogFormat,
+}
+
+impl<W: Write> Logger<W> {
+    pub fn new(out: W, format: LogFormat) -> Self {
+        Logger { out, format }
+    }
+
+    /// Log to `out` from now on, e.g. a fresh file after rotating the log
+    pub fn set_output(&mut self, out: W) {
+        self.out = out;
+    }
+
+    fn write(&mut self, level: Level, message: &str) {
+        let line = format_line(self.format, level, message, Utc::now());
+        let _ = writeln!(self.out, "{}", line);
+        let _ = self.out.flush();
+    }
+}
+
+fn format_line(format: LogFormat, level: Level, message: &str, timestamp: DateTime<Utc>) -> String {
+    match (format, level) {
+        (LogFormat::Plain, Level::Info) => format!("{} {}", plain_timestamp(timestamp), message),
+        (LogFormat::Plain, Level::Error) => format!("{} ERROR: {}", plain_timestamp(timestamp), message),
+        (LogFormat::Json, _) => json!({
+            "timestamp": timestamp.to_rfc3339(),
+            "level": level.as_str(),
+            "message": message,
+        }).to_string(),
+    }
+}
+
+/// `2024-01-02T03:04:05Z`; whole seconds are enough to read the log by
+fn plain_timestamp(timestamp: DateTime<Utc>) -> String {
+    timestamp.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+pub fn log<W: Write>(out: &mut Logger<W>, message: &str) {
+    out.write(Level::Info, message);
+}
+
+pub fn log_error<W: Write>(out: &mut Logger<W>, message: &str) {
+    out.write(Level::Error, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>().into_iter()
+    }
+
+    #[test]
+    fn format_from_flag() {
+        assert_eq!(log_format(args(&["--log-format", "json"])).unwrap(), LogFormat::Json);
+        assert_eq!(log_format(args(&["--log-format=plain"])).unwrap(), LogFormat::Plain);
+        assert!(log_format(args(&["--log-format", "xml"])).is_err());
+        assert!(log_format(args(&["--log-format"])).is_err());
+        assert!(log_format(args(&["--verbose"])).is_err());
+    }
+
+    #[test]
+    fn plain_lines_start_with_the_time() {
+        let timestamp = DateTime::parse_from_rfc3339("2024-01-02T03:04:05.678Z").unwrap().with_timezone(&Utc);
+        assert_eq!(
+            format_line(LogFormat::Plain, Level::Info, "Read state: 1 nodes, 0 clusters", timestamp),
+            "2024-01-02T03:04:05Z Read state: 1 nodes, 0 clusters"
+        );
+        assert_eq!(
+            format_line(LogFormat::Plain, Level::Error, "Error reading state file", timestamp),
+            "2024-01-02T03:04:05Z ERROR: Error reading state file"
+        );
+
+        let mut logger = Logger::new(Vec::new(), LogFormat::Plain);
+        log_error(&mut logger, "Error reading state file");
+        let written = String::from_utf8(logger.out).unwrap();
+        assert!(written.ends_with("Z ERROR: Error reading state file\n"), "{}", written);
+    }
+
+    #[test]
+    fn json_lines_carry_timestamp_level_and_message() {
+        let timestamp = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().with_timezone(&Utc);
+        let line = format_line(LogFormat::Json, Level::Error, "Node \"a\" failed", timestamp);
+        let parsed: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(parsed["timestamp"], "2026-01-02T03:04:05+00:00");
+        assert_eq!(parsed["level"], "error");
+        assert_eq!(parsed["message"], "Node \"a\" failed");
+    }
+}
@@ -1,64 +1,143 @@
+use gml_core::daemon_pid::DaemonPidFile;
 use gml_core::error::GmlError;
-use gml_core::state::{GmlState, NodeEntry, ClusterEntry};
+use gml_core::paths;
+use gml_core::state::{self, GmlState, NodeEntry, ClusterEntry};
 use chrono::{DateTime, Utc};
+use signal_hook::consts::{SIGINT, SIGTERM};
 use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::thread;
-use std::time::Duration;
-use std::fs::{OpenOptions, create_dir_all, File};
+use std::time::{Duration, Instant};
+use std::fs::create_dir_all;
 use std::io::Write;
-use dirs;
 
-fn open_log_file() -> Result<File, Box<dyn std::error::Error>> {
-    let home_dir = dirs::home_dir()
-        .ok_or("Unable to determine home directory")?;
-    let log_dir = home_dir.join(".gml");
-    let log_file = log_dir.join("gmld.log");
+mod clock;
+mod log_rotation;
+mod logging;
+mod webhook;
+
+use clock::ClockGuard;
+use log_rotation::LogRotation;
+use logging::{Logger, log, log_error};
+use webhook::{Deletion, StuckDeletion};
+
+/// How far to push back the timeout of an expired node that still has a live gml session on it
+const IN_USE_GRACE: chrono::Duration = chrono::Duration::minutes(15);
+
+/// Time between state scans
+const SCAN_INTERVAL: Duration = Duration::from_secs(60);
+
+fn log_rotation() -> Result<LogRotation, Box<dyn std::error::Error>> {
+    let log_dir = paths::gml_dir()?;
     
     // Create .gml directory if it doesn't exist
     create_dir_all(&log_dir)?;
     
-    // Open log file for appending (create if it doesn't exist)
-    let file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_file)?;
-    
-    Ok(file)
-}
-
-fn log<W: Write>(out: &mut W, message: &str) {
-    let _ = writeln!(out, "{}", message);
-    let _ = out.flush();
-}
-
-fn log_error<W: Write>(out: &mut W, message: &str) {
-    log(out, &format!("ERROR: {}", message));
+    Ok(LogRotation::from_env(log_dir.join("gmld.log")))
 }
 
 fn main() {
+    let log_format = match logging::log_format(std::env::args().skip(1)) {
+        Ok(format) => format,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+
     // Initialize logging to ~/.gml/gmld.log
-    let mut log_file = match open_log_file() {
-        Ok(f) => f,
+    let rotation = match log_rotation() {
+        Ok(rotation) => rotation,
+        Err(e) => {
+        eprintln!("Failed to initialize log file: {}", e);
+        return;
+        }
+    };
+    let mut logger = match rotation.open() {
+        Ok(f) => Logger::new(f, log_format),
         Err(e) => {
         eprintln!("Failed to initialize log file: {}", e);
         return;
         }
     };
     
-    log(&mut log_file, "GML Daemon starting...");
+    log(&mut logger, "GML Daemon starting...");
+
+    // Held until main returns, so the PID file is removed on graceful shutdown
+    let _pid_file = match DaemonPidFile::claim() {
+        Ok(Some(pid_file)) => pid_file,
+        Ok(None) => {
+            log(&mut logger, "Another gmld is already running; exiting");
+            return;
+        }
+        Err(e) => {
+            log_error(&mut logger, &format!("Failed to write PID file: {}", e));
+            std::process::exit(1);
+        }
+    };
+
+    // SIGTERM (e.g. `systemctl stop`) and SIGINT only raise this flag, so a scan in progress
+    // finishes before the daemon exits
+    let shutdown = Arc::new(AtomicBool::new(false));
+    for signal in [SIGTERM, SIGINT] {
+        if let Err(e) = signal_hook::flag::register(signal, Arc::clone(&shutdown)) {
+            log_error(&mut logger, &format!("Failed to register handler for signal {}: {}", signal, e));
+        }
+    }
+
+    let mut clock_guard = ClockGuard::new();
     
-    loop {
+    while !shutdown.load(Ordering::Relaxed) {
+        match rotation.rotate_if_needed() {
+            Ok(Some(fresh)) => {
+                logger.set_output(fresh);
+                log(&mut logger, "Rotated log; the previous log is in gmld.log.1");
+            }
+            Ok(None) => {}
+            Err(e) => log_error(&mut logger, &format!("Failed to rotate log: {}", e)),
+        }
+
+        let wall_now = Utc::now();
+        let mono_now = Instant::now();
+        if let Some(jump) = clock_guard.observe(wall_now, mono_now) {
+            log(&mut logger, &format!(
+                "WARNING: system clock jumped by {}s (NTP correction or suspend/resume); \
+                 expiry checks will not count a forward jump for the next {}s",
+                jump.num_seconds(),
+                clock::JUMP_HOLD.as_secs()
+            ));
+        }
+        let now = clock_guard.effective_now(wall_now, mono_now);
+
         match GmlState::load() {
             Ok(state) => {
-                log(&mut log_file, &format!("Read state: {} nodes, {} clusters", 
+                log(&mut logger, &format!("Read state: {} nodes, {} clusters", 
                     state.nodes.len(), 
                     state.clusters.len()));
                 
                 // Process node timeouts
                 for node_entry in &state.nodes {
+                    // A deletion requested with `gml node delete` goes ahead whatever the timeout says
+                    if let Some(ref delete_at) = node_entry.pending_delete_at {
+                        if let Err(e) = handle_pending_delete(&mut logger, node_entry, delete_at, now) {
+                            log_error(&mut logger, &format!("Error handling pending delete {}: {}", node_entry.id, e));
+                        }
+                        continue;
+                    }
+                    // Reserved nodes are never deleted automatically
+                    if node_entry.reserved {
+                        continue;
+                    }
+                    if let Some(ref soft_timeout) = node_entry.soft_timeout
+                        && !node_entry.soft_timeout_fired
+                        && let Err(e) = handle_soft_timeout(&mut logger, node_entry, soft_timeout, now)
+                    {
+                        log_error(&mut logger, &format!("Error handling soft timeout {}: {}", node_entry.id, e));
+                    }
                     if let Some(ref timeout) = node_entry.timeout {
-                        if let Err(e) = handle_node_timeout(&mut log_file, node_entry, timeout) {
-                            log_error(&mut log_file, &format!("Error handling node timeout {}: {}", node_entry.id, e));
+                        if let Err(e) = handle_node_timeout(&mut logger, node_entry, timeout, now) {
+                            log_error(&mut logger, &format!("Error handling node timeout {}: {}", node_entry.id, e));
                         }
                     }
                 }
@@ -66,83 +145,277 @@ fn main() {
                 // Process cluster timeouts
                 for cluster_entry in &state.clusters {
                     if let Some(ref timeout) = cluster_entry.timeout {
-                        if let Err(e) = handle_cluster_timeout(&mut log_file, cluster_entry, timeout) {
-                            log_error(&mut log_file, &format!("Error handling cluster timeout {}: {}", cluster_entry.id, e));
+                        if let Err(e) = handle_cluster_timeout(&mut logger, cluster_entry, timeout, now) {
+                            log_error(&mut logger, &format!("Error handling cluster timeout {}: {}", cluster_entry.id, e));
                         }
                     }
                 }
             }
             Err(e) => {
-                log_error(&mut log_file, &format!("Error reading state file: {}", e));
+                log_error(&mut logger, &format!("Error reading state file: {}", e));
             }
         }
         
-        // Sleep for 1 minute
-        thread::sleep(Duration::from_secs(60));
+        sleep_until_next_scan(&shutdown);
+    }
+
+    log(&mut logger, "Shutting down gracefully");
+}
+
+/// Wait out the scan interval, waking early once a shutdown is requested
+fn sleep_until_next_scan(shutdown: &AtomicBool) {
+    let started = Instant::now();
+    while !shutdown.load(Ordering::Relaxed) && started.elapsed() < SCAN_INTERVAL {
+        thread::sleep(Duration::from_millis(250));
     }
 }
 
 /// Handle node timeout - check if expired and stop/remove if needed
-fn handle_node_timeout<W: Write>(log_out: &mut W, node_entry: &NodeEntry, timeout: &str) -> Result<(), GmlError> {
+fn handle_node_timeout<W: Write>(log_out: &mut Logger<W>, node_entry: &NodeEntry, timeout: &str, now: DateTime<Utc>) -> Result<(), GmlError> {
     // Parse the timeout timestamp
     let timeout_dt = DateTime::parse_from_rfc3339(timeout)
         .map_err(|e| GmlError::from(format!("Failed to parse timeout for node {}: {}", node_entry.id, e)))?;
     let timeout_utc = timeout_dt.with_timezone(&Utc);
-    let now = Utc::now();
     
     // Check if timeout has expired
-    if now < timeout_utc {
+    if !clock::is_expired(timeout_utc, now) {
         // Not expired yet
         return Ok(());
     }
     
+    // Don't pull the node out from under an open connect/ssh session; check again after the grace period
+    if let Some(pid) = node_entry.active_session() {
+        let extended = (now + IN_USE_GRACE).to_rfc3339();
+        GmlState::update(|state| {
+            state.node_mut(&node_entry.id)?.timeout = Some(extended.clone());
+            Ok(())
+        })?;
+        log(log_out, &format!(
+            "Node {} has expired but is in use by gml process {}, extending timeout to {}",
+            node_entry.id, pid, extended
+        ));
+        return Ok(());
+    }
+
     log(log_out, &format!("Node {} has expired (timeout: {}), deleting...", node_entry.id, timeout));
-    
-    // Call gml node delete command
+    delete_with_retries(log_out, node_entry)?;
+    notify_deleted(log_out, std::slice::from_ref(&node_entry.id), &node_entry.provider, None);
+    Ok(())
+}
+
+/// Run the `soft-timeout` hook once the node's soft timeout has passed, ahead of deleting it at
+/// its timeout. The hook is marked as run even if it fails, so it isn't repeated every sweep.
+fn handle_soft_timeout<W: Write>(log_out: &mut Logger<W>, node_entry: &NodeEntry, soft_timeout: &str, now: DateTime<Utc>) -> Result<(), GmlError> {
+    let soft_timeout_utc = DateTime::parse_from_rfc3339(soft_timeout)
+        .map_err(|e| GmlError::from(format!("Failed to parse soft timeout for node {}: {}", node_entry.id, e)))?
+        .with_timezone(&Utc);
+    if !clock::is_expired(soft_timeout_utc, now) {
+        return Ok(());
+    }
+
+    log(log_out, &format!(
+        "Node {} reached its soft timeout ({}), running the soft-timeout hook; it will be deleted at {}",
+        node_entry.id, soft_timeout, node_entry.timeout.as_deref().unwrap_or("its timeout")
+    ));
+    let output = Command::new("gml")
+        .args(["node", "soft-timeout", &node_entry.id])
+        .output();
+    GmlState::update(|state| {
+        state.node_mut(&node_entry.id)?.soft_timeout_fired = true;
+        Ok(())
+    })?;
+
+    let output = output.map_err(|e| GmlError::from(format!("Failed to execute gml node soft-timeout: {}", e)))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(GmlError::from(format!("gml node soft-timeout failed: {}", stderr.trim())));
+    }
+    log(log_out, &format!("Ran the soft-timeout hook for node {}", node_entry.id));
+    Ok(())
+}
+
+/// Delete a node whose `gml node delete` grace period has run out
+fn handle_pending_delete<W: Write>(log_out: &mut Logger<W>, node_entry: &NodeEntry, delete_at: &str, now: DateTime<Utc>) -> Result<(), GmlError> {
+    let delete_at_utc = DateTime::parse_from_rfc3339(delete_at)
+        .map_err(|e| GmlError::from(format!("Failed to parse pending delete time for node {}: {}", node_entry.id, e)))?
+        .with_timezone(&Utc);
+    if !clock::is_expired(delete_at_utc, now) {
+        return Ok(());
+    }
+    // Wait for an open session to end rather than deleting the node out from under it
+    if let Some(pid) = node_entry.active_session() {
+        log(log_out, &format!("Node {} is due for deletion but is in use by gml process {}, waiting", node_entry.id, pid));
+        return Ok(());
+    }
+
+    log(log_out, &format!("Node {} was scheduled for deletion at {}, deleting...", node_entry.id, delete_at));
+    delete_with_retries(log_out, node_entry)
+}
+
+/// Delete a node, counting failures against it. The node stays in state when deletion fails, so
+/// the next sweep retries it; a node that never goes away is escalated instead of silently billing.
+fn delete_with_retries<W: Write>(log_out: &mut Logger<W>, node_entry: &NodeEntry) -> Result<(), GmlError> {
+    if let Err(e) = delete_node(&node_entry.id) {
+        let attempts = GmlState::update(|state| {
+            let node = state.node_mut(&node_entry.id)?;
+            node.delete_attempts += 1;
+            Ok(node.delete_attempts)
+        })?;
+        if attempts == state::DELETE_ATTEMPTS_BEFORE_ESCALATION {
+            log_error(log_out, &format!(
+                "Node {} has failed to delete {} times and may still be billing; \
+                 it is flagged in `gml status` until deleted",
+                node_entry.id, attempts
+            ));
+            notify_stuck(log_out, node_entry, attempts);
+        }
+        return Err(GmlError::from(format!("{} (attempt {}, retrying next sweep)", e.message(), attempts)));
+    }
+
+    log(log_out, &format!("Successfully deleted node {}", node_entry.id));
+
+    Ok(())
+}
+
+/// Arguments to `gml` that delete a node right away. `--yes` because there is no one to answer
+/// the confirmation prompt, and without a terminal it would fail instead.
+fn delete_node_args(node_id: &str) -> [&str; 5] {
+    ["node", "delete", node_id, "--now", "--yes"]
+}
+
+/// Arguments to `gml` that delete a cluster, without the confirmation prompt
+fn delete_cluster_args(cluster_id: &str) -> [&str; 4] {
+    ["cluster", "delete", cluster_id, "--yes"]
+}
+
+/// Delete a node through the CLI, which stops it with its provider and removes it from state
+fn delete_node(node_id: &str) -> Result<(), GmlError> {
     let output = Command::new("gml")
-        .args(&["node", "delete", &node_entry.id])
+        .args(delete_node_args(node_id))
         .output()
         .map_err(|e| GmlError::from(format!("Failed to execute gml node delete: {}", e)))?;
-    
+
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(GmlError::from(format!("gml node delete failed: {}", stderr)));
+        return Err(GmlError::from(format!("gml node delete failed: {}", stderr.trim())));
     }
-    
-    log(log_out, &format!("Successfully deleted node {}", node_entry.id));
-    
     Ok(())
 }
 
 /// Handle cluster timeout - check if expired and stop/remove if needed
-fn handle_cluster_timeout<W: Write>(log_out: &mut W, cluster_entry: &ClusterEntry, timeout: &str) -> Result<(), GmlError> {
+fn handle_cluster_timeout<W: Write>(log_out: &mut Logger<W>, cluster_entry: &ClusterEntry, timeout: &str, now: DateTime<Utc>) -> Result<(), GmlError> {
     // Parse the timeout timestamp
     let timeout_dt = DateTime::parse_from_rfc3339(timeout)
         .map_err(|e| GmlError::from(format!("Failed to parse timeout for cluster {}: {}", cluster_entry.id, e)))?;
     let timeout_utc = timeout_dt.with_timezone(&Utc);
-    let now = Utc::now();
     
     // Check if timeout has expired
-    if now < timeout_utc {
+    if !clock::is_expired(timeout_utc, now) {
         // Not expired yet
         return Ok(());
     }
     
     log(log_out, &format!("Cluster {} has expired (timeout: {}), deleting...", cluster_entry.id, timeout));
-    
+
+    // Members share the cluster's timeout, so some may already have been deleted (and notified
+    // about) earlier in this sweep; only the ones this delete removes are reported
+    let before = GmlState::load()?;
+
     // Call gml cluster delete command
     let output = Command::new("gml")
-        .args(&["cluster", "delete", &cluster_entry.id])
+        .args(delete_cluster_args(&cluster_entry.id))
         .output()
         .map_err(|e| GmlError::from(format!("Failed to execute gml cluster delete: {}", e)))?;
-    
+
+    // A failed delete can still have removed some members, which keeps only the rest
+    let removed = removed_members(cluster_entry, &before, &GmlState::load()?);
+    notify_deleted(log_out, &removed, &cluster_entry.provider, Some(&cluster_entry.id));
+
     if !output.status.success() {
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(GmlError::from(format!("gml cluster delete failed: {}", stderr)));
     }
     
     log(log_out, &format!("Successfully deleted cluster {}", cluster_entry.id));
-    
     Ok(())
 }
 
+/// Members of the cluster that were in `before` but are gone from `after`
+fn removed_members(cluster_entry: &ClusterEntry, before: &GmlState, after: &GmlState) -> Vec<String> {
+    before.nodes.iter()
+        .filter(|node| node.cluster_id.as_deref() == Some(&cluster_entry.id) || cluster_entry.members.contains(&node.id))
+        .filter(|node| after.node(&node.id).is_none())
+        .map(|node| node.id.clone())
+        .collect()
+}
+
+/// Tell `[daemon] webhook-url`, if one is set, about nodes deleted on timeout. The nodes are
+/// already gone, so a notification that fails is only logged.
+fn notify_deleted<W: Write>(log_out: &mut Logger<W>, node_ids: &[String], provider: &str, cluster_id: Option<&str>) {
+    let Some(url) = configured_webhook(log_out) else {
+        return;
+    };
+    let deleted_at = Utc::now();
+    for node_id in node_ids {
+        let deletion = Deletion { node_id, provider, cluster_id, deleted_at };
+        match webhook::notify(&url, &deletion) {
+            Ok(()) => log(log_out, &format!("Notified webhook of the deletion of node {}", node_id)),
+            Err(e) => log_error(log_out, &format!("Failed to notify webhook of the deletion of node {}: {}", node_id, e)),
+        }
+    }
+}
+
+/// Tell `[daemon] webhook-url`, if one is set, about a node that keeps failing to delete
+fn notify_stuck<W: Write>(log_out: &mut Logger<W>, node_entry: &NodeEntry, attempts: u32) {
+    let Some(url) = configured_webhook(log_out) else {
+        return;
+    };
+    let stuck = StuckDeletion { node_id: &node_entry.id, provider: &node_entry.provider, attempts };
+    match webhook::notify_stuck(&url, &stuck) {
+        Ok(()) => log(log_out, &format!("Notified webhook that node {} keeps failing to delete", node_entry.id)),
+        Err(e) => log_error(log_out, &format!("Failed to notify webhook about node {}: {}", node_entry.id, e)),
+    }
+}
+
+/// `[daemon] webhook-url`, logging a config that can't be read
+fn configured_webhook<W: Write>(log_out: &mut Logger<W>) -> Option<String> {
+    match webhook::webhook_url() {
+        Ok(url) => url,
+        Err(e) => {
+            log_error(log_out, &format!("Failed to read webhook-url: {}", e));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deletions_skip_the_confirmation_prompt() {
+        assert_eq!(delete_node_args("n1"), ["node", "delete", "n1", "--now", "--yes"]);
+        assert_eq!(delete_cluster_args("c1"), ["cluster", "delete", "c1", "--yes"]);
+    }
+
+    fn state_with(nodes: &[(&str, Option<&str>)]) -> GmlState {
+        let nodes: Vec<serde_json::Value> = nodes.iter().map(|(id, cluster_id)| serde_json::json!({
+            "id": id, "provider_id": format!("i-{}", id), "ip": "198.51.100.1", "provider": "lambda",
+            "created_at": "2026-01-01T00:00:00Z", "instance_type": "gpu_1x_a10", "timeout": null,
+            "user": "ubuntu", "cluster_id": cluster_id,
+        })).collect();
+        serde_json::from_value(serde_json::json!({ "nodes": nodes, "clusters": [] })).unwrap()
+    }
+
+    #[test]
+    fn only_members_removed_by_the_cluster_delete_are_reported() {
+        let cluster: ClusterEntry = serde_json::from_value(serde_json::json!({
+            "id": "c1", "provider": "lambda", "created_at": "2026-01-01T00:00:00Z", "node_count": 4,
+            "timeout": null, "members": ["n1", "n2", "n3", "legacy"],
+        })).unwrap();
+        // n1 was already deleted by its own timeout; n3 was kept, e.g. because it is in use
+        let before = state_with(&[("n2", Some("c1")), ("n3", Some("c1")), ("legacy", None), ("other", None)]);
+        let after = state_with(&[("n3", Some("c1")), ("other", None)]);
+        assert_eq!(removed_members(&cluster, &before, &after), vec!["n2", "legacy"]);
+    }
+}
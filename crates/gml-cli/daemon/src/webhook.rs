@@ -0,0 +1,147 @@
+//! Notifications to `[daemon] webhook-url` when gmld deletes nodes on timeout, or keeps failing to.
+//!
+//! The payload carries `node_id`, `provider` and `reason`, plus a `text` line so it can be sent
+//! straight to a Slack incoming webhook, which shows `text` and ignores the rest.
+
+use chrono::{DateTime, Utc};
+use gml_core::error::GmlError;
+use gml_core::paths;
+use serde_json::json;
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+/// Longest a notification may hold up the scan
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A node gmld deleted because its timeout passed
+pub struct Deletion<'a> {
+    pub node_id: &'a str,
+    pub provider: &'a str,
+    /// The cluster the node was deleted with, for cluster timeouts
+    pub cluster_id: Option<&'a str>,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// A node gmld has failed to delete often enough that it may be billing indefinitely
+pub struct StuckDeletion<'a> {
+    pub node_id: &'a str,
+    pub provider: &'a str,
+    /// Failed delete attempts so far
+    pub attempts: u32,
+}
+
+/// `[daemon] webhook-url` from `~/.gml/config.toml`. It is read on each use, so setting or
+/// changing it takes effect without restarting gmld.
+pub fn webhook_url() -> Result<Option<String>, GmlError> {
+    let path = paths::gml_dir()?.join("config.toml");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    webhook_url_in(&content).map_err(|e| GmlError::from(format!("Failed to parse {}: {}", path.display(), e)))
+}
+
+fn webhook_url_in(config: &str) -> Result<Option<String>, toml::de::Error> {
+    let config: toml::Table = toml::from_str(config)?;
+    Ok(config.get("daemon")
+        .and_then(|daemon| daemon.get("webhook-url"))
+        .and_then(|url| url.as_str())
+        .filter(|url| !url.is_empty())
+        .map(str::to_string))
+}
+
+fn payload(deletion: &Deletion) -> serde_json::Value {
+    let text = match deletion.cluster_id {
+        Some(cluster_id) => format!(
+            "gml deleted node {} ({}) of cluster {} after its timeout",
+            deletion.node_id, deletion.provider, cluster_id
+        ),
+        None => format!("gml deleted node {} ({}) after its timeout", deletion.node_id, deletion.provider),
+    };
+    let mut payload = json!({
+        "node_id": deletion.node_id,
+        "provider": deletion.provider,
+        "reason": "timeout",
+        "deleted_at": deletion.deleted_at.to_rfc3339(),
+        "text": text,
+    });
+    if let Some(cluster_id) = deletion.cluster_id {
+        payload["cluster_id"] = json!(cluster_id);
+    }
+    payload
+}
+
+fn stuck_payload(stuck: &StuckDeletion) -> serde_json::Value {
+    json!({
+        "node_id": stuck.node_id,
+        "provider": stuck.provider,
+        "reason": "delete_failed",
+        "attempts": stuck.attempts,
+        "text": format!(
+            "gml has failed to delete node {} ({}) {} times; it may still be billing",
+            stuck.node_id, stuck.provider, stuck.attempts
+        ),
+    })
+}
+
+/// POST the deletion to `url`
+pub fn notify(url: &str, deletion: &Deletion) -> Result<(), GmlError> {
+    post(url, &payload(deletion))
+}
+
+/// POST a node that keeps failing to delete to `url`
+pub fn notify_stuck(url: &str, stuck: &StuckDeletion) -> Result<(), GmlError> {
+    post(url, &stuck_payload(stuck))
+}
+
+fn post(url: &str, body: &serde_json::Value) -> Result<(), GmlError> {
+    let client = reqwest::blocking::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let response = client.post(url).json(body).send()?;
+    let status = response.status();
+    if !status.is_success() {
+        let body = response.text().unwrap_or_default();
+        return Err(GmlError::from(format!("Webhook returned {}: {}", status, body.trim())));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn url_comes_from_the_daemon_table() {
+        let config = "[gml]\nassume-yes = true\n\n[daemon]\nwebhook-url = \"https://hooks.example.com/x\"\n";
+        assert_eq!(webhook_url_in(config).unwrap().as_deref(), Some("https://hooks.example.com/x"));
+        assert_eq!(webhook_url_in("[daemon]\nwebhook-url = \"\"\n").unwrap(), None);
+        assert_eq!(webhook_url_in("[lambda]\nregion = \"us-east-1\"\n").unwrap(), None);
+    }
+
+    #[test]
+    fn payload_names_the_node_and_reason() {
+        let deleted_at = DateTime::parse_from_rfc3339("2026-01-02T03:04:05Z").unwrap().with_timezone(&Utc);
+        let deletion = Deletion { node_id: "abc", provider: "lambda", cluster_id: None, deleted_at };
+        let body = payload(&deletion);
+        assert_eq!(body["node_id"], "abc");
+        assert_eq!(body["provider"], "lambda");
+        assert_eq!(body["reason"], "timeout");
+        assert_eq!(body["deleted_at"], "2026-01-02T03:04:05+00:00");
+        assert_eq!(body["text"], "gml deleted node abc (lambda) after its timeout");
+        assert!(body.get("cluster_id").is_none());
+
+        let member = Deletion { cluster_id: Some("c1"), ..deletion };
+        assert_eq!(payload(&member)["cluster_id"], "c1");
+    }
+
+    #[test]
+    fn stuck_payload_reports_the_attempts() {
+        let body = stuck_payload(&StuckDeletion { node_id: "abc", provider: "lambda", attempts: 5 });
+        assert_eq!(body["reason"], "delete_failed");
+        assert_eq!(body["attempts"], 5);
+        assert_eq!(body["text"], "gml has failed to delete node abc (lambda) 5 times; it may still be billing");
+    }
+}
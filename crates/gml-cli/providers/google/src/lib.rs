@@ -1,9 +1,11 @@
 use async_trait::async_trait;
-use gml_core::{NodeDetails, NodeProvider, NodeRequest, error::GmlError};
+use gml_core::{NodeDetails, NodeProvider, NodeRequest, NodeType, error::GmlError};
 use gml_core::ssh;
 use google_cloud_lro::Poller;
 use google_cloud_tpu_v2::client::Tpu;
-use google_cloud_tpu_v2::model::{ListAcceleratorTypesResponse, NetworkConfig, Node, ServiceAccount};
+use google_cloud_tpu_v2::model::{ListAcceleratorTypesResponse, NetworkConfig, Node, ServiceAccount, node::State};
+use google_cloud_wkt::FieldMask;
+use std::collections::HashMap;
 use uuid::Uuid;
 
 /// Default TPU VM software image; override with `GML_GOOGLE_TPU_RUNTIME` if your zone needs another version.
@@ -25,14 +27,15 @@ fn map_google_error(e: impl std::fmt::Display) -> GmlError {
         || msg.to_lowercase().contains("access denied")
         || msg.to_lowercase().contains("unauthorized")
     {
-        GmlError::from(format!("{}\n\n{}", msg, GOOGLE_AUTH_HELP))
+        GmlError::provider(format!("{}\n\n{}", msg, GOOGLE_AUTH_HELP))
     } else {
-        GmlError::from(msg)
+        GmlError::provider(msg)
     }
 }
 
 pub struct Google {
     client: Tpu,
+    project: String,
     parent: String,
     /// `[gml] ssh-public-key` from config (same resolution as `gml_core::ssh::get_ssh_public_key`).
     gml_ssh_public_key: Option<String>,
@@ -46,7 +49,7 @@ impl Google {
     ) -> Result<Google, GmlError> {
         let project = project
             .or_else(|| std::env::var("GOOGLE_CLOUD_PROJECT").ok())
-            .ok_or_else(|| GmlError::from("project is required for google provider: set in config or GOOGLE_CLOUD_PROJECT env"))?;
+            .ok_or_else(|| GmlError::from("project is required for google provider: set project under [google] in config, GML_GOOGLE_PROJECT or GOOGLE_CLOUD_PROJECT"))?;
         let location = location.unwrap_or_else(|| "us-central1".to_string());
         let parent = format!("projects/{}/locations/{}", project, location);
 
@@ -54,15 +57,22 @@ impl Google {
 
         Ok(Google {
             client,
+            project,
             parent,
             gml_ssh_public_key,
         })
     }
 
     /// GCP instance metadata `ssh-keys` value: `username:one-line-public-key` (see Compute Engine docs).
-    fn ssh_keys_metadata_value(&self) -> Result<String, GmlError> {
-        let path = ssh::get_ssh_public_key(self.gml_ssh_public_key.as_deref())?;
-        let line = ssh::read_ssh_public_key_line(&path)?;
+    /// `public_key` from the request takes the place of the configured key.
+    fn ssh_keys_metadata_value(&self, public_key: Option<&str>) -> Result<String, GmlError> {
+        let line = match public_key {
+            Some(line) => line.to_string(),
+            None => {
+                let path = ssh::get_ssh_public_key(self.gml_ssh_public_key.as_deref())?;
+                ssh::read_ssh_public_key_line(&path)?
+            }
+        };
         Ok(format!("{}:{}", DEFAULT_TPU_SSH_USER, line))
     }
 
@@ -76,6 +86,14 @@ impl Google {
         }
     }
 
+    /// `projects/.../locations/{zone}` for an explicit zone, otherwise the configured location.
+    fn parent_for_zone(&self, zone: Option<&str>) -> String {
+        match zone {
+            Some(zone) => format!("projects/{}/locations/{}", self.project, zone),
+            None => self.parent.clone(),
+        }
+    }
+
     fn runtime_version() -> String {
         std::env::var("GML_GOOGLE_TPU_RUNTIME").unwrap_or_else(|_| DEFAULT_TPU_RUNTIME_VERSION.to_string())
     }
@@ -118,15 +136,37 @@ fn node_to_details(node: Node) -> NodeDetails {
             })
         })
         .unwrap_or_default();
+    // TPU node names look like `projects/{project}/locations/{zone}/nodes/{id}`
+    let zone = node
+        .name
+        .split('/')
+        .skip_while(|segment| *segment != "locations")
+        .nth(1)
+        .map(|zone| zone.to_string());
     NodeDetails {
         ip,
+        status: node_status(&node.state),
         id: node.name,
+        zone,
+        ssh_port: None,
+    }
+}
+
+/// A TPU node's state as a gml status: `READY` is `active` and `STOPPED` is `paused`, since
+/// that is what `gml node pause` does; other states keep their name, lowercased
+fn node_status(state: &State) -> Option<String> {
+    match state {
+        State::Unspecified => None,
+        State::Ready => Some("active".to_string()),
+        State::Stopped => Some("paused".to_string()),
+        other => other.name().map(str::to_lowercase),
     }
 }
 
 #[async_trait]
 impl NodeProvider for Google {
-    async fn get_node_types(&self) -> Result<String, GmlError> {
+    /// TPU listings carry no availability, so every type is listed
+    async fn get_node_types(&self, _available_only: bool) -> Result<String, GmlError> {
         let response = self
             .client
             .list_accelerator_types()
@@ -141,6 +181,51 @@ impl NodeProvider for Google {
             .map_err(|e| GmlError::from(format!("Failed to serialize: {}", e)))
     }
 
+    /// TPU listings carry no pricing, HBM size or availability, so only the chip count is filled in
+    async fn list_node_types(&self) -> Result<Vec<NodeType>, GmlError> {
+        let response = self
+            .client
+            .list_accelerator_types()
+            .set_parent(self.parent.clone())
+            .send()
+            .await
+            .map_err(map_google_error)?;
+
+        Ok(Google::filter_single_node_accelerator_types(response)
+            .accelerator_types
+            .into_iter()
+            .map(|at| NodeType {
+                accelerator_count: at.r#type.rsplit_once('-').and_then(|(_, n)| n.parse().ok()).unwrap_or(0),
+                name: at.r#type,
+                description: String::new(),
+                memory_gb: None,
+                price: None,
+                regions: None,
+            })
+            .collect())
+    }
+
+    /// TPU locations are zones, which is what `region` names for this provider
+    async fn list_regions(&self) -> Result<Vec<String>, GmlError> {
+        let mut zones = Vec::new();
+        let mut page_token = String::new();
+        loop {
+            let response = self
+                .client
+                .list_locations()
+                .set_name(format!("projects/{}", self.project))
+                .set_page_token(page_token)
+                .send()
+                .await
+                .map_err(map_google_error)?;
+            zones.extend(response.locations.into_iter().map(|location| location.location_id));
+            if response.next_page_token.is_empty() {
+                return Ok(zones);
+            }
+            page_token = response.next_page_token;
+        }
+    }
+
     async fn start_node(&self, request: NodeRequest) -> Result<NodeDetails, GmlError> {
         if request.instance_type.trim().is_empty() {
             return Err(GmlError::from(
@@ -148,9 +233,17 @@ impl NodeProvider for Google {
             ));
         }
 
-        let ssh_keys = self.ssh_keys_metadata_value()?;
+        // The TPU API has no boot disk size; attached data disks must be created separately
+        if request.disk_gb.is_some() {
+            return Err(GmlError::unsupported("Custom disk sizes"));
+        }
+
+        let ssh_keys = self.ssh_keys_metadata_value(request.public_key.as_deref())?;
         // Request a public IP on the default VPC so `gml connect` can SSH without IAP/tunneling.
-        let network_config = NetworkConfig::new().set_enable_external_ips(true);
+        let mut network_config = NetworkConfig::new().set_enable_external_ips(true);
+        if let Some(subnet) = &request.subnet {
+            network_config = network_config.set_subnetwork(subnet.clone());
+        }
         // Attach the default Compute Engine SA with `cloud-platform` scope so workloads on the TPU
         // can transparently reach GCS and other GCP APIs via ADC (still gated by IAM on the SA).
         let service_account = ServiceAccount::new()
@@ -162,19 +255,48 @@ impl NodeProvider for Google {
             .set_service_account(service_account)
             .set_metadata([("ssh-keys", ssh_keys)]);
 
+        // The node's name is chosen here rather than by the API, so it can be reported before
+        // the create call; if that call never lands, a later rollback just finds no such node.
+        let parent = self.parent_for_zone(request.zone.as_deref());
+        let node_id = Google::new_node_id();
+        if let Some(callback) = &request.on_launched {
+            callback(&format!("{}/nodes/{}", parent, node_id), request.region.as_deref());
+        }
+        if let Some(callback) = &request.on_status {
+            callback("creating");
+        }
         let node = self
             .client
             .create_node()
-            .set_parent(self.parent.clone())
-            .set_node_id(Google::new_node_id())
+            .set_parent(parent)
+            .set_node_id(node_id)
             .set_node(node_spec)
             .poller()
             .until_done()
             .await
             .map_err(map_google_error)?;
+        if let Some(callback) = &request.on_status {
+            callback("ready");
+        }
         Ok(node_to_details(node))
     }
 
+    fn describe_launch(&self, request: &NodeRequest) -> Result<String, GmlError> {
+        if request.disk_gb.is_some() {
+            return Err(GmlError::unsupported("Custom disk sizes"));
+        }
+        let mut description = format!(
+            "CreateNode in {} (node ID generated at launch)\n  acceleratorType: {}\n  runtimeVersion: {}\n  networkConfig.enableExternalIps: true",
+            self.parent_for_zone(request.zone.as_deref()),
+            request.instance_type,
+            Google::runtime_version()
+        );
+        if let Some(subnet) = &request.subnet {
+            description.push_str(&format!("\n  networkConfig.subnetwork: {}", subnet));
+        }
+        Ok(description)
+    }
+
     async fn stop_node(&self, details: NodeDetails) -> Result<NodeDetails, GmlError> {
         let name = self.node_resource_name(&details.id);
         self.client
@@ -187,9 +309,49 @@ impl NodeProvider for Google {
         Ok(details)
     }
 
+    /// Stopped TPU VMs keep their boot disk and aren't billed for accelerator time.
+    async fn pause_node(&self, details: &NodeDetails) -> Result<(), GmlError> {
+        self.client
+            .stop_node()
+            .set_name(self.node_resource_name(&details.id))
+            .poller()
+            .until_done()
+            .await
+            .map_err(map_google_error)?;
+        Ok(())
+    }
+
+    async fn resume_node(&self, details: &NodeDetails) -> Result<NodeDetails, GmlError> {
+        let node = self
+            .client
+            .start_node()
+            .set_name(self.node_resource_name(&details.id))
+            .poller()
+            .until_done()
+            .await
+            .map_err(map_google_error)?;
+        Ok(node_to_details(node))
+    }
+
     async fn get_user(&self) -> Result<String, GmlError> {
         Ok(DEFAULT_TPU_SSH_USER.to_string())
     }
+
+    /// Tags become TPU node labels, which GCP carries through to billing exports.
+    async fn set_tags(&self, details: &NodeDetails, tags: &HashMap<String, String>) -> Result<(), GmlError> {
+        let node = Node::new()
+            .set_name(self.node_resource_name(&details.id))
+            .set_labels(tags.clone());
+        self.client
+            .update_node()
+            .set_node(node)
+            .set_update_mask(FieldMask::default().set_paths(["labels"]))
+            .poller()
+            .until_done()
+            .await
+            .map_err(map_google_error)?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -1,13 +1,72 @@
 use async_trait::async_trait;
-use gml_core::{NodeProvider, NodeRequest, NodeDetails};
+use gml_core::{BootPolling, ClusterDetails, ClusterProvider, ClusterRequest, CostEstimate, NodeProvider, NodeRequest, NodeDetails, NodeType, StatusCallback};
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+use std::time::Duration;
 use gml_core::error::GmlError;
+use gml_core::suggest;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 const BASE_URL: &str = "https://cloud.lambda.ai/api/v1/";
+
+/// How much of an unexpected response body to keep in an error
+const RAW_BODY_LIMIT: usize = 1000;
+/// Lambda has no region listing endpoint, and the instance-types listing only names regions that
+/// have spare capacity right now, so it is merged with the regions known to exist.
+const KNOWN_REGIONS: &[&str] = &[
+    "asia-northeast-1", "asia-northeast-2", "asia-south-1", "australia-east-1", "europe-central-1",
+    "me-west-1", "us-east-1", "us-east-2", "us-east-3", "us-midwest-1", "us-south-1", "us-south-2",
+    "us-south-3", "us-west-1", "us-west-2", "us-west-3",
+];
+/// How many times an API call is tried when it keeps failing transiently, unless configured
+pub const DEFAULT_MAX_ATTEMPTS: u32 = 3;
+/// Delay before the first retry, doubled for each one after
+const RETRY_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Longest a single API call may take before it fails as a timeout, and is retried if safe
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Longest to wait for a connection to the API
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
 pub struct Lambda {
     pub api_key: String,
-    pub ssh_key_id: String,
+    /// Registered SSH keys authorized on every node launched without a key of its own
+    pub ssh_key_names: Vec<String>,
     pub region: String,
+    pub boot_polling: BootPolling,
+    /// Tries per API call, counting the first; see [`Retry`]
+    pub max_attempts: u32,
+    /// Shared by every call so connections are pooled, notably across boot polling
+    client: reqwest::Client,
+}
+
+/// Which failures of an API call are retried
+#[derive(Clone, Copy)]
+enum Retry {
+    /// For calls that are safe to repeat: rate limits, server errors, timeouts and failed connections
+    Transient,
+    /// For calls that must not run twice, like a launch: only rate limits and failed connections,
+    /// which show the request was not acted on. A server error or timeout may hide a launched instance.
+    Unprocessed,
+}
+
+impl Retry {
+    fn status(self, status: reqwest::StatusCode) -> bool {
+        status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            || (matches!(self, Retry::Transient) && status.is_server_error())
+    }
+
+    fn error(self, error: &reqwest::Error) -> bool {
+        error.is_connect() || (matches!(self, Retry::Transient) && error.is_timeout())
+    }
+}
+
+/// Backoff before retry number `retry` (from 1): the base delay doubled per retry, with the
+/// upper half jittered so parallel launches don't retry in lockstep
+fn retry_delay(retry: u32) -> Duration {
+    let delay = RETRY_BASE_DELAY * 2u32.saturating_pow(retry.saturating_sub(1));
+    let jitter = RandomState::new().hash_one(retry) % 1000;
+    delay / 2 + delay / 2 * jitter as u32 / 1000
 }
 
 #[derive(Serialize)]
@@ -17,13 +76,18 @@ struct LaunchRequest {
     ssh_key_names: Vec<String>,
 }
 
+// Successful responses are parsed leniently: a missing field is reported with what it means for
+// the operation, rather than as a bare serde error
+
 #[derive(Deserialize)]
 struct LaunchResponse {
+    #[serde(default)]
     data: LaunchResponseData,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct LaunchResponseData {
+    #[serde(default)]
     instance_ids: Vec<String>,
 }
 
@@ -36,9 +100,36 @@ struct InfoResponse {
 struct InfoResponseData {
     #[serde(default)]
     ip: Option<String>,
+    #[serde(default)]
     status: String,
 }
 
+#[derive(Deserialize)]
+struct InstancesResponse {
+    #[serde(default)]
+    data: Vec<InstanceInfo>,
+}
+
+#[derive(Deserialize)]
+struct InstanceInfo {
+    id: String,
+    #[serde(default)]
+    ip: Option<String>,
+    #[serde(default)]
+    status: String,
+}
+
+impl InstanceInfo {
+    fn exists(&self) -> bool {
+        status_exists(&self.status)
+    }
+}
+
+/// Terminated instances stay in the API for a while after they are gone
+fn status_exists(status: &str) -> bool {
+    !matches!(status, "terminating" | "terminated")
+}
+
 #[derive(Serialize)]
 struct TerminateRequest {
     instance_ids: Vec<String>,
@@ -46,11 +137,13 @@ struct TerminateRequest {
 
 #[derive(Deserialize)]
 struct TerminateResponse {
+    #[serde(default)]
     data: TerminateResponseData,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 struct TerminateResponseData {
+    #[serde(default)]
     terminated_instances: Vec<TerminatedInstance>,
 }
 
@@ -59,89 +152,294 @@ struct TerminatedInstance {
     id: String,
 }
 
+/// Error body Lambda returns on non-success responses
+#[derive(Deserialize)]
+struct LambdaApiError {
+    error: LambdaApiErrorBody,
+}
+
+#[derive(Deserialize)]
+struct LambdaApiErrorBody {
+    code: String,
+    message: String,
+    #[serde(default)]
+    suggestion: Option<String>,
+}
+
+/// Turn a failed response into a readable error, using Lambda's message and suggestion when the
+/// body has the documented shape and the raw body otherwise
+fn api_error(status: reqwest::StatusCode, body: &str) -> GmlError {
+    match serde_json::from_str::<LambdaApiError>(body) {
+        Ok(LambdaApiError { error }) => {
+            let mut message = format!("Lambda API error ({}, {}): {}", status, error.code, error.message);
+            if let Some(suggestion) = error.suggestion {
+                message.push_str(&format!("\n  Suggestion: {}", suggestion));
+            }
+            GmlError::Provider { status: Some(status.as_u16()), body: message }
+        }
+        Err(_) => GmlError::Provider {
+            status: Some(status.as_u16()),
+            body: format!("API Error ({}): {}", status, body),
+        },
+    }
+}
+
+/// A successful response's body, truncated for use in an error message
+fn raw_body(body: &str) -> String {
+    match body.char_indices().nth(RAW_BODY_LIMIT) {
+        Some((end, _)) => format!("{}...", &body[..end]),
+        None => body.to_string(),
+    }
+}
+
+/// Parse a successful response, naming the request and keeping the raw body when its shape is
+/// not what we expect
+fn parse_response<T: DeserializeOwned>(request: &str, body: &str) -> Result<T, GmlError> {
+    serde_json::from_str(body).map_err(|e| GmlError::provider(format!(
+        "Unexpected response from Lambda to {}: {}; raw: {}",
+        request, e, raw_body(body)
+    )))
+}
+
+/// The ID of the instance a successful launch response reports
+fn launched_instance_id(body: &str) -> Result<String, GmlError> {
+    let response: LaunchResponse = parse_response("the launch request", body)?;
+    response.data.instance_ids.into_iter().next().ok_or_else(|| GmlError::provider(format!(
+        "Launch succeeded but no instance ID was in the response; raw: {}",
+        raw_body(body)
+    )))
+}
+
+fn missing_ssh_key_message(name: &str, available: &[String]) -> String {
+    let available: Vec<&str> = available.iter().map(String::as_str).collect();
+    let mut message = format!(
+        "ssh key '{}' not found in your Lambda account; available: [{}]",
+        name,
+        available.join(", ")
+    );
+    let suggestions = suggest::closest_matches(name, &available);
+    if !suggestions.is_empty() {
+        message.push_str(&format!("\n  Did you mean: {}?", suggestions.join(", ")));
+    }
+    message.push_str("\n  Fix ssh-key-name under [lambda] in ~/.gml/config.toml, or pass --force to skip this check");
+    message
+}
+
+#[derive(Deserialize)]
+struct SshKeysResponse {
+    #[serde(default)]
+    data: Vec<SshKey>,
+}
+
+#[derive(Deserialize)]
+struct SshKey {
+    name: String,
+    #[serde(default)]
+    public_key: String,
+}
+
+#[derive(Serialize)]
+struct AddSshKeyRequest<'a> {
+    name: &'a str,
+    public_key: &'a str,
+}
+
+/// Key type and material of a public key line, without its comment
+fn key_material(public_key: &str) -> Vec<&str> {
+    public_key.split_whitespace().take(2).collect()
+}
+
+/// Name to register a `--private-key-from` key under; derived from the key itself, so the same
+/// key always maps to the same name
+fn byo_key_name(public_key: &str) -> String {
+    let material: String = key_material(public_key)
+        .last()
+        .copied()
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    let suffix = &material[material.len().saturating_sub(12)..];
+    format!("gml-{}", suffix)
+}
+
+#[derive(Deserialize)]
+struct InstanceTypesResponse {
+    #[serde(default)]
+    data: HashMap<String, InstanceTypeEntry>,
+}
+
+#[derive(Deserialize)]
+struct InstanceTypeEntry {
+    instance_type: InstanceTypeInfo,
+    #[serde(default)]
+    regions_with_capacity_available: Vec<RegionInfo>,
+}
+
+#[derive(Deserialize)]
+struct InstanceTypeInfo {
+    #[serde(default)]
+    price_cents_per_hour: Option<u64>,
+    #[serde(default)]
+    description: String,
+    /// e.g. "A100 (40 GB SXM4)"
+    #[serde(default)]
+    gpu_description: String,
+    #[serde(default)]
+    specs: Option<InstanceTypeSpecs>,
+}
+
+#[derive(Deserialize)]
+struct InstanceTypeSpecs {
+    #[serde(default)]
+    gpus: u32,
+}
+
+#[derive(Deserialize)]
+struct RegionInfo {
+    name: String,
+}
+
+/// Filter out instance types with empty regions_with_capacity_available
+/// Structure: { "data": { "instance_type_name": { "regions_with_capacity_available": [...] }, ... } }
+fn retain_types_with_capacity(listing: &mut serde_json::Value) {
+    if let Some(serde_json::Value::Object(data_map)) = listing.get_mut("data") {
+        data_map.retain(|_, instance_data| {
+            instance_data
+                .get("regions_with_capacity_available")
+                .and_then(|regions| regions.as_array())
+                .map_or(false, |regions_array| !regions_array.is_empty())
+        });
+    }
+}
+
+/// Every region named in the listing plus the known ones, sorted and without duplicates
+fn region_names(types: InstanceTypesResponse) -> Vec<String> {
+    let mut regions: Vec<String> = KNOWN_REGIONS.iter().map(|r| r.to_string()).collect();
+    regions.extend(types.data.into_values().flat_map(|entry| entry.regions_with_capacity_available).map(|r| r.name));
+    regions.sort();
+    regions.dedup();
+    regions
+}
+
+/// Per-GPU memory from a GPU description such as "H100 (80 GB SXM5)"
+fn parse_gpu_memory_gb(gpu_description: &str) -> Option<u32> {
+    let words: Vec<&str> = gpu_description
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|word| !word.is_empty())
+        .collect();
+    words.iter().enumerate().find_map(|(i, word)| {
+        // Accept both "40 GB" and "40GB"
+        let amount = match word.strip_suffix("GB") {
+            Some("") => *words.get(i.checked_sub(1)?)?,
+            Some(amount) => amount,
+            None => return None,
+        };
+        amount.parse().ok()
+    })
+}
+
 #[async_trait]
 impl NodeProvider for Lambda {
     async fn start_node(&self, request: NodeRequest) -> Result<NodeDetails, GmlError> {
-        let client = reqwest::Client::new();
-        
-        // Create launch request with region_name from CLI flag or config
-        let payload = LaunchRequest {
-            region_name: self.region.clone(),
-            instance_type_name: request.instance_type.clone(),
-            ssh_key_names: vec![self.ssh_key_id.clone()],
+        // Lambda instances come with a fixed-size disk; persistent filesystems are attached separately
+        if request.disk_gb.is_some() {
+            return Err(GmlError::unsupported("Custom disk sizes"));
+        }
+
+        let ssh_key_names = match &request.public_key {
+            Some(public_key) => vec![self.register_ssh_key(public_key).await?],
+            None => self.ssh_key_names.clone(),
         };
 
+        let payload = self.launch_payload(&request, ssh_key_names);
+        let region = payload.region_name.clone();
         let url = BASE_URL.to_owned() + "instance-operations/launch";
 
-        let response = client.post(url)
-            .basic_auth(&self.api_key, None::<&str>)
-            .header("accept", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| GmlError::from(format!("Request failed: {}", e)))?;
+        let launch = self.request(reqwest::Method::POST, &url).json(&payload);
+        let response = self.send(launch, Retry::Unprocessed).await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await.unwrap_or_default();
-            return Err(GmlError::from(format!("API Error ({}): {}", status, text)));
+            return Err(api_error(status, &text));
         }
 
-        let response_text = response.text()
-            .await
-            .map_err(|e| GmlError::from(format!("Failed to read response body: {}", e)))?;
+        let response_text = response.text().await?;
         
-        let launch_response: LaunchResponse = serde_json::from_str(&response_text)
-            .map_err(|e| GmlError::from(format!("Failed to parse response: {} - Response body: {}", e, response_text)))?;
-
-        let instance_id = launch_response.data.instance_ids.first()
-            .ok_or_else(|| GmlError::from("No instance ID returned"))?
-            .clone();
+        let instance_id = launched_instance_id(&response_text)?;
+        if let Some(callback) = &request.on_launched {
+            callback(&instance_id, Some(&region));
+        }
 
-        let ip = self.get_node_ip(&instance_id).await?;
+        let ip = self.get_node_ip(&instance_id, request.on_status.as_ref()).await?;
 
+        // Lambda has no availability zones or subnets, so request.zone and request.subnet are ignored
         Ok(NodeDetails {
             ip: ip,
             id: instance_id,
+            zone: None,
+            ssh_port: None,
+            // get_node_ip only returns once the instance is active
+            status: Some("active".to_string()),
         })
     }
 
-    async fn stop_node(&self, details: NodeDetails) -> Result<NodeDetails, GmlError> {
-        let client = reqwest::Client::new();
+    fn describe_launch(&self, request: &NodeRequest) -> Result<String, GmlError> {
+        if request.disk_gb.is_some() {
+            return Err(GmlError::unsupported("Custom disk sizes"));
+        }
 
+        let mut description = String::new();
+        let ssh_key_names = match &request.public_key {
+            Some(public_key) => {
+                description.push_str(&format!("POST {}ssh-keys, unless the key is already registered\n", BASE_URL));
+                vec![byo_key_name(public_key)]
+            }
+            None => self.ssh_key_names.clone(),
+        };
+        let payload = self.launch_payload(request, ssh_key_names);
+        description.push_str(&format!(
+            "POST {}instance-operations/launch\n{}",
+            BASE_URL,
+            serde_json::to_string_pretty(&payload)?
+        ));
+        Ok(description)
+    }
+
+    async fn stop_node(&self, details: NodeDetails) -> Result<NodeDetails, GmlError> {
         let payload = TerminateRequest {
             instance_ids: vec![details.id.clone()],
         };
 
         let url = BASE_URL.to_owned() + "instance-operations/terminate";
 
-        let response = client.post(url)
-            .basic_auth(&self.api_key, None::<&str>)
-            .header("accept", "application/json")
-            .json(&payload)
-            .send()
-            .await
-            .map_err(|e| GmlError::from(format!("Request failed: {}", e)))?;
+        // Terminating an instance twice is harmless
+        let request = self.request(reqwest::Method::POST, &url).json(&payload);
+        let response = self.send(request, Retry::Transient).await?;
 
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await.unwrap_or_default();
-            return Err(GmlError::from(format!("API Error ({}): {}", status, text)));
+            return Err(api_error(status, &text));
         }
 
-        let response_text = response.text()
-            .await
-            .map_err(|e| GmlError::from(format!("Failed to read response body: {}", e)))?;
+        let response_text = response.text().await?;
         
-        let terminate_response: TerminateResponse = serde_json::from_str(&response_text)
-            .map_err(|e| GmlError::from(format!("Failed to parse response: {} - Response body: {}", e, response_text)))?;
+        let terminate_response: TerminateResponse = parse_response("the terminate request", &response_text)?;
 
         let instance = terminate_response.data.terminated_instances.first()
-            .ok_or_else(|| GmlError::from("No terminated instance returned"))?;
+            .ok_or_else(|| GmlError::provider(format!(
+                "Terminate succeeded but instance {} was not listed as terminated; raw: {}",
+                details.id, raw_body(&response_text)
+            )))?;
 
         Ok(NodeDetails {
             ip: details.ip,
             id: instance.id.clone(),
+            zone: details.zone,
+            ssh_port: details.ssh_port,
+            status: None,
         })
     }
 
@@ -150,109 +448,462 @@ impl NodeProvider for Lambda {
         Ok("ubuntu".to_string())
     }
 
-    async fn get_node_types(&self) -> Result<String, GmlError> {
-        let client = reqwest::Client::new();
+    async fn get_node_types(&self, available_only: bool) -> Result<String, GmlError> {
+        let response_text = self.fetch_instance_types().await?;
         
-        let url = BASE_URL.to_owned() + "instance-types";
+        let mut json_value: serde_json::Value = serde_json::from_str(&response_text)?;
+        if available_only {
+            retain_types_with_capacity(&mut json_value);
+        }
         
-        let response = client.get(&url)
-            .basic_auth(&self.api_key, None::<&str>)
-            .header("accept", "application/json")
-            .send()
-            .await
-            .map_err(|e| GmlError::from(format!("Request failed: {}", e)))?;
+        let pretty_json = serde_json::to_string_pretty(&json_value)?;
         
+        Ok(pretty_json)
+    }
+
+    /// Lambda only rejects an unknown SSH key name once the launch request is sent, so look it up first
+    async fn validate_request(&self, request: &NodeRequest) -> Result<(), GmlError> {
+        // A key passed with the request is registered at launch if it isn't already
+        if request.public_key.is_some() {
+            return Ok(());
+        }
+        let keys: Vec<String> = self.list_ssh_keys().await?.into_iter().map(|key| key.name).collect();
+        match self.ssh_key_names.iter().find(|name| !keys.contains(name)) {
+            Some(missing) => Err(GmlError::from(missing_ssh_key_message(missing, &keys))),
+            None => Ok(()),
+        }
+    }
+
+    async fn list_node_types(&self) -> Result<Vec<NodeType>, GmlError> {
+        let response_text = self.fetch_instance_types().await?;
+        let types: InstanceTypesResponse = parse_response("the instance-types listing", &response_text)?;
+
+        Ok(types.data.into_iter().map(|(name, entry)| {
+            let info = entry.instance_type;
+            NodeType {
+                name,
+                accelerator_count: info.specs.map_or(0, |specs| specs.gpus),
+                memory_gb: parse_gpu_memory_gb(&info.gpu_description),
+                description: info.description,
+                price: info.price_cents_per_hour.map(|cents| CostEstimate {
+                    hourly: cents as f64 / 100.0,
+                    currency: "USD".to_string(),
+                }),
+                regions: Some(entry.regions_with_capacity_available.into_iter().map(|r| r.name).collect()),
+            }
+        }).collect())
+    }
+
+    async fn list_regions(&self) -> Result<Vec<String>, GmlError> {
+        let response_text = self.fetch_instance_types().await?;
+        let types: InstanceTypesResponse = parse_response("the instance-types listing", &response_text)?;
+        Ok(region_names(types))
+    }
+
+    async fn get_node_status(&self, id: &str) -> Result<String, GmlError> {
+        let gone = || GmlError::Provider {
+            status: Some(reqwest::StatusCode::NOT_FOUND.as_u16()),
+            body: format!("Instance {} no longer exists on Lambda", id),
+        };
+        let info = match self.fetch_instance(id).await {
+            Err(GmlError::Provider { status: Some(404), .. }) => return Err(gone()),
+            result => result?,
+        };
+        if !status_exists(&info.status) {
+            return Err(gone());
+        }
+        Ok(info.status)
+    }
+
+    async fn list_nodes(&self) -> Result<Vec<NodeDetails>, GmlError> {
+        let url = format!("{}instances", BASE_URL);
+
+        let request = self.request(reqwest::Method::GET, &url);
+        let response = self.send(request, Retry::Transient).await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+        if !status.is_success() {
+            return Err(api_error(status, &response_text));
+        }
+
+        let instances: InstancesResponse = parse_response("the instance listing", &response_text)?;
+        Ok(instances.data.into_iter()
+            .filter(InstanceInfo::exists)
+            .map(|instance| NodeDetails {
+                // Booting instances don't have an IP yet
+                ip: instance.ip.unwrap_or_default(),
+                id: instance.id,
+                zone: None,
+                ssh_port: None,
+                status: Some(instance.status),
+            })
+            .collect())
+    }
+
+    async fn estimate_cost(&self, request: &NodeRequest) -> Result<CostEstimate, GmlError> {
+        let response_text = self.fetch_instance_types().await?;
+        let types: InstanceTypesResponse = parse_response("the instance-types listing", &response_text)?;
+
+        let entry = types.data.get(&request.instance_type)
+            .ok_or_else(|| GmlError::from(format!("Unknown instance type '{}'", request.instance_type)))?;
+        let cents = entry.instance_type.price_cents_per_hour
+            .ok_or_else(|| GmlError::provider(format!("Lambda lists no price for '{}'", request.instance_type)))?;
+
+        Ok(CostEstimate {
+            hourly: cents as f64 / 100.0,
+            currency: "USD".to_string(),
+        })
+    }
+}
+
+impl Lambda {
+    /// The SSH keys registered with the account
+    async fn list_ssh_keys(&self) -> Result<Vec<SshKey>, GmlError> {
+        let url = BASE_URL.to_owned() + "ssh-keys";
+
+        let request = self.request(reqwest::Method::GET, &url);
+        let response = self.send(request, Retry::Transient).await?;
+
         if !response.status().is_success() {
             let status = response.status();
             let text = response.text().await.unwrap_or_default();
-            return Err(GmlError::from(format!("API Error ({}): {}", status, text)));
+            return Err(api_error(status, &text));
         }
+
+        let response_text = response.text().await?;
+        let keys: SshKeysResponse = parse_response("the SSH key listing", &response_text)?;
+
+        Ok(keys.data)
+    }
+
+    /// Body of the launch call, in the request's region if it names one, else the handle's
+    fn launch_payload(&self, request: &NodeRequest, ssh_key_names: Vec<String>) -> LaunchRequest {
+        LaunchRequest {
+            region_name: request.region.clone().unwrap_or_else(|| self.region.clone()),
+            instance_type_name: request.instance_type.clone(),
+            ssh_key_names,
+        }
+    }
+
+    /// Name of the registered key matching `public_key`, registering it first if needed
+    async fn register_ssh_key(&self, public_key: &str) -> Result<String, GmlError> {
+        let keys = self.list_ssh_keys().await?;
+        if let Some(key) = keys.iter().find(|key| key_material(&key.public_key) == key_material(public_key)) {
+            return Ok(key.name.clone());
+        }
+
+        let name = byo_key_name(public_key);
+        let url = BASE_URL.to_owned() + "ssh-keys";
+        let request = self.request(reqwest::Method::POST, &url)
+            .json(&AddSshKeyRequest { name: &name, public_key });
+        let response = self.send(request, Retry::Unprocessed).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(api_error(status, &text));
+        }
+        Ok(name)
+    }
+
+    /// Fetch the raw instance-types listing, which includes pricing and regional capacity
+    async fn fetch_instance_types(&self) -> Result<String, GmlError> {
+        let url = BASE_URL.to_owned() + "instance-types";
         
-        let response_text = response.text()
-            .await
-            .map_err(|e| GmlError::from(format!("Failed to read response body: {}", e)))?;
-        
-        // Parse JSON and filter out entries with empty regions_with_capacity_available
-        let mut json_value: serde_json::Value = serde_json::from_str(&response_text)
-            .map_err(|e| GmlError::from(format!("Failed to parse response: {} - Response body: {}", e, response_text)))?;
+        let request = self.request(reqwest::Method::GET, &url);
+        let response = self.send(request, Retry::Transient).await?;
         
-        // Filter out instance types with empty regions_with_capacity_available
-        // Structure: { "data": { "instance_type_name": { "regions_with_capacity_available": [...] }, ... } }
-        if let Some(serde_json::Value::Object(data_map)) = json_value.get_mut("data") {
-            data_map.retain(|_, instance_data| {
-                instance_data
-                    .get("regions_with_capacity_available")
-                    .and_then(|regions| regions.as_array())
-                    .map_or(false, |regions_array| !regions_array.is_empty())
-            });
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(api_error(status, &text));
         }
         
-        let pretty_json = serde_json::to_string_pretty(&json_value)
-            .map_err(|e| GmlError::from(format!("Failed to pretty print JSON: {}", e)))?;
-        
-        Ok(pretty_json)
+        response.text()
+            .await
+            .map_err(GmlError::from)
     }
-}
 
-impl Lambda {
-    async fn get_node_ip(&self, instance_id: &str) -> Result<String, GmlError> {
-        const MAX_RETRIES: u32 = 60; // 10 minutes / 10 seconds = 60 attempts
-        const RETRY_DELAY_SECS: u64 = 10;
-        
-        let client = reqwest::Client::new();
+    async fn get_node_ip(&self, instance_id: &str, on_status: Option<&StatusCallback>) -> Result<String, GmlError> {
+        let max_attempts = self.boot_polling.attempts();
+
+        let mut last_status: Option<String> = None;
         
-        for attempt in 1..=MAX_RETRIES {
-            let url = format!("{}instances/{}", BASE_URL, instance_id);
-
-            let response = client.get(&url)
-                .basic_auth(&self.api_key, None::<&str>)
-                .header("accept", "application/json")
-                .send()
-                .await
-                .map_err(|e| {
-                    GmlError::from(format!("Request failed: {}", e))
-                })?;
-                
-            if !response.status().is_success() {
-                let status = response.status();
-                let text = response.text().await.unwrap_or_default();
-                return Err(GmlError::from(format!("API Error ({}): {}", status, text)));
-            }
+        for attempt in 1..=max_attempts {
+            let info = self.fetch_instance(instance_id).await?;
 
-            let response_text = response.text()
-                .await
-                .map_err(|e| {
-                    GmlError::from(format!("Failed to read response body: {}", e))
-                })?;
-            
-            let info: InfoResponse = serde_json::from_str(&response_text)
-                .map_err(|e| {
-                    GmlError::from(format!("Failed to parse response: {} - Response body: {}", e, response_text))
-                })?;
+            // Report status transitions (e.g. booting -> active) to the caller
+            if last_status.as_deref() != Some(info.status.as_str()) {
+                if let Some(callback) = on_status {
+                    callback(&info.status);
+                }
+                last_status = Some(info.status.clone());
+            }
 
             // Check if both IP is available and status is "active"
-            if let Some(ip) = &info.data.ip {
-                if info.data.status == "active" {
+            if let Some(ip) = &info.ip {
+                if info.status == "active" {
                     return Ok(ip.clone());
                 }
             }
             
-            if attempt < MAX_RETRIES {
-                tokio::time::sleep(std::time::Duration::from_secs(RETRY_DELAY_SECS)).await;
+            if attempt < max_attempts {
+                tokio::time::sleep(self.boot_polling.interval).await;
             }
         }
 
         Err(GmlError::from(format!(
-            "Instance {} did not become active with an IP address after {} minutes. Please try again later.",
-            instance_id, (MAX_RETRIES as u64 * RETRY_DELAY_SECS) / 60
+            "Instance {} did not become active with an IP address within {}s. Please try again later, \
+             or raise boot-timeout under [lambda] in ~/.gml/config.toml for slow-booting types.",
+            instance_id, self.boot_polling.timeout.as_secs()
         )))
     }
 
-    pub fn new(api_key: String, ssh_key_id: String, region: String) -> Lambda {
+    /// The instance's entry from `instances/{id}`
+    async fn fetch_instance(&self, instance_id: &str) -> Result<InfoResponseData, GmlError> {
+        let url = format!("{}instances/{}", BASE_URL, instance_id);
+
+        let request = self.request(reqwest::Method::GET, &url);
+        let response = self.send(request, Retry::Transient).await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let text = response.text().await.unwrap_or_default();
+            return Err(api_error(status, &text));
+        }
+
+        let response_text = response.text().await?;
+        let info: InfoResponse = parse_response("the instance status request", &response_text)?;
+        Ok(info.data)
+    }
+
+    /// A request to the API with the account's credentials
+    fn request(&self, method: reqwest::Method, url: &str) -> reqwest::RequestBuilder {
+        self.client.request(method, url)
+            .basic_auth(&self.api_key, None::<&str>)
+            .header("accept", "application/json")
+    }
+
+    /// Send `request`, retrying the failures `retry` allows with exponential backoff, up to
+    /// `max_attempts` tries. The last response or error is returned as is.
+    async fn send(&self, request: reqwest::RequestBuilder, retry: Retry) -> Result<reqwest::Response, reqwest::Error> {
+        let mut attempt = 1;
+        loop {
+            // A request that can't be cloned (a streamed body) is only sent once
+            let Some(this_try) = request.try_clone().filter(|_| attempt < self.max_attempts) else {
+                return request.send().await;
+            };
+            let outcome = this_try.send().await;
+            let worth_retrying = match &outcome {
+                Ok(response) => retry.status(response.status()),
+                Err(e) => retry.error(e),
+            };
+            if !worth_retrying {
+                return outcome;
+            }
+            tokio::time::sleep(retry_delay(attempt)).await;
+            attempt += 1;
+        }
+    }
+
+    pub fn new(api_key: String, ssh_key_names: Vec<String>, region: String, boot_polling: BootPolling, max_attempts: u32) -> Lambda {
         Lambda {
             api_key,
-            ssh_key_id,
-            region
+            ssh_key_names,
+            region,
+            boot_polling,
+            max_attempts,
+            // Without timeouts a hung call would never fail, so it could neither be retried nor reported
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .connect_timeout(CONNECT_TIMEOUT)
+                .build()
+                .expect("failed to initialize the HTTP client"),
+        }
+    }
+}
+
+/// Lambda has no cluster primitive, so a cluster is just independently launched instances.
+/// Termination is by instance ID alone, so the default `delete_cluster` works from any region's handle.
+#[async_trait]
+impl ClusterProvider for Lambda {
+    /// Like the default, but with each member's region set on its request so members can be
+    /// spread across regions. Launches still hold the request's launch slots.
+    async fn create_cluster(&self, request: ClusterRequest) -> Result<ClusterDetails, GmlError> {
+        let launches = (0..request.node_count)
+            .map(|index| {
+                let mut member = request.member.clone();
+                if let Some(region) = request.region_for(index) {
+                    member.region = Some(region.to_string());
+                }
+                (self as &dyn NodeProvider, member)
+            })
+            .collect();
+        let nodes = gml_core::start_nodes_with_rollback(launches, request.launch_slots.as_deref()).await?;
+        Ok(ClusterDetails { nodes })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn api_error_surfaces_message_and_suggestion() {
+        let body = r#"{"error": {"code": "instance-operations/launch/insufficient-capacity", "message": "Not enough capacity to fulfill launch request.", "suggestion": "Choose an instance type with more availability, or try again later."}}"#;
+        let err = api_error(reqwest::StatusCode::BAD_REQUEST, body).to_string();
+        assert!(err.contains("Not enough capacity to fulfill launch request."));
+        assert!(err.contains("Suggestion: Choose an instance type"));
+        assert!(!err.contains('{'));
+    }
+
+    #[test]
+    fn api_error_falls_back_to_raw_body() {
+        let err = api_error(reqwest::StatusCode::BAD_GATEWAY, "<html>bad gateway</html>").to_string();
+        assert!(err.contains("<html>bad gateway</html>"));
+    }
+
+    #[test]
+    fn instance_listing_leaves_out_terminated() {
+        let body = r#"{"data": [
+            {"id": "a", "ip": "198.51.100.2", "status": "active", "region": {"name": "us-east-1"}},
+            {"id": "b", "status": "booting"},
+            {"id": "c", "ip": "198.51.100.3", "status": "terminated"}
+        ]}"#;
+        let instances: InstancesResponse = serde_json::from_str(body).unwrap();
+        let existing: Vec<&str> = instances.data.iter()
+            .filter(|i| i.exists())
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(existing, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn launch_without_instance_id_keeps_the_raw_body() {
+        assert_eq!(launched_instance_id(r#"{"data": {"instance_ids": ["i-1"]}}"#).unwrap(), "i-1");
+
+        let err = launched_instance_id(r#"{"data": {"instances": []}}"#).unwrap_err();
+        assert!(err.message().contains("no instance ID"));
+        assert!(err.message().contains(r#"raw: {"data": {"instances": []}}"#));
+
+        let err = launched_instance_id("not json").unwrap_err();
+        assert!(err.message().contains("the launch request"));
+        assert!(err.message().contains("raw: not json"));
+    }
+
+    #[test]
+    fn instance_types_without_price_or_specs_still_parse() {
+        let body = r#"{"data": {"gpu_1x_new": {"instance_type": {"description": "new"}}}}"#;
+        let types: InstanceTypesResponse = parse_response("the instance-types listing", body).unwrap();
+        let info = &types.data["gpu_1x_new"].instance_type;
+        assert_eq!(info.price_cents_per_hour, None);
+        assert!(types.data["gpu_1x_new"].regions_with_capacity_available.is_empty());
+    }
+
+    #[test]
+    fn capacity_filter_drops_types_without_regions() {
+        let mut listing: serde_json::Value = serde_json::from_str(r#"{"data": {
+            "gpu_1x_a10": {"regions_with_capacity_available": [{"name": "us-east-1"}]},
+            "gpu_8x_h100": {"regions_with_capacity_available": []},
+            "gpu_1x_new": {}
+        }}"#).unwrap();
+        retain_types_with_capacity(&mut listing);
+        let names: Vec<&String> = listing["data"].as_object().unwrap().keys().collect();
+        assert_eq!(names, vec!["gpu_1x_a10"]);
+    }
+
+    #[test]
+    fn region_names_include_new_regions_from_the_listing() {
+        let body = r#"{"data": {
+            "gpu_1x_a10": {"instance_type": {}, "regions_with_capacity_available": [{"name": "us-east-1"}, {"name": "us-new-9"}]}
+        }}"#;
+        let regions = region_names(parse_response("the instance-types listing", body).unwrap());
+        assert!(regions.contains(&"us-new-9".to_string()));
+        assert_eq!(regions.iter().filter(|r| *r == "us-east-1").count(), 1);
+        assert!(regions.contains(&"us-west-1".to_string()));
+    }
+
+    #[test]
+    fn launches_are_not_retried_after_server_errors() {
+        use reqwest::StatusCode;
+        assert!(Retry::Transient.status(StatusCode::BAD_GATEWAY));
+        assert!(Retry::Transient.status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!Retry::Transient.status(StatusCode::BAD_REQUEST));
+        assert!(Retry::Unprocessed.status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!Retry::Unprocessed.status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    #[test]
+    fn retry_delay_doubles_within_jitter() {
+        for retry in 1..=4 {
+            let full = RETRY_BASE_DELAY * 2u32.pow(retry - 1);
+            let delay = retry_delay(retry);
+            assert!(delay >= full / 2 && delay <= full, "retry {}: {:?}", retry, delay);
         }
     }
+
+    #[test]
+    fn gpu_memory_from_description() {
+        assert_eq!(parse_gpu_memory_gb("A100 (40 GB SXM4)"), Some(40));
+        assert_eq!(parse_gpu_memory_gb("GH200 (96 GB)"), Some(96));
+        assert_eq!(parse_gpu_memory_gb("RTX 6000 (24GB)"), Some(24));
+        assert_eq!(parse_gpu_memory_gb("Tesla V100"), None);
+        assert_eq!(parse_gpu_memory_gb(""), None);
+    }
+
+    #[test]
+    fn byo_key_name_ignores_the_comment() {
+        let name = byo_key_name("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHk+q/9Xv2abcdEFGH me@laptop");
+        assert_eq!(name, "gml-9Xv2abcdEFGH");
+        assert_eq!(byo_key_name("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHk+q/9Xv2abcdEFGH"), name);
+        assert_eq!(
+            key_material("ssh-ed25519 AAAA me@laptop"),
+            key_material("ssh-ed25519 AAAA other@ci")
+        );
+    }
+
+    #[test]
+    fn dry_run_describes_the_launch_payload() {
+        let lambda = Lambda::new(
+            "key".to_string(),
+            vec!["laptop".to_string(), "ci".to_string()],
+            "us-east-1".to_string(),
+            BootPolling::default(),
+            1,
+        );
+        let request = NodeRequest {
+            instance_type: "gpu_1x_a10".to_string(),
+            region: None,
+            zone: None,
+            subnet: None,
+            disk_gb: None,
+            public_key: None,
+            on_status: None,
+            on_launched: None,
+        };
+        let description = lambda.describe_launch(&request).unwrap();
+        assert!(description.starts_with("POST https://cloud.lambda.ai/api/v1/instance-operations/launch\n"));
+        assert!(description.contains(r#""region_name": "us-east-1""#));
+        assert!(description.contains(r#""instance_type_name": "gpu_1x_a10""#));
+        assert!(description.contains(r#""laptop""#));
+        assert!(description.contains(r#""ci""#));
+
+        let byo = NodeRequest { public_key: Some("ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIHk+q/9Xv2abcdEFGH".to_string()), ..request };
+        let description = lambda.describe_launch(&byo).unwrap();
+        assert!(description.starts_with("POST https://cloud.lambda.ai/api/v1/ssh-keys"));
+        assert!(description.contains(r#""gml-9Xv2abcdEFGH""#));
+
+        let elsewhere = NodeRequest { region: Some("us-west-1".to_string()), public_key: None, ..byo };
+        let description = lambda.describe_launch(&elsewhere).unwrap();
+        assert!(description.contains(r#""region_name": "us-west-1""#));
+    }
+
+    #[test]
+    fn missing_ssh_key_lists_and_suggests_keys() {
+        let keys = vec!["my-laptop".to_string(), "ci".to_string()];
+        let message = missing_ssh_key_message("my-labtop", &keys);
+        assert!(message.contains("available: [my-laptop, ci]"));
+        assert!(message.contains("Did you mean: my-laptop?"));
+    }
 }
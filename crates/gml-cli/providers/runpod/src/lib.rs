@@ -0,0 +1,327 @@
+use async_trait::async_trait;
+use gml_core::{BootPolling, CostEstimate, NodeProvider, NodeRequest, NodeDetails, NodeType, StatusCallback};
+use gml_core::error::GmlError;
+use gml_core::ssh;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::json;
+
+const GRAPHQL_URL: &str = "https://api.runpod.io/graphql";
+/// RunPod's PyTorch image runs sshd and authorizes the key passed in `PUBLIC_KEY`
+const DEFAULT_IMAGE: &str = "runpod/pytorch:2.4.0-py3.11-cuda12.4.1-devel-ubuntu22.04";
+/// Container disk used when the request doesn't ask for a size
+const DEFAULT_CONTAINER_DISK_GB: u32 = 50;
+const DEPLOY_MUTATION: &str =
+    "mutation Deploy($input: PodFindAndDeployOnDemandInput) { podFindAndDeployOnDemand(input: $input) { id } }";
+
+pub struct RunPod {
+    pub api_key: String,
+    /// `[gml] ssh-public-key` from config (same resolution as `gml_core::ssh::get_ssh_public_key`).
+    gml_ssh_public_key: Option<String>,
+    boot_polling: BootPolling,
+}
+
+#[derive(Deserialize)]
+struct GraphQlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Deserialize)]
+struct GraphQlError {
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct DeployData {
+    #[serde(rename = "podFindAndDeployOnDemand")]
+    pod: DeployedPod,
+}
+
+#[derive(Deserialize)]
+struct DeployedPod {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct PodData {
+    pod: Option<Pod>,
+}
+
+#[derive(Deserialize)]
+struct Pod {
+    #[serde(rename = "desiredStatus")]
+    desired_status: String,
+    runtime: Option<PodRuntime>,
+}
+
+#[derive(Deserialize)]
+struct PodRuntime {
+    #[serde(default)]
+    ports: Option<Vec<PodPort>>,
+}
+
+#[derive(Deserialize)]
+struct PodPort {
+    ip: Option<String>,
+    #[serde(rename = "isIpPublic")]
+    is_ip_public: bool,
+    #[serde(rename = "privatePort")]
+    private_port: u16,
+    #[serde(rename = "publicPort")]
+    public_port: Option<u16>,
+}
+
+#[derive(Deserialize)]
+struct GpuTypesData {
+    #[serde(rename = "gpuTypes")]
+    gpu_types: Vec<GpuType>,
+}
+
+#[derive(Deserialize)]
+struct GpuType {
+    id: String,
+    #[serde(rename = "displayName")]
+    display_name: String,
+    #[serde(rename = "memoryInGb")]
+    memory_in_gb: Option<u32>,
+    #[serde(rename = "lowestPrice")]
+    lowest_price: Option<LowestPrice>,
+}
+
+#[derive(Deserialize)]
+struct LowestPrice {
+    /// On-demand price per hour in USD; `null` when no machine has this GPU free
+    #[serde(rename = "uninterruptablePrice")]
+    uninterruptable_price: Option<f64>,
+}
+
+const GPU_TYPES_QUERY: &str = "query GpuTypes { gpuTypes { id displayName memoryInGb \
+    lowestPrice(input: { gpuCount: 1 }) { uninterruptablePrice } } }";
+
+fn graphql_error(errors: &[GraphQlError]) -> GmlError {
+    let messages: Vec<&str> = errors.iter().map(|e| e.message.as_str()).collect();
+    GmlError::provider(format!("RunPod API error: {}", messages.join("; ")))
+}
+
+/// Public address and port that reach the pod's sshd, once the pod has been given one
+fn ssh_endpoint(runtime: &PodRuntime) -> Option<(String, u16)> {
+    runtime.ports.as_ref()?
+        .iter()
+        .filter(|port| port.private_port == 22 && port.is_ip_public)
+        .find_map(|port| Some((port.ip.clone()?, port.public_port?)))
+}
+
+#[async_trait]
+impl NodeProvider for RunPod {
+    async fn start_node(&self, request: NodeRequest) -> Result<NodeDetails, GmlError> {
+        let variables = self.deploy_variables(&request)?;
+        let deployed: DeployData = self.graphql(DEPLOY_MUTATION, variables).await?;
+
+        let pod_id = deployed.pod.id;
+        if let Some(callback) = &request.on_launched {
+            callback(&pod_id, request.region.as_deref());
+        }
+
+        let (ip, port) = self.get_ssh_endpoint(&pod_id, request.on_status.as_ref()).await?;
+        Ok(NodeDetails {
+            ip,
+            id: pod_id,
+            zone: None,
+            ssh_port: Some(port),
+            status: Some("active".to_string()),
+        })
+    }
+
+    fn describe_launch(&self, request: &NodeRequest) -> Result<String, GmlError> {
+        Ok(format!(
+            "POST {}\n{}\n{}",
+            GRAPHQL_URL,
+            DEPLOY_MUTATION,
+            serde_json::to_string_pretty(&self.deploy_variables(request)?)?
+        ))
+    }
+
+    async fn stop_node(&self, details: NodeDetails) -> Result<NodeDetails, GmlError> {
+        let _: serde_json::Value = self.graphql(
+            "mutation Terminate($input: PodTerminateInput!) { podTerminate(input: $input) }",
+            json!({ "input": { "podId": details.id } }),
+        ).await?;
+        Ok(details)
+    }
+
+    /// RunPod images log in as root
+    async fn get_user(&self) -> Result<String, GmlError> {
+        Ok("root".to_string())
+    }
+
+    async fn get_node_types(&self, available_only: bool) -> Result<String, GmlError> {
+        let mut value: serde_json::Value = self.graphql(GPU_TYPES_QUERY, json!({})).await?;
+        // As in `list_node_types`, an unpriced type has no machine free
+        if available_only && let Some(serde_json::Value::Array(gpu_types)) = value.get_mut("gpuTypes") {
+            gpu_types.retain(|gpu| !gpu["lowestPrice"]["uninterruptablePrice"].is_null());
+        }
+        serde_json::to_string_pretty(&value)
+            .map_err(|e| GmlError::from(format!("Failed to pretty print JSON: {}", e)))
+    }
+
+    async fn list_node_types(&self) -> Result<Vec<NodeType>, GmlError> {
+        let data: GpuTypesData = self.graphql(GPU_TYPES_QUERY, json!({})).await?;
+        Ok(data.gpu_types.into_iter().map(|gpu| {
+            let hourly = gpu.lowest_price.and_then(|price| price.uninterruptable_price);
+            NodeType {
+                name: gpu.id,
+                description: gpu.display_name,
+                accelerator_count: 1,
+                memory_gb: gpu.memory_in_gb,
+                price: hourly.map(|hourly| CostEstimate { hourly, currency: "USD".to_string() }),
+                // RunPod doesn't list capacity by region, but an unpriced type has no machine free
+                regions: if hourly.is_some() { None } else { Some(Vec::new()) },
+            }
+        }).collect())
+    }
+
+    async fn estimate_cost(&self, request: &NodeRequest) -> Result<CostEstimate, GmlError> {
+        let node_types = self.list_node_types().await?;
+        let node_type = node_types.into_iter()
+            .find(|t| t.name == request.instance_type)
+            .ok_or_else(|| GmlError::from(format!("Unknown instance type '{}'", request.instance_type)))?;
+        node_type.price
+            .ok_or_else(|| GmlError::from(format!("No price listed for '{}'; it may have no capacity", request.instance_type)))
+    }
+}
+
+impl RunPod {
+    pub fn new(api_key: String, gml_ssh_public_key: Option<String>, boot_polling: BootPolling) -> RunPod {
+        RunPod {
+            api_key,
+            gml_ssh_public_key,
+            boot_polling,
+        }
+    }
+
+    /// Variables for the deploy mutation, authorizing the request's key or else the configured one
+    fn deploy_variables(&self, request: &NodeRequest) -> Result<serde_json::Value, GmlError> {
+        let public_key = match &request.public_key {
+            Some(public_key) => public_key.clone(),
+            None => {
+                let key_path = ssh::get_ssh_public_key(self.gml_ssh_public_key.as_deref())?;
+                ssh::read_ssh_public_key_line(&key_path)?
+            }
+        };
+
+        // RunPod picks the machine, so request.zone and request.subnet are ignored
+        Ok(json!({
+            "input": {
+                "cloudType": "ALL",
+                "gpuCount": 1,
+                "gpuTypeId": request.instance_type,
+                "name": "gml",
+                "imageName": DEFAULT_IMAGE,
+                "containerDiskInGb": request.disk_gb.unwrap_or(DEFAULT_CONTAINER_DISK_GB),
+                "ports": "22/tcp",
+                "supportPublicIp": true,
+                "startSsh": true,
+                "env": [{ "key": "PUBLIC_KEY", "value": public_key }],
+            }
+        }))
+    }
+
+    /// Run a GraphQL operation and return its `data`, turning GraphQL errors into provider errors
+    async fn graphql<T: DeserializeOwned>(&self, query: &str, variables: serde_json::Value) -> Result<T, GmlError> {
+        let client = reqwest::Client::new();
+
+        let response = client.post(GRAPHQL_URL)
+            .bearer_auth(&self.api_key)
+            .header("accept", "application/json")
+            .json(&json!({ "query": query, "variables": variables }))
+            .send()
+            .await?;
+
+        let status = response.status();
+        let response_text = response.text().await?;
+
+        // GraphQL errors can come with any status, so look for them before checking it
+        let parsed: Result<GraphQlResponse<T>, _> = serde_json::from_str(&response_text);
+        match parsed {
+            Ok(response) if !response.errors.is_empty() => Err(graphql_error(&response.errors)),
+            Ok(GraphQlResponse { data: Some(data), .. }) if status.is_success() => Ok(data),
+            _ if !status.is_success() => Err(GmlError::Provider {
+                status: Some(status.as_u16()),
+                body: format!("API Error ({}): {}", status, response_text),
+            }),
+            Ok(_) => Err(GmlError::from(format!("No data in response: {}", response_text))),
+            Err(e) => Err(GmlError::from(format!("Failed to parse response: {} - Response body: {}", e, response_text))),
+        }
+    }
+
+    /// Poll the pod until its sshd is reachable on a public IP
+    async fn get_ssh_endpoint(&self, pod_id: &str, on_status: Option<&StatusCallback>) -> Result<(String, u16), GmlError> {
+        let max_attempts = self.boot_polling.attempts();
+        let mut last_status: Option<String> = None;
+
+        for attempt in 1..=max_attempts {
+            let data: PodData = self.graphql(
+                "query Pod($input: PodFilter) { pod(input: $input) { desiredStatus runtime { ports { ip isIpPublic privatePort publicPort } } } }",
+                json!({ "input": { "podId": pod_id } }),
+            ).await?;
+            let pod = data.pod
+                .ok_or_else(|| GmlError::from(format!("Pod {} not found", pod_id)))?;
+
+            // The runtime only appears once the image is pulled and the container has started
+            let status = match &pod.runtime {
+                Some(_) => "running".to_string(),
+                None => pod.desired_status.to_lowercase(),
+            };
+            if last_status.as_deref() != Some(status.as_str()) {
+                if let Some(callback) = on_status {
+                    callback(&status);
+                }
+                last_status = Some(status);
+            }
+
+            if let Some(endpoint) = pod.runtime.as_ref().and_then(ssh_endpoint) {
+                return Ok(endpoint);
+            }
+
+            if attempt < max_attempts {
+                tokio::time::sleep(self.boot_polling.interval).await;
+            }
+        }
+
+        Err(GmlError::from(format!(
+            "Pod {} did not expose SSH on a public IP within {}s. Please try again later, \
+             or raise boot-timeout under [runpod] in ~/.gml/config.toml.",
+            pod_id, self.boot_polling.timeout.as_secs()
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ssh_endpoint_uses_public_mapping_of_port_22() {
+        let runtime: PodRuntime = serde_json::from_str(r#"{"ports": [
+            {"ip": "10.0.0.5", "isIpPublic": false, "privatePort": 22, "publicPort": 22},
+            {"ip": "194.68.245.9", "isIpPublic": true, "privatePort": 8888, "publicPort": 22001},
+            {"ip": "194.68.245.9", "isIpPublic": true, "privatePort": 22, "publicPort": 22046}
+        ]}"#).unwrap();
+        assert_eq!(ssh_endpoint(&runtime), Some(("194.68.245.9".to_string(), 22046)));
+
+        let starting: PodRuntime = serde_json::from_str(r#"{"ports": null}"#).unwrap();
+        assert_eq!(ssh_endpoint(&starting), None);
+    }
+
+    #[test]
+    fn graphql_errors_are_joined() {
+        let response: GraphQlResponse<DeployData> = serde_json::from_str(
+            r#"{"errors": [{"message": "There are no longer any instances available with the requested specifications."}], "data": null}"#,
+        ).unwrap();
+        let err = graphql_error(&response.errors).to_string();
+        assert!(err.contains("no longer any instances available"));
+        assert!(!err.contains('{'));
+    }
+}
@@ -1,10 +1,43 @@
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition;
 use kube::CustomResourceExt;
 use serde_yaml;
+use std::path::{Path, PathBuf};
 
 mod pytorch_train_job;
 use pytorch_train_job::PyTorchTrainJob;
 
+const DEFAULT_OUTPUT: &str = "pytorch_train_job_crd.yaml";
+
+/// Usage: crdgen [OUTPUT_PATH]
 fn main() {
-    let yaml = serde_yaml::to_string(&PyTorchTrainJob::crd()).unwrap();
-    std::fs::write("pytorch_train_job_crd.yaml", yaml).unwrap();
-}
\ No newline at end of file
+    let output = std::env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from(DEFAULT_OUTPUT));
+
+    if let Err(e) = generate(&output) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+    println!("Wrote {}", output.display());
+}
+
+fn generate(output: &Path) -> Result<(), String> {
+    let crd = PyTorchTrainJob::crd();
+    let yaml = serde_yaml::to_string(&crd)
+        .map_err(|e| format!("Failed to serialize CRD: {}", e))?;
+
+    // Make sure what we write parses back into the same CRD before anything consumes it
+    let parsed: CustomResourceDefinition = serde_yaml::from_str(&yaml)
+        .map_err(|e| format!("Generated CRD is not valid YAML: {}", e))?;
+    if parsed != crd {
+        return Err("Generated CRD does not round-trip through YAML".to_string());
+    }
+
+    if let Some(parent) = output.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Failed to create directory {}: {}", parent.display(), e))?;
+    }
+    std::fs::write(output, yaml)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))
+}
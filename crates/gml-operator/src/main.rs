@@ -1,35 +1,216 @@
 use std::{sync::Arc, time::Duration};
 use futures::StreamExt;
+use k8s_openapi::api::batch::v1::Job;
+use k8s_openapi::api::core::v1::{Pod, Service};
 use kube::{
-    Api, Client, ResourceExt,
-    runtime::controller::{Action, Controller}
+    Api, Client, Resource, ResourceExt,
+    api::{DeleteParams, ListParams, Patch, PatchParams},
+    runtime::controller::{Action, Controller},
+    runtime::events::{Event as KubeEvent, EventType, Recorder, Reporter},
+    runtime::finalizer::{finalizer, Event},
 };
 use crate::pytorch_train_job::PyTorchTrainJob;
 
 mod pytorch_train_job;
+mod resources;
+
+/// Keeps a PyTorchTrainJob around until the resources it owns have been torn down
+const FINALIZER: &str = "gml.gerardosalazar.com/cleanup";
+
+/// Label put on every pod created for a job, holding the job's name
+pub const JOB_LABEL: &str = "gml.gerardosalazar.com/job";
 
 #[derive(thiserror::Error, Debug)]
-pub enum Error {}
+pub enum Error {
+    #[error("kube api error: {0}")]
+    Kube(#[from] kube::Error),
+    #[error("finalizer error: {0}")]
+    Finalizer(#[source] Box<kube::runtime::finalizer::Error<Error>>),
+    #[error("{0} has no namespace")]
+    MissingNamespace(String),
+}
+
+/// Name events are reported under, shown as the source in `kubectl describe`
+const REPORTER: &str = "gml-operator";
+
+/// Field manager for the server-side applies of the objects a job owns
+const FIELD_MANAGER: &str = "gml-operator";
+
+pub struct Context {
+    client: Client,
+    recorder: Recorder,
+}
+
+impl Context {
+    /// Attach an event to the job so it shows up in `kubectl describe`.
+    /// Failing to publish is logged rather than failing the reconcile.
+    async fn publish(&self, job: &PyTorchTrainJob, type_: EventType, reason: &str, action: &str, note: Option<String>) {
+        let event = KubeEvent {
+            type_,
+            reason: reason.to_string(),
+            note,
+            action: action.to_string(),
+            secondary: None,
+        };
+        if let Err(e) = self.recorder.publish(&event, &job.object_ref(&())).await {
+            eprintln!("failed to publish event {} for {}: {}", reason, job.name_any(), e);
+        }
+    }
+}
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 #[tokio::main]
 async fn main() -> Result<(), kube::Error> {
     let client = Client::try_default().await?;
-    let pytorch_train_jobs = Api::<PyTorchTrainJob>::all(client);
+    let (pytorch_train_jobs, jobs) = match watch_namespace() {
+        Some(namespace) => {
+            println!("watching PyTorchTrainJobs in namespace {}", namespace);
+            (
+                Api::<PyTorchTrainJob>::namespaced(client.clone(), &namespace),
+                Api::<Job>::namespaced(client.clone(), &namespace),
+            )
+        }
+        None => {
+            println!("watching PyTorchTrainJobs in all namespaces");
+            (Api::<PyTorchTrainJob>::all(client.clone()), Api::<Job>::all(client.clone()))
+        }
+    };
 
+    // Changes to the Jobs we created re-run the reconcile of their owner, which updates its status
     Controller::new(pytorch_train_jobs.clone(), Default::default())
-        .run(reconcile, error_policy, Arc::new(()))
+        .owns(jobs, Default::default())
+        .run(reconcile, error_policy, Arc::new(Context {
+            recorder: Recorder::new(client.clone(), Reporter::from(REPORTER)),
+            client,
+        }))
         .for_each(|_| futures::future::ready(()))
         .await;
     Ok(())
 }
 
-async fn reconcile(obj: Arc<PyTorchTrainJob>, ctx: Arc<()>) -> Result<Action> {
+/// Namespace to watch, from `--namespace <ns>` or the `WATCH_NAMESPACE` env var.
+/// Unset, empty or `*` means all namespaces.
+fn watch_namespace() -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let from_flag = args
+        .windows(2)
+        .find(|pair| pair[0] == "--namespace")
+        .map(|pair| pair[1].clone());
+
+    from_flag
+        .or_else(|| std::env::var("WATCH_NAMESPACE").ok())
+        .filter(|ns| !ns.is_empty() && ns != "*")
+}
+
+async fn reconcile(obj: Arc<PyTorchTrainJob>, ctx: Arc<Context>) -> Result<Action> {
     println!("reconcile request: {}", obj.name_any());
+    let namespace = obj.namespace().ok_or_else(|| Error::MissingNamespace(obj.name_any()))?;
+    let jobs = Api::<PyTorchTrainJob>::namespaced(ctx.client.clone(), &namespace);
+
+    // The finalizer is added on first sight; deletion then waits until cleanup succeeds
+    finalizer(&jobs, FINALIZER, obj, |event| async move {
+        match event {
+            Event::Apply(job) => apply(job, ctx).await,
+            Event::Cleanup(job) => cleanup(job, ctx).await,
+        }
+    })
+    .await
+    .map_err(|e| Error::Finalizer(Box::new(e)))
+}
+
+/// Create or update the headless service and training Job for `job`. Server-side apply makes
+/// this idempotent, so it is safe to run on every reconcile.
+async fn apply(job: Arc<PyTorchTrainJob>, ctx: Arc<Context>) -> Result<Action> {
+    let namespace = job.namespace().ok_or_else(|| Error::MissingNamespace(job.name_any()))?;
+    if job.spec.nodes < 1 {
+        // Retrying can't help, so wait for the spec to be edited
+        let message = format!("spec.nodes must be at least 1, got {}", job.spec.nodes);
+        ctx.publish(&job, EventType::Warning, "InvalidSpec", "Reconcile", Some(message)).await;
+        return Ok(Action::await_change());
+    }
+
+    let params = PatchParams::apply(FIELD_MANAGER).force();
+    let services = Api::<Service>::namespaced(ctx.client.clone(), &namespace);
+    let jobs = Api::<Job>::namespaced(ctx.client.clone(), &namespace);
+    let name = job.name_any();
+    let applied = async {
+        let existing_service = services.get_opt(&name).await?;
+        let existing_job = jobs.get_opt(&name).await?;
+        services.patch(&name, &params, &Patch::Apply(resources::headless_service(&job))).await?;
+        let training_job = jobs.patch(&name, &params, &Patch::Apply(resources::training_job(&job))).await?;
+        // Only spec changes bump a Job's generation, so pods progressing don't count as a change
+        let changed = existing_service.is_none()
+            || existing_job.is_none_or(|existing| existing.metadata.generation != training_job.metadata.generation);
+        Ok::<_, kube::Error>((training_job, changed))
+    };
+    let (training_job, changed) = match applied.await {
+        Ok(applied) => applied,
+        Err(e) => {
+            ctx.publish(&job, EventType::Warning, "ApplyFailed", "CreateJob", Some(e.to_string())).await;
+            return Err(e.into());
+        }
+    };
+
+    let status = resources::train_job_status(training_job.status.as_ref());
+    if job.status.as_ref() != Some(&status) {
+        let train_jobs = Api::<PyTorchTrainJob>::namespaced(ctx.client.clone(), &namespace);
+        let patch = serde_json::json!({ "status": status });
+        train_jobs.patch_status(&job.name_any(), &PatchParams::default(), &Patch::Merge(&patch)).await?;
+        if job.status.as_ref().map(|s| s.phase.as_str()) != Some(status.phase.as_str()) {
+            ctx.publish(&job, EventType::Normal, &status.phase, "UpdateStatus", None).await;
+        }
+    }
+
+    // Reconciles that changed nothing, such as the hourly requeue, would only fill the event list
+    if changed {
+        let note = format!("Job {} runs {} pod(s) of {}", name, job.spec.nodes, job.spec.image);
+        ctx.publish(&job, EventType::Normal, "Reconciled", "CreateJob", Some(note)).await;
+    }
     Ok(Action::requeue(Duration::from_secs(3600)))
 }
 
-fn error_policy(_object: Arc<PyTorchTrainJob>, _err: &Error, _ctx: Arc<()>) -> Action {
+/// Tear down everything the job owns. Must be idempotent and tolerate `apply` never having run.
+async fn cleanup(job: Arc<PyTorchTrainJob>, ctx: Arc<Context>) -> Result<Action> {
+    let namespace = job.namespace().ok_or_else(|| Error::MissingNamespace(job.name_any()))?;
+    let pods = Api::<Pod>::namespaced(ctx.client.clone(), &namespace);
+    let selector = format!("{}={}", JOB_LABEL, job.name_any());
+
+    println!("cleaning up pods for {}", job.name_any());
+    ctx.publish(&job, EventType::Normal, "CleanupStarted", "DeletePods", Some(format!("Deleting pods matching {}", selector))).await;
+    // Delete the Job first, or its controller would replace the pods deleted below
+    let jobs = Api::<Job>::namespaced(ctx.client.clone(), &namespace);
+    if let Err(e) = delete_if_present(&jobs, &job.name_any()).await {
+        ctx.publish(&job, EventType::Warning, "CleanupFailed", "DeleteJob", Some(e.to_string())).await;
+        return Err(e.into());
+    }
+    // Owner references would take the service with the job too, but not if garbage collection lags
+    let services = Api::<Service>::namespaced(ctx.client.clone(), &namespace);
+    if let Err(e) = delete_if_present(&services, &job.name_any()).await {
+        ctx.publish(&job, EventType::Warning, "CleanupFailed", "DeleteService", Some(e.to_string())).await;
+        return Err(e.into());
+    }
+    if let Err(e) = pods.delete_collection(&DeleteParams::default(), &ListParams::default().labels(&selector)).await {
+        ctx.publish(&job, EventType::Warning, "CleanupFailed", "DeletePods", Some(e.to_string())).await;
+        return Err(e.into());
+    }
+    ctx.publish(&job, EventType::Normal, "CleanedUp", "DeletePods", None).await;
+    Ok(Action::await_change())
+}
+
+/// Delete `name`, treating an object that is already gone as deleted
+async fn delete_if_present<K>(api: &Api<K>, name: &str) -> Result<(), kube::Error>
+where
+    K: Clone + serde::de::DeserializeOwned + std::fmt::Debug,
+{
+    match api.delete(name, &DeleteParams::background()).await {
+        Ok(_) => Ok(()),
+        Err(kube::Error::Api(status)) if status.is_not_found() => Ok(()),
+        Err(e) => Err(e),
+    }
+}
+
+fn error_policy(object: Arc<PyTorchTrainJob>, err: &Error, _ctx: Arc<Context>) -> Action {
+    eprintln!("reconcile failed for {}: {}", object.name_any(), err);
     Action::requeue(Duration::from_secs(5))
 }
@@ -4,7 +4,37 @@ use kube::CustomResource;
 
 #[derive(CustomResource, Deserialize, Serialize, Clone, Debug, JsonSchema)]
 #[kube(group="gml.gerardosalazar.com", version = "v1", kind = "PyTorchTrainJob", namespaced)]
+#[kube(status = "PyTorchTrainJobStatus")]
+#[kube(printcolumn = r#"{"name":"Phase", "type":"string", "jsonPath":".status.phase"}"#)]
+#[kube(printcolumn = r#"{"name":"Active", "type":"integer", "jsonPath":".status.active"}"#)]
+#[kube(printcolumn = r#"{"name":"Succeeded", "type":"integer", "jsonPath":".status.succeeded"}"#)]
+#[kube(printcolumn = r#"{"name":"Failed", "type":"integer", "jsonPath":".status.failed"}"#)]
+#[kube(printcolumn = r#"{"name":"Age", "type":"date", "jsonPath":".metadata.creationTimestamp"}"#)]
+#[serde(rename_all = "camelCase")]
 pub struct PyTorchTrainJobSpec {
-    image: String,
-    nodes: i32,
-}
\ No newline at end of file
+    /// Container image every training pod runs
+    pub image: String,
+    /// Number of training pods, which is also the `WORLD_SIZE`
+    pub nodes: i32,
+    /// GPUs to give each pod, as an `nvidia.com/gpu` limit. 0 requests none.
+    #[serde(default)]
+    pub gpus_per_node: i32,
+    /// CPU limit for each pod, as a Kubernetes quantity such as `8` or `500m`
+    #[serde(default)]
+    pub cpu: Option<String>,
+    /// Memory limit for each pod, as a Kubernetes quantity such as `64Gi`
+    #[serde(default)]
+    pub memory: Option<String>,
+}
+/// Progress of the job's training pods, mirrored from the Job the operator created for it
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, JsonSchema)]
+pub struct PyTorchTrainJobStatus {
+    /// `Pending`, `Running`, `Succeeded` or `Failed`
+    pub phase: String,
+    /// Training pods currently running
+    pub active: i32,
+    /// Training pods that exited successfully
+    pub succeeded: i32,
+    /// Training pods that failed
+    pub failed: i32,
+}
@@ -0,0 +1,172 @@
+//! The Kubernetes objects created for a PyTorchTrainJob.
+//!
+//! Each job gets an Indexed `Job` with one pod per node, and a headless `Service` of the same
+//! name so every pod is reachable as `<job>-<index>.<job>`. Pod 0 is the rendezvous master.
+//! Both are owned by the PyTorchTrainJob, so Kubernetes garbage collects them with it.
+
+use std::collections::BTreeMap;
+use k8s_openapi::api::batch::v1::{Job, JobSpec, JobStatus};
+use k8s_openapi::api::core::v1::{
+    Container, EnvVar, EnvVarSource, ObjectFieldSelector, PodSpec, PodTemplateSpec, ResourceRequirements, Service,
+    ServicePort, ServiceSpec,
+};
+use k8s_openapi::apimachinery::pkg::api::resource::Quantity;
+use kube::api::ObjectMeta;
+use kube::{Resource, ResourceExt};
+use crate::pytorch_train_job::{PyTorchTrainJob, PyTorchTrainJobStatus};
+use crate::JOB_LABEL;
+
+/// Port the master listens on for `torch.distributed` rendezvous
+pub const MASTER_PORT: i32 = 29500;
+
+/// Annotation the Job controller sets on each pod of an Indexed Job
+const COMPLETION_INDEX_ANNOTATION: &str = "batch.kubernetes.io/job-completion-index";
+
+/// Extended resource the NVIDIA device plugin advertises GPUs as
+const GPU_RESOURCE: &str = "nvidia.com/gpu";
+
+/// Metadata shared by everything created for `job`: same name and namespace, the job label,
+/// and `job` as the controlling owner
+fn owned_metadata(job: &PyTorchTrainJob) -> ObjectMeta {
+    ObjectMeta {
+        name: Some(job.name_any()),
+        namespace: job.namespace(),
+        labels: Some(job_labels(job)),
+        owner_references: job.controller_owner_ref(&()).map(|owner| vec![owner]),
+        ..Default::default()
+    }
+}
+
+fn job_labels(job: &PyTorchTrainJob) -> BTreeMap<String, String> {
+    BTreeMap::from([(JOB_LABEL.to_string(), job.name_any())])
+}
+
+/// Headless service that gives each training pod a stable DNS name
+pub fn headless_service(job: &PyTorchTrainJob) -> Service {
+    Service {
+        metadata: owned_metadata(job),
+        spec: Some(ServiceSpec {
+            cluster_ip: Some("None".to_string()),
+            selector: Some(job_labels(job)),
+            // Workers look up the master before it is ready
+            publish_not_ready_addresses: Some(true),
+            ports: Some(vec![ServicePort {
+                name: Some("rendezvous".to_string()),
+                port: MASTER_PORT,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Indexed Job running `spec.nodes` pods of `spec.image`, with the env vars `torch.distributed`
+/// reads. `RANK` is each pod's completion index.
+pub fn training_job(job: &PyTorchTrainJob) -> Job {
+    let name = job.name_any();
+    let env = vec![
+        env_value("MASTER_ADDR", format!("{}-0.{}", name, name)),
+        env_value("MASTER_PORT", MASTER_PORT.to_string()),
+        env_value("WORLD_SIZE", job.spec.nodes.to_string()),
+        EnvVar {
+            name: "RANK".to_string(),
+            value_from: Some(EnvVarSource {
+                field_ref: Some(ObjectFieldSelector {
+                    field_path: format!("metadata.annotations['{}']", COMPLETION_INDEX_ANNOTATION),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        },
+    ];
+
+    Job {
+        metadata: owned_metadata(job),
+        spec: Some(JobSpec {
+            completion_mode: Some("Indexed".to_string()),
+            completions: Some(job.spec.nodes),
+            parallelism: Some(job.spec.nodes),
+            // A distributed run can't continue with a member missing, so don't retry pods
+            backoff_limit: Some(0),
+            template: PodTemplateSpec {
+                metadata: Some(ObjectMeta {
+                    labels: Some(job_labels(job)),
+                    ..Default::default()
+                }),
+                spec: Some(PodSpec {
+                    // Pods of an Indexed Job are named <job>-<index>; with a subdomain that
+                    // becomes their DNS name under the headless service
+                    subdomain: Some(name),
+                    restart_policy: Some("Never".to_string()),
+                    containers: vec![Container {
+                        name: "pytorch".to_string(),
+                        image: Some(job.spec.image.clone()),
+                        env: Some(env),
+                        resources: resource_limits(job),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                }),
+            },
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// Container limits from `gpusPerNode`, `cpu` and `memory`; `None` when none of them are set.
+/// Kubernetes uses the limits as the requests too.
+fn resource_limits(job: &PyTorchTrainJob) -> Option<ResourceRequirements> {
+    let spec = &job.spec;
+    let mut limits = BTreeMap::new();
+    if spec.gpus_per_node > 0 {
+        limits.insert(GPU_RESOURCE.to_string(), Quantity(spec.gpus_per_node.to_string()));
+    }
+    if let Some(cpu) = &spec.cpu {
+        limits.insert("cpu".to_string(), Quantity(cpu.clone()));
+    }
+    if let Some(memory) = &spec.memory {
+        limits.insert("memory".to_string(), Quantity(memory.clone()));
+    }
+    (!limits.is_empty()).then(|| ResourceRequirements {
+        limits: Some(limits),
+        ..Default::default()
+    })
+}
+
+/// The job's status from its Job's pod counts and conditions. The phase is `Succeeded` or `Failed`
+/// once the Job has finished either way, `Running` while any pod runs, and `Pending` before that.
+pub fn train_job_status(status: Option<&JobStatus>) -> PyTorchTrainJobStatus {
+    let count = |field: Option<i32>| field.unwrap_or(0);
+    let Some(status) = status else {
+        return PyTorchTrainJobStatus { phase: "Pending".to_string(), ..Default::default() };
+    };
+    let finished_as = |type_: &str| {
+        status.conditions.iter().flatten().any(|c| c.type_ == type_ && c.status == "True")
+    };
+    let phase = if finished_as("Complete") {
+        "Succeeded"
+    } else if finished_as("Failed") {
+        "Failed"
+    } else if count(status.active) > 0 {
+        "Running"
+    } else {
+        "Pending"
+    };
+    PyTorchTrainJobStatus {
+        phase: phase.to_string(),
+        active: count(status.active),
+        succeeded: count(status.succeeded),
+        failed: count(status.failed),
+    }
+}
+
+fn env_value(name: &str, value: String) -> EnvVar {
+    EnvVar {
+        name: name.to_string(),
+        value: Some(value),
+        ..Default::default()
+    }
+}